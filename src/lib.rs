@@ -25,6 +25,14 @@ extern crate url_serde;
 
 extern crate dirs;
 
+extern crate argon2;
+extern crate chacha20poly1305;
+extern crate zeroize;
+
+extern crate caseless;
+extern crate rust_stemmers;
+extern crate unicode_normalization;
+
 #[macro_use]
 extern crate failure;
 #[macro_use]
@@ -39,8 +47,6 @@ pub mod pinboard;
 pub use crate::pinboard::{Pin, PinBuilder, Pinboard, Tag};
 
 // TODO: make get_api_response return reqwest::Response so we can use serde_json::from_read
-// TODO: Properly escape search queries that are used in regex for fuzzy option. <06-02-18, Hamid>
-//       Some special chars to escape: (  ) | ? * + [  ]
 // TODO: Fix tests so we don't have to pass --test-threads=1. It seems issue is related to
 //       multithread access to cache files as some tests maybe
 //       deleting/updating while others reading.
@@ -0,0 +1,199 @@
+//! Tags meaning the same thing, e.g. `js`, `javascript` and `JavaScript`.
+//!
+//! Aliases are kept in the cache folder. Each group has a canonical tag that its aliases can
+//! be renamed to, and searches in tags look for all the tags of a group at once. Tags of a
+//! group are compared like tags are, ignoring case and Unicode normalization form.
+use std::collections::HashMap;
+
+use super::normalize::{self, normalize};
+use super::tag::{Tag, TagFreq};
+
+/// A canonical tag and its aliases.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TagAlias {
+    pub canonical: String,
+    pub aliases: Vec<String>,
+}
+
+impl TagAlias {
+    pub fn new<S: Into<String>>(canonical: S, aliases: Vec<String>) -> Self {
+        TagAlias {
+            canonical: canonical.into(),
+            aliases,
+        }
+    }
+
+    /// The canonical tag followed by its aliases.
+    pub fn tags(&self) -> impl Iterator<Item = &str> {
+        Some(self.canonical.as_str())
+            .into_iter()
+            .chain(self.aliases.iter().map(String::as_str))
+    }
+
+    /// Returns true if `tag` is the canonical tag or one of its aliases.
+    pub fn contains(&self, tag: &str) -> bool {
+        self.tags().any(|t| normalize::eq(t, tag))
+    }
+}
+
+/// Makes `alias` an alias of `canonical`, removing it from any other group. Returns false if
+/// it already was one.
+pub fn add(groups: &mut Vec<TagAlias>, alias: &str, canonical: &str) -> bool {
+    if groups
+        .iter()
+        .any(|g| normalize::eq(&g.canonical, canonical) && g.contains(alias))
+    {
+        return false;
+    }
+    remove(groups, alias);
+    match groups
+        .iter_mut()
+        .find(|g| normalize::eq(&g.canonical, canonical))
+    {
+        Some(group) => group.aliases.push(alias.to_string()),
+        None => groups.push(TagAlias::new(canonical, vec![alias.to_string()])),
+    }
+    true
+}
+
+/// Removes `alias` from its group, and groups left without aliases. Returns false if it
+/// wasn't an alias.
+pub fn remove(groups: &mut Vec<TagAlias>, alias: &str) -> bool {
+    let mut removed = false;
+    for group in groups.iter_mut() {
+        let count = group.aliases.len();
+        group.aliases.retain(|a| !normalize::eq(a, alias));
+        removed |= group.aliases.len() != count;
+    }
+    groups.retain(|g| !g.aliases.is_empty());
+    removed
+}
+
+/// Normalized tags of the group `query` belongs to, other than `query` itself. `query` must
+/// already be normalized.
+pub fn synonyms(groups: &[TagAlias], query: &str, strip_diacritics: bool) -> Vec<String> {
+    groups
+        .iter()
+        .filter(|g| g.tags().any(|t| normalize(t, strip_diacritics) == query))
+        .flat_map(|g| g.tags().map(|t| normalize(t, strip_diacritics)))
+        .fold(Vec::new(), |mut synonyms, t| {
+            if t != query && !synonyms.contains(&t) {
+                synonyms.push(t);
+            }
+            synonyms
+        })
+}
+
+/// The canonical form of `tag`, which is `tag` itself if it has no alias.
+pub fn canonical<'a>(groups: &'a [TagAlias], tag: &'a str) -> &'a str {
+    groups
+        .iter()
+        .find(|g| g.contains(tag))
+        .map_or(tag, |g| g.canonical.as_str())
+}
+
+/// `tags` with aliases replaced by their canonical tag, adding up their frequencies.
+/// Tags keep the position of the first of their group.
+pub fn merge(groups: &[TagAlias], tags: &[&Tag]) -> Vec<Tag> {
+    let mut merged: Vec<Tag> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for tag in tags {
+        let name = canonical(groups, &tag.0);
+        let count = match tag.1 {
+            TagFreq::Used(n) => n,
+            _ => 0,
+        };
+        match positions.get(&normalize(name, false)) {
+            Some(&i) => {
+                if let TagFreq::Used(ref mut n) = merged[i].1 {
+                    *n += count;
+                }
+            }
+            None => {
+                positions.insert(normalize(name, false), merged.len());
+                merged.push(Tag(name.to_string(), TagFreq::Used(count)));
+            }
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger;
+
+    fn groups() -> Vec<TagAlias> {
+        let mut groups = Vec::new();
+        assert!(add(&mut groups, "js", "javascript"));
+        assert!(add(&mut groups, "ECMAScript", "javascript"));
+        assert!(add(&mut groups, "golang", "go"));
+        groups
+    }
+
+    #[test]
+    fn adds_and_removes() {
+        let _ = env_logger::try_init();
+        debug!("adds_and_removes: starting");
+        let mut groups = groups();
+        assert_eq!(
+            vec![
+                TagAlias::new("javascript", vec!["js".into(), "ECMAScript".into()]),
+                TagAlias::new("go", vec!["golang".into()]),
+            ],
+            groups
+        );
+        assert!(!add(&mut groups, "JS", "JAVASCRIPT"));
+        assert!(!add(&mut groups, "JavaScript", "javascript"));
+
+        // Moves to another group
+        assert!(add(&mut groups, "js", "typescript"));
+        assert_eq!(vec!["ECMAScript"], groups[0].aliases);
+        assert_eq!(TagAlias::new("typescript", vec!["js".into()]), groups[2]);
+
+        assert!(remove(&mut groups, "GOLANG"));
+        assert!(!remove(&mut groups, "golang"));
+        assert_eq!(2, groups.len());
+    }
+
+    #[test]
+    fn finds_synonyms() {
+        let _ = env_logger::try_init();
+        debug!("finds_synonyms: starting");
+        let groups = groups();
+        assert_eq!(
+            vec!["javascript", "ecmascript"],
+            synonyms(&groups, "js", false)
+        );
+        assert_eq!(vec!["js", "ecmascript"], synonyms(&groups, "javascript", false));
+        assert!(synonyms(&groups, "rust", false).is_empty());
+
+        assert_eq!("javascript", canonical(&groups, "JS"));
+        assert_eq!("rust", canonical(&groups, "rust"));
+    }
+
+    #[test]
+    fn merges_frequencies() {
+        let _ = env_logger::try_init();
+        debug!("merges_frequencies: starting");
+        let groups = groups();
+        let tags = vec![
+            Tag::new("rust".into(), 4),
+            Tag::new("ECMAScript".into(), 2),
+            Tag::new("golang".into(), 1),
+            Tag::new("js".into(), 3),
+        ];
+        let merged = merge(&groups, &tags.iter().collect::<Vec<&Tag>>());
+        assert_eq!(
+            vec![
+                ("rust", TagFreq::Used(4)),
+                ("javascript", TagFreq::Used(5)),
+                ("go", TagFreq::Used(1)),
+            ],
+            merged
+                .iter()
+                .map(|t| (t.0.as_str(), t.1.clone()))
+                .collect::<Vec<(&str, TagFreq)>>()
+        );
+    }
+}
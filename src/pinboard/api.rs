@@ -14,7 +14,7 @@ use std::io::Read;
 use failure::{err_msg, Error};
 
 use super::pin::Pin;
-use super::tag::Tag;
+use super::tag::{Tag, TagFreq, TagSuggestions};
 
 #[cfg(not(test))]
 const BASE_URL: &str = "https://api.pinboard.in/v1";
@@ -114,38 +114,52 @@ impl<'api, 'pin> Api<'api> {
         Ok(pins)
     }
 
-    pub fn suggest_tags<T: AsRef<str>>(&self, url: T) -> Result<Vec<String>, Error> {
+    /// Tags suggested by the server for `url`, all marked with [`TagFreq::Popular`].
+    pub fn suggest_tags<T: AsRef<str>>(&self, url: T) -> Result<TagSuggestions, Error> {
         debug!("suggest_tags: starting.");
         let mut query = HashMap::new();
         query.insert("url", url.as_ref());
 
-        Ok(self
+        let items = self
             .get_api_response([BASE_URL, "/posts/suggest"].concat().as_str(), query)
             .and_then(|res| {
                 serde_json::from_str::<Vec<serde_json::Value>>(&res)
                     .map_err(|e| ApiError::SerdeError(e.to_string()).into())
-            })?
-            .into_iter()
-            .find(|item| !item["popular"].is_null())
-            .map(|item| {
-                item["popular"]
-                    .as_array()
-                    .unwrap_or(&vec![json!([])])
-                    .iter()
-                    .map(|v| v.as_str().unwrap_or("").to_string())
-                    .collect::<Vec<String>>()
-            })
-            .ok_or_else(|| {
-                ApiError::UnrecognizedResponse(
-                    "Unrecognized response from API: posts/suggest".to_string(),
-                )
-            })?)
+            })?;
+
+        let tags = |key: &str| {
+            items
+                .iter()
+                .find(|item| !item[key].is_null())
+                .map(|item| {
+                    item[key]
+                        .as_array()
+                        .map(|v| v.as_slice())
+                        .unwrap_or(&[])
+                        .iter()
+                        .filter_map(|v| v.as_str())
+                        .map(|t| Tag(t.to_string(), TagFreq::Popular))
+                        .collect::<Vec<Tag>>()
+                })
+        };
+        match (tags("popular"), tags("recommended")) {
+            (None, None) => Err(ApiError::UnrecognizedResponse(
+                "Unrecognized response from API: posts/suggest".to_string(),
+            )
+            .into()),
+            (popular, recommended) => Ok(TagSuggestions {
+                popular: popular.unwrap_or_default(),
+                recommended: recommended.unwrap_or_default(),
+            }),
+        }
     }
 
     pub fn add_url(&self, p: Pin) -> Result<(), Error> {
         debug!("add_url: starting.");
         let url: &str = &p.url;
         let extended = &p.extended.unwrap_or_default();
+        // Keeps the creation time of existing pins when they are replaced.
+        let dt = &p.time.format("%Y-%m-%dT%H:%M:%SZ").to_string();
         let mut map = HashMap::new();
         debug!(" url: {}", url);
 
@@ -155,6 +169,7 @@ impl<'api, 'pin> Api<'api> {
         map.insert("toread", &p.toread);
         map.insert("extended", extended);
         map.insert("shared", &p.shared);
+        map.insert("dt", dt);
         map.insert("replace", "yes");
 
         debug!("Sending payload to: {}/posts/add\n\t{:?}", BASE_URL, map);
@@ -488,11 +503,18 @@ mod tests {
         );
         let api = Api::new(include_str!("api_token.txt"));
         let url = "http://blog.com/";
-        let res = api.suggest_tags(url);
+        let res = api.suggest_tags(url).expect("impossible");
+        let names = |tags: &[Tag]| tags.iter().map(|t| t.0.clone()).collect::<Vec<String>>();
+        assert_eq!(vec!["datetime", "library", "rust"], names(&res.popular));
         assert_eq!(
-            vec!["datetime", "library", "rust"],
-            res.expect("impossible")
+            vec!["datetime", "library", "programming", "rust"],
+            names(&res.recommended)
         );
+        assert!(res
+            .popular
+            .iter()
+            .chain(&res.recommended)
+            .all(|t| t.1 == TagFreq::Popular));
     }
 
     #[test]
@@ -0,0 +1,193 @@
+//! Tag changes applied to many bookmarks at once.
+//!
+//! Each changed bookmark is posted again with its new tags, so an edit of many bookmarks
+//! takes a while given the rate limit of the API. The edit is kept in the cache folder while
+//! it runs, so that it can be resumed if it is interrupted.
+use std::fmt;
+
+use super::normalize;
+use super::pin::{Pin, PinBuilder};
+
+/// A change to the tags of a bookmark.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum TagEdit {
+    /// Adds the tags the bookmark doesn't have yet, blank ones are ignored.
+    Add(Vec<String>),
+    /// Removes the tags.
+    Remove(Vec<String>),
+    /// Replaces the first tag by the second one, or removes it if the second one is blank.
+    Replace(String, String),
+}
+
+impl TagEdit {
+    fn apply(&self, tags: &mut Vec<String>) {
+        let has = |tags: &[String], tag: &str| tags.iter().any(|t| normalize::eq(t, tag));
+        match *self {
+            TagEdit::Add(ref added) => {
+                for tag in added.iter().flat_map(|t| t.split_whitespace()) {
+                    if !has(tags, tag) {
+                        tags.push(tag.to_string());
+                    }
+                }
+            }
+            TagEdit::Remove(ref removed) => tags.retain(|t| !has(removed, t)),
+            TagEdit::Replace(ref old, ref new) => {
+                if let Some(i) = tags.iter().position(|t| normalize::eq(t, old)) {
+                    let elsewhere = tags
+                        .iter()
+                        .enumerate()
+                        .any(|(j, t)| j != i && normalize::eq(t, new));
+                    if elsewhere || new.trim().is_empty() {
+                        tags.remove(i);
+                    } else {
+                        tags[i] = new.clone();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Tags of `tags` once `edits` are applied in order, or `None` if they don't change.
+pub fn edited(tags: &str, edits: &[TagEdit]) -> Option<String> {
+    let mut list = tags
+        .split_whitespace()
+        .map(String::from)
+        .collect::<Vec<String>>();
+    for edit in edits {
+        edit.apply(&mut list);
+    }
+    let new_tags = list.join(" ");
+    if new_tags == tags.split_whitespace().collect::<Vec<&str>>().join(" ") {
+        None
+    } else {
+        Some(new_tags)
+    }
+}
+
+/// A bookmark with its new tags, and the tags it had.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TagChange {
+    pub pin: Pin<'static>,
+    pub old_tags: String,
+}
+
+impl fmt::Display for TagChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.pin.url)?;
+        writeln!(f, "- {}", self.old_tags)?;
+        writeln!(f, "+ {}", self.pin.tags)
+    }
+}
+
+impl TagChange {
+    /// Change of `pin` to `tags`.
+    pub fn new(pin: &Pin, tags: String) -> Self {
+        let mut builder = PinBuilder::new(pin.url.to_string(), pin.title.to_string())
+            .tags(tags)
+            .shared(pin.shared.to_string())
+            .toread(pin.toread.to_string());
+        if let Some(ref extended) = pin.extended {
+            builder = builder.description(extended.to_string());
+        }
+        let mut new_pin = builder.into_pin();
+        new_pin.time = pin.time;
+        TagChange {
+            pin: new_pin,
+            old_tags: pin.tags.to_string(),
+        }
+    }
+}
+
+/// Tag changes to a set of bookmarks, of which the first `done` were posted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BulkTagEdit {
+    pub changes: Vec<TagChange>,
+    pub done: usize,
+}
+
+impl BulkTagEdit {
+    pub fn new(changes: Vec<TagChange>) -> Self {
+        BulkTagEdit { changes, done: 0 }
+    }
+
+    /// Changes left to post.
+    pub fn pending(&self) -> &[TagChange] {
+        &self.changes[self.done..]
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done >= self.changes.len()
+    }
+}
+
+impl fmt::Display for BulkTagEdit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            write!(f, "{}", change)?;
+        }
+        writeln!(f, "{} of {} bookmarks updated", self.done, self.changes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger;
+
+    #[test]
+    fn edits_tags() {
+        let _ = env_logger::try_init();
+        debug!("edits_tags: starting");
+        let add = TagEdit::Add(vec!["archive".into(), "Rust".into()]);
+        assert_eq!(Some("rust todo archive".into()), edited("rust todo", &[add.clone()]));
+        assert_eq!(None, edited("archive  RUST", &[add]));
+        assert_eq!(None, edited("rust", &[TagEdit::Add(vec!["".into(), " ".into()])]));
+
+        let remove = TagEdit::Remove(vec!["TODO".into(), "missing".into()]);
+        assert_eq!(Some("rust".into()), edited("todo rust", &[remove.clone()]));
+        assert_eq!(None, edited("rust", &[remove]));
+
+        let replace = TagEdit::Replace("js".into(), "javascript".into());
+        assert_eq!(Some("a javascript b".into()), edited("a JS b", &[replace.clone()]));
+        assert_eq!(Some("javascript b".into()), edited("javascript JS b", &[replace]));
+        let recase = TagEdit::Replace("freebsd".into(), "FreeBSD".into());
+        assert_eq!(Some("a FreeBSD".into()), edited("a freebsd", &[recase.clone()]));
+        assert_eq!(None, edited("a FreeBSD", &[recase]));
+        let blank = TagEdit::Replace("js".into(), " ".into());
+        assert_eq!(Some("a b".into()), edited("a js b", &[blank]));
+
+        let edits = [
+            TagEdit::Add(vec!["archive".into()]),
+            TagEdit::Remove(vec!["todo".into()]),
+        ];
+        assert_eq!(Some("rust archive".into()), edited("todo rust", &edits));
+        assert_eq!(Some("archive".into()), edited("", &edits));
+    }
+
+    #[test]
+    fn tracks_progress() {
+        let _ = env_logger::try_init();
+        debug!("tracks_progress: starting");
+        let change = |url: &str| {
+            let pin = PinBuilder::new(url.to_string(), "title".to_string())
+                .tags("rust")
+                .description("kept")
+                .into_pin();
+            TagChange::new(&pin, "rust archive".to_string())
+        };
+        let mut edit = BulkTagEdit::new(vec![change("https://a.com/"), change("https://b.com/")]);
+        assert_eq!(Some("kept".into()), edit.changes[0].pin.extended);
+        assert_eq!(2, edit.pending().len());
+        edit.done = 1;
+        assert_eq!("https://b.com/", edit.pending()[0].pin.url);
+        assert!(!edit.is_done());
+        edit.done = 2;
+        assert!(edit.is_done());
+        assert_eq!(
+            "https://a.com/\n- rust\n+ rust archive\n",
+            edit.changes[0].to_string()
+        );
+        assert!(edit.to_string().ends_with("2 of 2 bookmarks updated\n"));
+    }
+}
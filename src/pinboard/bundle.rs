@@ -0,0 +1,132 @@
+//! Named sets of tags, like the tag bundles of Pinboard's web interface.
+//!
+//! Pinboard's API doesn't expose bundles, so they are kept in the cache folder. They can be
+//! exported to JSON and imported back, to be shared. Tags of a bundle are compared like tags
+//! are, ignoring case and Unicode normalization form.
+use failure::Error;
+use serde_json;
+
+use super::cached_data::Named;
+use super::normalize;
+use super::validate;
+
+/// A named set of tags.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TagBundle {
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+impl TagBundle {
+    /// Creates the bundle `name` of `tags`, which are split on whitespace and commas, only
+    /// keeping the first spelling of tags differing by case.
+    pub fn new<S: Into<String>>(name: S, tags: Vec<String>) -> Self {
+        TagBundle {
+            name: name.into(),
+            tags: split_tags(&tags),
+        }
+    }
+
+    /// Returns true if `tag` is one of the bundle's tags.
+    pub fn contains(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| normalize::eq(t, tag))
+    }
+
+    /// Adds the `tags` the bundle doesn't have yet. Returns false if it had them all.
+    pub fn add_tags(&mut self, tags: &[String]) -> bool {
+        let count = self.tags.len();
+        for tag in split_tags(tags) {
+            if !self.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        self.tags.len() != count
+    }
+
+    /// Removes `tags` from the bundle. Returns false if it had none of them.
+    pub fn remove_tags(&mut self, tags: &[String]) -> bool {
+        let count = self.tags.len();
+        self.tags
+            .retain(|t| !tags.iter().any(|removed| normalize::eq(t, removed)));
+        self.tags.len() != count
+    }
+}
+
+impl Named for TagBundle {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+fn split_tags(tags: &[String]) -> Vec<String> {
+    validate::normalize_tags(&tags.join(" "))
+        .split_whitespace()
+        .map(String::from)
+        .collect()
+}
+
+/// Bundles as a JSON list of objects with a `name` and a list of `tags`.
+pub fn to_json(bundles: &[TagBundle]) -> Result<String, Error> {
+    Ok(serde_json::to_string_pretty(bundles)?)
+}
+
+/// Bundles read from JSON as written by [`to_json`](fn.to_json.html). Their tags are split
+/// and deduplicated as by [`TagBundle::new`](struct.TagBundle.html#method.new).
+pub fn from_json(json: &str) -> Result<Vec<TagBundle>, Error> {
+    let bundles = serde_json::from_str::<Vec<TagBundle>>(json)?;
+    let mut checked: Vec<TagBundle> = Vec::new();
+    for bundle in bundles {
+        if bundle.name.trim().is_empty() {
+            bail!("Tag bundle name can't be empty.");
+        }
+        if checked.iter().any(|b| b.name == bundle.name) {
+            bail!("Tag bundle {} is defined more than once.", bundle.name);
+        }
+        checked.push(TagBundle::new(bundle.name, bundle.tags));
+    }
+    Ok(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger;
+
+    #[test]
+    fn edits_tags() {
+        let _ = env_logger::try_init();
+        debug!("edits_tags: starting");
+        let mut bundle = TagBundle::new("dev", vec!["rust,go".into(), "Rust".into()]);
+        assert_eq!(vec!["rust", "go"], bundle.tags);
+        assert!(bundle.contains("RUST"));
+        assert!(!bundle.contains("python"));
+
+        assert!(bundle.add_tags(&["python".into(), "GO".into()]));
+        assert!(!bundle.add_tags(&["Python".into()]));
+        assert_eq!(vec!["rust", "go", "python"], bundle.tags);
+
+        assert!(bundle.remove_tags(&["Go".into(), "java".into()]));
+        assert!(!bundle.remove_tags(&["java".into()]));
+        assert_eq!(vec!["rust", "python"], bundle.tags);
+    }
+
+    #[test]
+    fn round_trips_json() {
+        let _ = env_logger::try_init();
+        debug!("round_trips_json: starting");
+        let bundles = vec![
+            TagBundle::new("dev", vec!["rust".into(), "go".into()]),
+            TagBundle::new("home", vec!["cooking".into()]),
+        ];
+        let json = to_json(&bundles).unwrap();
+        assert_eq!(bundles, from_json(&json).unwrap());
+
+        assert_eq!(
+            vec![TagBundle::new("dev", vec!["rust".into(), "go".into()])],
+            from_json(r#"[{"name": "dev", "tags": ["rust go", "RUST"]}]"#).unwrap()
+        );
+        assert!(from_json(r#"[{"name": " ", "tags": []}]"#).is_err());
+        assert!(from_json(r#"[{"name": "a", "tags": []}, {"name": "a", "tags": []}]"#).is_err());
+        assert!(from_json(r#"{"name": "a"}"#).is_err());
+    }
+}
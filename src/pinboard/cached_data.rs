@@ -1,18 +1,44 @@
 use super::*;
 use env_logger;
-use std::io::Write;
+use std::fs::OpenOptions;
 use std::io::{BufReader, BufWriter};
+use std::io::{Read, Write};
 
 use crate::rmps;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 
 use failure::Error;
 
+use self::alias::TagAlias;
+use self::canonical::{canonicalize, UrlStrictness};
+use self::crypto::CacheKey;
+use self::fuzzy::FuzzyMatcher;
+use self::index::SearchIndex;
+use self::normalize::normalize;
+use self::search::Field;
+use self::suggest::TagSuggester;
 use self::tag::Tag;
+use self::words::{Analyzer, Language};
 use super::pin::Pin;
 
 const TAGS_CACHE_FN: &str = "tags.cache";
 const PINS_CACHE_FN: &str = "pins.cache";
+const INDEX_CACHE_FN: &str = "index.cache";
+pub const SEARCHES_CACHE_FN: &str = "searches.cache";
+pub const ALIASES_CACHE_FN: &str = "aliases.cache";
+/// Bulk tag edit being applied, if any.
+pub const BULK_CACHE_FN: &str = "bulk.cache";
+pub const BUNDLES_CACHE_FN: &str = "bundles.cache";
+
+/// Stores of the cache folder besides pins, tags and index, see
+/// [`read_store`](struct.CachedData.html#method.read_store).
+const STORE_FNS: &[&str] = &[
+    SEARCHES_CACHE_FN,
+    ALIASES_CACHE_FN,
+    BULK_CACHE_FN,
+    BUNDLES_CACHE_FN,
+];
 
 const FILE_BUF_SIZE: usize = 4 * 1024 * 1024;
 const CACHE_BUF_SIZE: usize = 1024;
@@ -24,17 +50,43 @@ pub struct CachedData<'pin> {
     pub cache_dir: PathBuf,
     pub tags_cache_file: PathBuf,
     pub pins_cache_file: PathBuf,
+    pub index_cache_file: PathBuf,
+    /// Narrows down searches, `None` means every pin has to be checked.
+    pub index: Option<SearchIndex>,
+    /// Fingerprint of the pins and tags when they were last loaded or indexed.
+    fingerprint: u64,
+    /// Tag usage of the pins, learned when they are loaded.
+    suggester: TagSuggester,
+    /// Tag aliases, kept in memory as tag searches look them up.
+    aliases: Vec<TagAlias>,
+    /// Whether diacritics are stripped from the normalized fields of pins and tags.
+    strip_diacritics: bool,
+    /// Language of the words computed for word search, `None` if they aren't computed.
+    word_language: Option<Language>,
+    /// Strictness of the canonical urls of the pins.
+    url_strictness: UrlStrictness,
     cache_files_valid: bool,
+    key: Option<CacheKey>,
 }
 
-// TODO: Add a url_lowered field to CachedPin so we don't have to call
-//       .to_lowercase() in pinboard.find_url() every time //
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct CachedPin<'pin> {
     pub pin: Pin<'pin>,
     pub tag_list: Vec<String>,
     pub title_lowered: String,
     pub extended_lowered: Option<String>,
+    /// Normalized url, searched like the other normalized fields.
+    #[serde(skip)]
+    pub url_lowered: String,
+    /// Canonical form of the url, used to find pins by url.
+    #[serde(skip)]
+    pub url_normalized: String,
+    /// Stemmed words of the title, sorted. Only computed when word search is enabled.
+    #[serde(skip)]
+    pub title_words: Vec<String>,
+    /// Stemmed words of the description, sorted. Only computed when word search is enabled.
+    #[serde(skip)]
+    pub extended_words: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -43,36 +95,159 @@ pub struct CachedTag {
     pub tag_lowered: String,
 }
 
+impl<'pin> CachedPin<'pin> {
+    pub fn new(pin: Pin<'pin>) -> Self {
+        CachedPin::normalized(pin, false)
+    }
+
+    /// Same as `new` but diacritics are also stripped from the normalized fields if
+    /// `strip_diacritics` is true.
+    pub fn normalized(pin: Pin<'pin>, strip_diacritics: bool) -> Self {
+        let mut cached_pin = CachedPin {
+            pin,
+            tag_list: Vec::new(),
+            title_lowered: String::new(),
+            extended_lowered: None,
+            url_lowered: String::new(),
+            url_normalized: String::new(),
+            title_words: Vec::new(),
+            extended_words: Vec::new(),
+        };
+        cached_pin.normalize(strip_diacritics);
+        cached_pin.canonicalize_url(UrlStrictness::default());
+        cached_pin
+    }
+
+    /// Recomputes the normalized fields from the pin.
+    pub fn normalize(&mut self, strip_diacritics: bool) {
+        self.tag_list = self
+            .pin
+            .tags
+            .split_whitespace()
+            .map(|t| normalize(t, strip_diacritics))
+            .collect();
+        self.title_lowered = normalize(&self.pin.title, strip_diacritics);
+        self.url_lowered = normalize(&self.pin.url, strip_diacritics);
+        self.extended_lowered = self
+            .pin
+            .extended
+            .as_ref()
+            .map(|e| normalize(e, strip_diacritics));
+    }
+
+    /// Recomputes the canonical form of the url.
+    pub fn canonicalize_url(&mut self, strictness: UrlStrictness) {
+        self.url_normalized = canonicalize(&self.pin.url, strictness);
+    }
+
+    /// Recomputes the words of the normalized title and description, or clears them if there
+    /// is no `analyzer`.
+    pub fn analyze(&mut self, analyzer: Option<&Analyzer>) {
+        match analyzer {
+            Some(analyzer) => {
+                self.title_words = analyzer.words(&self.title_lowered);
+                self.extended_words = self
+                    .extended_lowered
+                    .as_ref()
+                    .map(|e| analyzer.words(e))
+                    .unwrap_or_default();
+            }
+            None => {
+                self.title_words = Vec::new();
+                self.extended_words = Vec::new();
+            }
+        }
+    }
+
+    /// Returns true if the title or the description has all of `words`.
+    pub fn has_words(&self, words: &[String]) -> bool {
+        words::contains_all(&self.title_words, words)
+            || words::contains_all(&self.extended_words, words)
+    }
+
+    /// Returns true if any of the title, tags, url or description contains `query`, which
+    /// should already be normalized.
+    pub fn contains(&self, query: &str) -> bool {
+        self.title_lowered.contains(query)
+            || self.tag_list.iter().any(|t| t.contains(query))
+            || self.url_lowered.contains(query)
+            || self
+                .extended_lowered
+                .as_ref()
+                .map_or(false, |e| e.contains(query))
+    }
+
+    /// Returns true if any of the title, tags, url or description fuzzily matches.
+    pub fn contains_fuzzy(&self, matcher: &FuzzyMatcher) -> bool {
+        matcher.is_match(&self.title_lowered)
+            || self.tag_list.iter().any(|t| matcher.is_match(t))
+            || matcher.is_match(&self.url_lowered)
+            || self
+                .extended_lowered
+                .as_ref()
+                .map_or(false, |e| matcher.is_match(e))
+    }
+}
+
+impl CachedTag {
+    pub fn new(tag: Tag, strip_diacritics: bool) -> Self {
+        CachedTag {
+            tag_lowered: normalize(&tag.0, strip_diacritics),
+            tag,
+        }
+    }
+}
+
+/// Items kept in a list store, which are replaced by name, see
+/// [`save_named`](struct.CachedData.html#method.save_named).
+pub trait Named {
+    fn name(&self) -> &str;
+}
+
+#[derive(Debug, Fail)]
+pub enum CacheError {
+    #[fail(display = "cache file is encrypted but no key was given: {}", _0)]
+    MissingKey(String),
+    #[fail(display = "couldn't decrypt cache: {}", _0)]
+    Decryption(String),
+    #[fail(display = "couldn't encrypt cache data")]
+    Encryption,
+    #[fail(display = "couldn't derive key from passphrase: {}", _0)]
+    KeyDerivation(String),
+    #[fail(display = "couldn't set permissions of {}: {}", _0, _1)]
+    Permission(String, String),
+}
+
 impl<'pin> CachedData<'pin> {
     pub fn new<P: AsRef<Path>>(c_dir: Option<P>) -> Result<Self, Error> {
-        let _ = env_logger::try_init();
-        debug!("new: starting");
-        let cached_dir = c_dir.map(|p| p.as_ref().to_path_buf()).unwrap_or_else(|| {
-            let mut dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from(""));
-            dir.push(".cache");
-            dir.push("rusty-pin");
-            dir
-        });
-        debug!("  cached_dir: {:?}", cached_dir);
-        let mut data = CachedData::create_cache_dir(cached_dir).and_then(|c_path| {
-            Ok(CachedData {
-                pins: None,
-                tags: None,
-                tags_cache_file: c_path.join(TAGS_CACHE_FN),
-                pins_cache_file: c_path.join(PINS_CACHE_FN),
-                cache_dir: c_path,
-                cache_files_valid: false,
-            })
-        })?;
+        CachedData::with_key(c_dir, None)
+    }
 
-        if data.load_cache_data_from_file().is_err() {
-            data.cache_files_valid = false;
+    /// Same as `new` but cache files are encrypted/decrypted using `key`.
+    ///
+    /// An error is returned if the existing cache files can't be decrypted, so they are never
+    /// silently replaced by a fresh (and possibly unencrypted) copy.
+    pub fn with_key<P: AsRef<Path>>(
+        c_dir: Option<P>,
+        key: Option<CacheKey>,
+    ) -> Result<Self, Error> {
+        let _ = env_logger::try_init();
+        debug!("with_key: starting");
+        let mut data = CachedData::init(c_dir)?;
+        data.key = key;
+
+        if let Err(e) = data.load_cache_data_from_file() {
+            match e.downcast_ref::<CacheError>() {
+                Some(CacheError::MissingKey(_))
+                | Some(CacheError::Decryption(_))
+                | Some(CacheError::KeyDerivation(_)) => return Err(e),
+                _ => data.cache_files_valid = false,
+            }
         }
         Ok(data)
     }
 
     /// Create an instance for CachedData but don't load actual cached files.
-    #[allow(dead_code)]
     pub fn init<P: AsRef<Path>>(c_dir: Option<P>) -> Result<Self, Error> {
         let _ = env_logger::try_init();
         debug!("init: starting");
@@ -89,8 +264,17 @@ impl<'pin> CachedData<'pin> {
                 tags: None,
                 tags_cache_file: c_path.join(TAGS_CACHE_FN),
                 pins_cache_file: c_path.join(PINS_CACHE_FN),
+                index_cache_file: c_path.join(INDEX_CACHE_FN),
+                index: None,
+                fingerprint: 0,
+                suggester: TagSuggester::default(),
+                aliases: Vec::new(),
+                strip_diacritics: false,
+                word_language: None,
+                url_strictness: UrlStrictness::default(),
                 cache_dir: c_path,
                 cache_files_valid: false,
+                key: None,
             })
         })?;
         Ok(data)
@@ -100,7 +284,15 @@ impl<'pin> CachedData<'pin> {
         let _ = env_logger::try_init();
         debug!("create_cache_dir: starting");
         use std::fs;
+        // Only a folder created here is restricted, an existing one may be shared.
+        let created = !cache_dir.as_ref().exists();
         fs::create_dir_all(&cache_dir)?;
+        #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
+        {
+            if created {
+                CachedData::fix_cache_perm(cache_dir.as_ref(), 0o700)?;
+            }
+        }
         debug!(
             "  success create_cache_dir: {:?}",
             cache_dir.as_ref().to_path_buf()
@@ -115,18 +307,132 @@ impl<'pin> CachedData<'pin> {
         self.cache_dir = CachedData::create_cache_dir(p)?;
         self.tags_cache_file = self.cache_dir.join(TAGS_CACHE_FN);
         self.pins_cache_file = self.cache_dir.join(PINS_CACHE_FN);
+        self.index_cache_file = self.cache_dir.join(INDEX_CACHE_FN);
+        self.aliases = Vec::new();
         self.pins = None;
         self.tags = None;
+        self.index = None;
+        self.suggester = TagSuggester::default();
         self.cache_files_valid = false;
         Ok(())
     }
 
+    /// Changes the key used for the cache files and rewrites them with it.
+    /// Passing `None` stores the cache unencrypted.
+    pub fn set_key(&mut self, key: Option<CacheKey>) -> Result<(), Error> {
+        debug!("set_key: starting");
+        let stores = STORE_FNS
+            .iter()
+            .map(|name| self.store_path(name))
+            .filter(|p| p.exists())
+            .map(|p| self.read_cache_data(&p).map(|data| (p, data)))
+            .collect::<Result<Vec<(PathBuf, Vec<u8>)>, Error>>()?;
+        self.key = key;
+        if self.cache_files_valid {
+            self.save_cache_data_to_file()?;
+        }
+        for (p, data) in stores {
+            self.write_cache_data(&p, &data)?;
+        }
+        Ok(())
+    }
+
+    /// Path of the store `name` of the cache folder.
+    pub fn store_path(&self, name: &str) -> PathBuf {
+        self.cache_dir.join(name)
+    }
+
+    /// Reads the store `name` of the cache folder, one of the `*_CACHE_FN` listed in
+    /// `STORE_FNS`. Returns `None` if it was never written.
+    pub fn read_store<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, Error> {
+        debug!("read_store: {}", name);
+        let p = self.store_path(name);
+        if p.exists() {
+            self.read_cache_file(&p).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn write_store<T: Serialize>(&self, name: &str, data: &T) -> Result<(), Error> {
+        debug!("write_store: {}", name);
+        self.write_cache_file(&self.store_path(name), data)
+    }
+
+    /// Deletes the store `name`. Returns false if there was none.
+    pub fn remove_store(&self, name: &str) -> Result<bool, Error> {
+        debug!("remove_store: {}", name);
+        let p = self.store_path(name);
+        if p.exists() {
+            fs::remove_file(&p)?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Items of the list kept in the store `name`, none if it was never written.
+    pub fn read_list<T: DeserializeOwned>(&self, name: &str) -> Result<Vec<T>, Error> {
+        Ok(self.read_store(name)?.unwrap_or_default())
+    }
+
+    /// Adds `items` to the list kept in the store `name`, each replacing the item with the
+    /// same name if any.
+    pub fn save_named<T>(&self, name: &str, items: Vec<T>) -> Result<(), Error>
+    where
+        T: Named + Serialize + DeserializeOwned,
+    {
+        let mut list = self.read_list::<T>(name)?;
+        for item in items {
+            match list.iter_mut().find(|i| i.name() == item.name()) {
+                Some(existing) => *existing = item,
+                None => list.push(item),
+            }
+        }
+        self.write_store(name, &list)
+    }
+
+    /// Deletes the item called `item` from the list kept in the store `name`. Returns false if
+    /// there was none.
+    pub fn delete_named<T>(&self, name: &str, item: &str) -> Result<bool, Error>
+    where
+        T: Named + Serialize + DeserializeOwned,
+    {
+        let mut list = self.read_list::<T>(name)?;
+        let count = list.len();
+        list.retain(|i| i.name() != item);
+        if list.len() == count {
+            return Ok(false);
+        }
+        self.write_store(name, &list)?;
+        Ok(true)
+    }
+
+    /// Tag aliases as last loaded or saved.
+    pub fn tag_aliases(&self) -> &[TagAlias] {
+        &self.aliases
+    }
+
+    /// Saves the tag aliases and keeps them in memory.
+    pub fn set_tag_aliases(&mut self, aliases: Vec<TagAlias>) -> Result<(), Error> {
+        debug!("set_tag_aliases: starting");
+        self.write_store(ALIASES_CACHE_FN, &aliases)?;
+        self.aliases = aliases;
+        Ok(())
+    }
+
     pub fn load_cache_data_from_file(&mut self) -> Result<(), Error> {
         debug!("load_cache_data_from_file: starting");
+        self.aliases = self.read_list(ALIASES_CACHE_FN)?;
         match (self.tags_cache_file.exists(), self.pins_cache_file.exists()) {
             (true, true) => {
                 self.read_cached_pins()?;
+                self.normalize_urls();
+                self.canonicalize_urls();
+                self.analyze_words();
                 self.read_cached_tags()?;
+                self.read_cached_index();
+                self.rebuild_suggester();
                 self.cache_files_valid = true;
                 Ok(())
             }
@@ -134,19 +440,307 @@ impl<'pin> CachedData<'pin> {
         }
     }
 
+    pub fn save_cache_data_to_file(&self) -> Result<(), Error> {
+        debug!("save_cache_data_to_file: starting");
+        if let Some(ref pins) = self.pins {
+            self.write_cache_file(&self.pins_cache_file, pins)?;
+        }
+        if let Some(ref tags) = self.tags {
+            self.write_cache_file(&self.tags_cache_file, tags)?;
+        }
+        if let Some(ref index) = self.index {
+            self.write_cache_file(&self.index_cache_file, index)?;
+        }
+        Ok(())
+    }
+
     fn read_cached_pins(&mut self) -> Result<(), Error> {
         debug!("read_cached_pins: starting");
-        let fp = File::open(&self.pins_cache_file)?;
-        let reader = BufReader::with_capacity(FILE_BUF_SIZE, fp);
-        self.pins = rmps::from_read(reader)?;
+        let pins = self.read_cache_file(&self.pins_cache_file)?;
+        self.pins = Some(pins);
         Ok(())
     }
 
     fn read_cached_tags(&mut self) -> Result<(), Error> {
         debug!("read_cached_tags: starting");
-        let fp = File::open(&self.tags_cache_file)?;
-        let reader = BufReader::with_capacity(FILE_BUF_SIZE, fp);
-        self.tags = rmps::from_read(reader)?;
+        let tags = self.read_cache_file(&self.tags_cache_file)?;
+        self.tags = Some(tags);
+        Ok(())
+    }
+
+    /// Loads the search index, rebuilding it if it is missing or doesn't match the cached
+    /// pins and tags. The index can always be rebuilt, so it never fails.
+    fn read_cached_index(&mut self) {
+        debug!("read_cached_index: starting");
+        let (pins, tags) = (
+            self.pins.as_ref().map_or(&[][..], |p| p),
+            self.tags.as_ref().map_or(&[][..], |t| t),
+        );
+        let (pin_count, tag_count) = (pins.len(), tags.len());
+        let fingerprint = index::fingerprint(pins, tags);
+        self.fingerprint = fingerprint;
+        self.index = self
+            .read_cache_file::<SearchIndex>(&self.index_cache_file)
+            .ok()
+            .filter(|index| index.is_built_for(pin_count, tag_count, fingerprint));
+        match self.index {
+            Some(ref index) if index.strips_diacritics() == self.strip_diacritics => (),
+            Some(_) => {
+                info!("  cache was normalized differently, renormalizing");
+                self.normalize_fields();
+                self.rebuild_index();
+            }
+            None => {
+                info!("  rebuilding search index");
+                self.rebuild_index();
+            }
+        }
+    }
+
+    /// Builds the search index from the pins and tags in memory.
+    pub fn rebuild_index(&mut self) {
+        debug!("rebuild_index: starting");
+        self.index = match (self.pins.as_ref(), self.tags.as_ref()) {
+            (Some(pins), Some(tags)) => Some(SearchIndex::new(pins, tags, self.strip_diacritics)),
+            _ => None,
+        };
+        self.fingerprint = self.index.as_ref().map_or(0, SearchIndex::fingerprint);
+    }
+
+    /// Learns tag usage from the pins in memory, for tag suggestions.
+    pub fn rebuild_suggester(&mut self) {
+        debug!("rebuild_suggester: starting");
+        self.suggester = TagSuggester::new(
+            self.pins.as_ref().map_or(&[][..], |p| p),
+            self.strip_diacritics,
+        );
+    }
+
+    /// Tag usage of the pins as last loaded, see [`rebuild_suggester`](#method.rebuild_suggester).
+    pub fn suggester(&self) -> &TagSuggester {
+        &self.suggester
+    }
+
+    /// Returns true if diacritics are stripped from the normalized fields.
+    pub fn strips_diacritics(&self) -> bool {
+        self.strip_diacritics
+    }
+
+    /// Sets whether diacritics are stripped from the normalized fields of pins and tags,
+    /// renormalizing them and rebuilding the index if the setting changed.
+    pub fn set_strip_diacritics(&mut self, v: bool) {
+        debug!("set_strip_diacritics: {}", v);
+        if self.strip_diacritics == v {
+            return;
+        }
+        self.strip_diacritics = v;
+        self.normalize_fields();
+        self.rebuild_index();
+        self.rebuild_suggester();
+    }
+
+    /// Strictness of the canonical urls of the pins.
+    pub fn url_strictness(&self) -> UrlStrictness {
+        self.url_strictness
+    }
+
+    /// Sets the strictness of the canonical urls of the pins, recomputing them if it changed.
+    pub fn set_url_strictness(&mut self, strictness: UrlStrictness) {
+        debug!("set_url_strictness: {:?}", strictness);
+        if self.url_strictness == strictness {
+            return;
+        }
+        self.url_strictness = strictness;
+        self.canonicalize_urls();
+    }
+
+    /// Recomputes the normalized urls of the pins in memory.
+    fn normalize_urls(&mut self) {
+        debug!("normalize_urls: starting");
+        let strip = self.strip_diacritics;
+        if let Some(ref mut pins) = self.pins {
+            pins.iter_mut()
+                .for_each(|p| p.url_lowered = normalize(&p.pin.url, strip));
+        }
+    }
+
+    /// Recomputes the canonical urls of the pins in memory.
+    fn canonicalize_urls(&mut self) {
+        debug!("canonicalize_urls: starting");
+        let strictness = self.url_strictness;
+        if let Some(ref mut pins) = self.pins {
+            pins.iter_mut().for_each(|p| p.canonicalize_url(strictness));
+        }
+    }
+
+    /// Language of the words computed for word search, if any.
+    pub fn word_language(&self) -> Option<Language> {
+        self.word_language
+    }
+
+    /// Sets the language of the words computed for word search, `None` to not compute them,
+    /// and recomputes them if it changed.
+    pub fn set_word_language(&mut self, language: Option<Language>) {
+        debug!("set_word_language: {:?}", language);
+        if self.word_language == language {
+            return;
+        }
+        self.word_language = language;
+        self.analyze_words();
+    }
+
+    /// Analyzer for the words of the normalized fields, `None` if they aren't computed.
+    pub fn analyzer(&self) -> Option<Analyzer> {
+        self.word_language
+            .map(|l| Analyzer::new(l, self.strip_diacritics))
+    }
+
+    /// Recomputes the words of the pins in memory.
+    fn analyze_words(&mut self) {
+        debug!("analyze_words: starting");
+        let analyzer = self.analyzer();
+        if let Some(ref mut pins) = self.pins {
+            pins.iter_mut().for_each(|p| p.analyze(analyzer.as_ref()));
+        }
+    }
+
+    /// Recomputes the normalized fields of the pins and tags in memory.
+    fn normalize_fields(&mut self) {
+        debug!("normalize_fields: starting");
+        let strip = self.strip_diacritics;
+        if let Some(ref mut pins) = self.pins {
+            pins.iter_mut().for_each(|p| p.normalize(strip));
+        }
+        if let Some(ref mut tags) = self.tags {
+            tags.iter_mut()
+                .for_each(|t| t.tag_lowered = normalize(&t.tag.0, strip));
+        }
+        self.analyze_words();
+    }
+
+    /// Cached pins that may contain (or fuzzily match) all `queries`, each in any of
+    /// `fields`, in cache order. Pins still need to be checked against the queries.
+    pub fn candidate_pins<S: AsRef<str>>(
+        &self,
+        queries: &[S],
+        fuzzy: bool,
+        fields: &[Field],
+    ) -> Vec<&CachedPin<'pin>> {
+        let pins = match self.pins {
+            Some(ref pins) => pins,
+            None => return Vec::new(),
+        };
+        let index = match self.index {
+            Some(ref index)
+                if index.is_built_for(
+                    pins.len(),
+                    self.tags.as_ref().map_or(0, Vec::len),
+                    self.fingerprint,
+                ) =>
+            {
+                index
+            }
+            _ => return pins.iter().collect(),
+        };
+        let mut ids: Option<Vec<u32>> = None;
+        for q in queries {
+            if let Some(found) = index.pins(q.as_ref(), fuzzy, fields) {
+                ids = Some(match ids {
+                    Some(mut ids) => {
+                        ids.retain(|id| found.binary_search(id).is_ok());
+                        ids
+                    }
+                    None => found,
+                });
+            }
+        }
+        match ids {
+            Some(ids) => ids.into_iter().map(|id| &pins[id as usize]).collect(),
+            None => pins.iter().collect(),
+        }
+    }
+
+    /// Cached tags that may contain (or fuzzily match) `query`, in cache order.
+    pub fn candidate_tags(&self, query: &str, fuzzy: bool) -> Vec<&CachedTag> {
+        let tags = match self.tags {
+            Some(ref tags) => tags,
+            None => return Vec::new(),
+        };
+        let ids = match self.index {
+            Some(ref index)
+                if index.is_built_for(
+                    self.pins.as_ref().map_or(0, Vec::len),
+                    tags.len(),
+                    self.fingerprint,
+                ) =>
+            {
+                index.tags(query, fuzzy)
+            }
+            _ => None,
+        };
+        match ids {
+            Some(ids) => ids.into_iter().map(|id| &tags[id as usize]).collect(),
+            None => tags.iter().collect(),
+        }
+    }
+
+    /// Reads and deserializes a cache file, decrypting it first if it is encrypted.
+    pub fn read_cache_file<T: DeserializeOwned>(&self, p: &Path) -> Result<T, Error> {
+        debug!("read_cache_file: {:?}", p);
+        Ok(rmps::from_slice(&self.read_cache_data(p)?)?)
+    }
+
+    /// Reads a cache file, decrypting it if it is encrypted.
+    fn read_cache_data(&self, p: &Path) -> Result<Vec<u8>, Error> {
+        let fp = File::open(p)?;
+        let mut data: Vec<u8> = Vec::with_capacity(CACHE_BUF_SIZE);
+        BufReader::with_capacity(FILE_BUF_SIZE, fp).read_to_end(&mut data)?;
+        let data = if crypto::is_sealed(&data) {
+            match self.key {
+                Some(ref key) => crypto::open(key, &data)?,
+                None => return Err(CacheError::MissingKey(p.display().to_string()).into()),
+            }
+        } else {
+            if self.key.is_some() {
+                info!("  {:?} is not encrypted, it will be on next write", p);
+            }
+            data
+        };
+        Ok(data)
+    }
+
+    /// Serializes `data` and writes it to a cache file only readable by the current user,
+    /// encrypting it if a key is set.
+    pub fn write_cache_file<T: Serialize>(&self, p: &Path, data: &T) -> Result<(), Error> {
+        debug!("write_cache_file: {:?}", p);
+        let mut buf: Vec<u8> = Vec::with_capacity(CACHE_BUF_SIZE);
+        data.serialize(&mut Serializer::new(&mut buf))?;
+        self.write_cache_data(p, &buf)
+    }
+
+    /// Writes `data` to a cache file only readable by the current user, encrypting it if a
+    /// key is set.
+    fn write_cache_data(&self, p: &Path, data: &[u8]) -> Result<(), Error> {
+        let buf = match self.key {
+            Some(ref key) => crypto::seal(key, data)?,
+            None => data.to_vec(),
+        };
+
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let f = options.open(p)?;
+        // File may have existed with looser permissions.
+        #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
+        CachedData::fix_cache_perm(p, 0o600)?;
+
+        let mut writer = BufWriter::with_capacity(FILE_BUF_SIZE, f);
+        writer.write_all(&buf)?;
+        writer.flush()?;
         Ok(())
     }
 
@@ -157,111 +751,60 @@ impl<'pin> CachedData<'pin> {
 
     pub fn update_cache(&mut self, api: &api::Api) -> Result<(), Error> {
         debug!("update_cache: starting");
-        // Fetch & write all pins
-        let f = File::create(&self.pins_cache_file)?;
-
-        // Sort pins in descending creation time order
-        api.all_pins()
+        let strip = self.strip_diacritics;
+        // Fetch all pins & sort them in descending creation time order
+        let pins = api
+            .all_pins()
             .and_then(|mut pins| {
                 debug!(" sorting pins");
                 pins.sort_by(|pin1, pin2| pin1.time().cmp(&pin2.time()).reverse());
                 Ok(pins)
             })
             .and_then(|pins: Vec<Pin>| {
-                // Lower case all fields of each pin
-                debug!(" lowercasing fields");
+                // Normalize all fields of each pin
+                debug!(" normalizing fields");
                 Ok(pins
                     .into_iter()
-                    .map(|pin| {
-                        let tags_lowered = pin.tags.to_lowercase();
-                        CachedPin {
-                            tag_list: tags_lowered
-                                .split_whitespace()
-                                .map(std::string::ToString::to_string)
-                                .collect(),
-                            title_lowered: pin.title.to_lowercase(),
-                            extended_lowered: pin.extended.as_ref().map(|e| e.to_lowercase()),
-                            pin,
-                        }
-                    })
-                    .collect())
-            })
-            .and_then(|pins: Vec<CachedPin>| {
-                debug!(" serializing pins");
-                let mut buf: Vec<u8> = Vec::with_capacity(CACHE_BUF_SIZE);
-                pins.serialize(&mut Serializer::new(&mut buf))?;
-                self.pins = Some(pins);
-                Ok(buf)
-            })
-            .and_then(|data| {
-                debug!(" writing to cache");
-                let mut writer = BufWriter::with_capacity(FILE_BUF_SIZE, f);
-                writer.write_all(&data)?;
-                Ok(())
+                    .map(|pin| CachedPin::normalized(pin, strip))
+                    .collect::<Vec<CachedPin>>())
             })?;
+        self.pins = Some(pins);
+        self.canonicalize_urls();
+        self.analyze_words();
 
-        #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
-        self.fix_cache_file_perm(&self.pins_cache_file);
-
-        assert!(self.pins.is_some());
-
-        // Fetch & write all tags
-        //
-        let f = File::create(&self.tags_cache_file)?;
-
-        // Sort tags by frequency before writing
-        api.tags_frequency()
+        // Fetch all tags & sort them by frequency
+        let tags = api
+            .tags_frequency()
             .and_then(|mut tags| {
                 debug!("  sorting tags");
                 tags.sort_by(|t1, t2| t1.cmp(&t2).reverse());
                 Ok(tags)
             })
             .and_then(|tags| {
-                debug!("  lowercasing tags");
+                debug!("  normalizing tags");
                 Ok(tags
                     .into_iter()
-                    .map(|tag| CachedTag {
-                        tag_lowered: tag.0.to_lowercase(),
-                        tag,
-                    })
-                    .collect())
-            })
-            .and_then(|cached_tags: Vec<CachedTag>| {
-                debug!("  serializing tags");
-                let mut buf: Vec<u8> = Vec::with_capacity(CACHE_BUF_SIZE);
-                cached_tags.serialize(&mut Serializer::new(&mut buf))?;
-                self.tags = Some(cached_tags);
-                Ok(buf)
-            })
-            .and_then(|data| {
-                debug!("  writing to cache");
-                let mut writer = BufWriter::with_capacity(FILE_BUF_SIZE, f);
-                writer.write_all(&data)?;
-                Ok(())
+                    .map(|tag| CachedTag::new(tag, strip))
+                    .collect::<Vec<CachedTag>>())
             })?;
+        self.tags = Some(tags);
+        self.rebuild_index();
+        self.rebuild_suggester();
 
-        #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
-        self.fix_cache_file_perm(&self.pins_cache_file);
-
-        assert!(self.tags.is_some());
+        debug!("  writing to cache");
+        self.save_cache_data_to_file()?;
         self.cache_files_valid = true;
         Ok(())
     }
 
     #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
-    fn fix_cache_file_perm(&self, p: &PathBuf) {
-        debug!("fix_cache_file_perm: starting");
-        // TODO: don't just unwrap, return a proper error.
+    fn fix_cache_perm(p: &Path, mode: u32) -> Result<(), Error> {
+        debug!("fix_cache_perm: starting");
         use std::fs::set_permissions;
         use std::fs::Permissions;
         use std::os::unix::fs::PermissionsExt;
-        let permissions = Permissions::from_mode(0o600);
-        if let Err(e) = set_permissions(p, permissions) {
-            error!(
-                "Couldn't set proper file permission for cache files: {:?}",
-                e
-            );
-        }
+        set_permissions(p, Permissions::from_mode(mode))
+            .map_err(|e| CacheError::Permission(p.display().to_string(), e.to_string()).into())
     }
 }
 
@@ -292,6 +835,10 @@ mod tests {
             tag_list: vec!["rust".into(), "macros".into()],
             title_lowered: "The Little Book of Rust Macros".to_lowercase(),
             extended_lowered: Some("WoW!!!".to_lowercase()),
+            url_lowered: "https://danielkeep.github.io/tlborm/book/readme.html".into(),
+            url_normalized: "https://danielkeep.github.io/tlborm/book/readme.html".into(),
+            title_words: vec!["book".into(), "littl".into()],
+            extended_words: vec!["wow".into()],
         };
 
         let mut buf: Vec<u8> = Vec::new();
@@ -321,5 +868,117 @@ mod tests {
             vec![String::from("rust"), String::from("macros")],
             new_cached.tag_list
         );
+        // Urls and words are recomputed when loaded rather than cached
+        assert!(new_cached.url_lowered.is_empty());
+        assert!(new_cached.url_normalized.is_empty());
+        assert!(new_cached.title_words.is_empty());
+        assert!(new_cached.extended_words.is_empty());
+    }
+
+    fn sample_cached_pin() -> CachedPin<'static> {
+        let pin = PinBuilder::new(
+            "https://githuуй.com/Здравствуйт?q=13#fragment",
+            "A private bookmark",
+        )
+        .tags("secret")
+        .shared("no")
+        .into_pin();
+        CachedPin {
+            url_normalized: canonicalize(&pin.url, UrlStrictness::default()),
+            url_lowered: normalize(&pin.url, false),
+            pin,
+            tag_list: vec!["secret".into()],
+            title_lowered: "a private bookmark".into(),
+            extended_lowered: None,
+            title_words: Vec::new(),
+            extended_words: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn encrypted_cache_file() {
+        let _ = env_logger::try_init();
+        debug!("encrypted_cache_file: starting");
+        let dir = crate::tests::rand_temp_path();
+        let mut c = CachedData::init(Some(&dir)).expect("Can't initiate 'CachedData'.");
+        c.key = Some(CacheKey::from_passphrase("passphrase"));
+        c.pins = Some(vec![sample_cached_pin()]);
+        c.tags = Some(vec![]);
+        c.save_cache_data_to_file().expect("Can't write cache");
+
+        let raw = std::fs::read(&c.pins_cache_file).expect("Can't read cache file");
+        assert!(crypto::is_sealed(&raw));
+        assert!(!raw.windows(7).any(|w| w == b"private"));
+
+        // Right key
+        let c2 = CachedData::with_key(Some(&dir), Some(CacheKey::from_passphrase("passphrase")))
+            .expect("Can't load encrypted cache");
+        assert!(c2.cache_ok());
+        assert_eq!(c.pins, c2.pins);
+
+        // No key
+        let e = CachedData::new(Some(&dir)).expect_err("Loaded encrypted cache without key");
+        match e.downcast_ref::<CacheError>() {
+            Some(CacheError::MissingKey(_)) => (),
+            _ => panic!("unexpected error: {:?}", e),
+        }
+
+        // Wrong key
+        let e = CachedData::with_key(Some(&dir), Some(CacheKey::from_passphrase("wrong")))
+            .expect_err("Loaded encrypted cache with wrong key");
+        match e.downcast_ref::<CacheError>() {
+            Some(CacheError::Decryption(_)) => (),
+            _ => panic!("unexpected error: {:?}", e),
+        }
+
+        // Removing the key writes plain files again
+        let mut c3 = c2;
+        c3.set_key(None).expect("Can't remove cache key");
+        let c4 = CachedData::new(Some(&dir)).expect("Can't load plain cache");
+        assert!(c4.cache_ok());
+        assert_eq!(c.pins, c4.pins);
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
+    #[test]
+    fn cache_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = env_logger::try_init();
+        debug!("cache_permissions: starting");
+        let mut dir = crate::tests::rand_temp_path();
+        dir.push("rusty-pin");
+        let mut c = CachedData::init(Some(&dir)).expect("Can't initiate 'CachedData'.");
+        c.pins = Some(vec![sample_cached_pin()]);
+        c.tags = Some(vec![]);
+        // Pre-existing, world readable file
+        File::create(&c.tags_cache_file).expect("Can't create file");
+        std::fs::set_permissions(&c.tags_cache_file, std::fs::Permissions::from_mode(0o644))
+            .expect("Can't set permissions");
+        c.save_cache_data_to_file().expect("Can't write cache");
+
+        let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(0o700, mode(&c.cache_dir));
+        assert_eq!(0o600, mode(&c.pins_cache_file));
+        assert_eq!(0o600, mode(&c.tags_cache_file));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
+    #[test]
+    fn existing_cache_dir_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = env_logger::try_init();
+        debug!("existing_cache_dir_permissions: starting");
+        let dir = crate::tests::rand_temp_path();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755))
+            .expect("Can't set permissions");
+        let mut c = CachedData::init(Some(&dir)).expect("Can't initiate 'CachedData'.");
+        c.pins = Some(vec![sample_cached_pin()]);
+        c.tags = Some(vec![]);
+        c.save_cache_data_to_file().expect("Can't write cache");
+
+        let mode = |p: &Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+        assert_eq!(0o755, mode(&dir));
+        assert_eq!(0o600, mode(&c.pins_cache_file));
+        assert_eq!(0o600, mode(&c.tags_cache_file));
     }
 }
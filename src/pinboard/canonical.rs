@@ -0,0 +1,146 @@
+//! Canonical form of URLs, so that different ways of writing the same address compare equal.
+//!
+//! How much is ignored depends on the [`UrlStrictness`]. Canonical forms are always lower
+//! cased, so urls are compared case insensitively at every level.
+use url::Url;
+
+/// Query parameters that only track where a visitor came from.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "mc_cid", "mc_eid", "igshid", "_ga", "_hsenc",
+    "_hsmi", "ref_src",
+];
+
+/// How different two urls can be and still be considered the same.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UrlStrictness {
+    /// Urls must be identical, ignoring case and surrounding whitespace.
+    Exact,
+    /// Urls must be the same once parsed: scheme and host case, default ports and IDN vs
+    /// punycode hosts are ignored.
+    Parsed,
+    /// Same as `Parsed` but fragments, trailing slashes and tracking parameters such as
+    /// `utm_source` are ignored too.
+    #[default]
+    Normal,
+    /// Same as `Normal` but `http` vs `https`, a leading `www.` and the order of query
+    /// parameters are ignored too.
+    Loose,
+}
+
+fn is_tracking_param(param: &str) -> bool {
+    let name = param.split('=').next().unwrap_or("").to_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+/// Returns the canonical form of `url` for the given `strictness`.
+/// Urls that can't be parsed are only trimmed and lower cased.
+pub fn canonicalize(url: &str, strictness: UrlStrictness) -> String {
+    let url = url.trim();
+    let mut parsed = match strictness {
+        UrlStrictness::Exact => return url.to_lowercase(),
+        _ => match Url::parse(url) {
+            Ok(parsed) => parsed,
+            Err(_) => return url.to_lowercase(),
+        },
+    };
+    if strictness == UrlStrictness::Parsed || parsed.cannot_be_a_base() {
+        return parsed.as_str().to_lowercase();
+    }
+
+    parsed.set_fragment(None);
+    let mut params = parsed
+        .query()
+        .unwrap_or("")
+        .split('&')
+        .filter(|p| !p.is_empty() && !is_tracking_param(p))
+        .map(str::to_string)
+        .collect::<Vec<String>>();
+    if strictness == UrlStrictness::Loose {
+        params.sort();
+    }
+    if params.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.set_query(Some(&params.join("&")));
+    }
+    if parsed.path().len() > 1 && parsed.path().ends_with('/') {
+        let path = parsed.path().trim_end_matches('/').to_string();
+        parsed.set_path(&path);
+    }
+
+    if strictness == UrlStrictness::Loose {
+        if parsed.scheme() == "https" {
+            let _ = parsed.set_scheme("http");
+        }
+        let host = parsed
+            .host_str()
+            .filter(|h| h.starts_with("www."))
+            .map(|h| h["www.".len()..].to_string());
+        if let Some(host) = host {
+            let _ = parsed.set_host(Some(&host));
+        }
+    }
+    parsed.as_str().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger;
+
+    fn same(a: &str, b: &str, strictness: UrlStrictness) -> bool {
+        canonicalize(a, strictness) == canonicalize(b, strictness)
+    }
+
+    #[test]
+    fn strictness_levels() {
+        let _ = env_logger::try_init();
+        debug!("strictness_levels: starting");
+        use self::UrlStrictness::*;
+        let pairs = [
+            // (a, b, the least strict level at which they differ)
+            ("http://A.com/Path", "http://a.com/path", None),
+            ("HTTP://a.com:80/x", "http://a.com/x", Some(Exact)),
+            ("https://a.com:443", "https://a.com/", Some(Exact)),
+            (
+                "https://githuуй.com/Здравствуйт?q=13",
+                "https://xn--githu-sxe3e.com/%D0%97%D0%B4%D1%80%D0%B0%D0%B2%D1%81%D1%82%D0%B2%D1%83%D0%B9%D1%82?q=13",
+                Some(Exact),
+            ),
+            (
+                "https://github.com/sharkdp/hexyl#preview",
+                "https://github.com/sharkdp/hexyl",
+                Some(Parsed),
+            ),
+            ("https://a.com/x/", "https://a.com/x", Some(Parsed)),
+            (
+                "https://a.com/x?utm_source=rss&id=1&UTM_MEDIUM=feed&fbclid=abc",
+                "https://a.com/x?id=1",
+                Some(Parsed),
+            ),
+            ("https://a.com/x?utm_source=rss", "https://a.com/x", Some(Parsed)),
+            ("http://www.a.com/x", "https://a.com/x", Some(Normal)),
+            ("https://a.com/?b=2&a=1", "https://a.com/?a=1&b=2", Some(Normal)),
+        ];
+        for &(a, b, differ) in &pairs {
+            for &level in &[Exact, Parsed, Normal, Loose] {
+                let expected = match differ {
+                    None => true,
+                    Some(d) => level as u8 > d as u8,
+                };
+                assert_eq!(expected, same(a, b, level), "{} {} {:?}", a, b, level);
+            }
+        }
+
+        // Things that are never ignored
+        for &level in &[Normal, Loose] {
+            assert!(!same("https://a.com/x?id=1", "https://a.com/x?id=2", level));
+            assert!(!same("https://a.com/x", "https://a.com/y", level));
+            assert!(!same("https://a.com:8080/x", "https://a.com/x", level));
+            assert!(!same("https://blog.a.com/", "https://a.com/", level));
+        }
+        assert_eq!("https://a.com/", canonicalize("https://a.com/#top", Normal));
+        assert_eq!("not a url", canonicalize(" Not a URL ", Loose));
+        assert_eq!("mailto:me@a.com", canonicalize("mailto:Me@a.com", Normal));
+    }
+}
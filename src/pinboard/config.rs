@@ -1,9 +1,25 @@
+use std::time::Duration;
+
+use super::canonical::UrlStrictness;
+use super::words::Language;
+
 #[derive(Debug)]
 pub struct Config {
     pub tag_only_search: bool,
     pub fuzzy_search: bool,
+    pub diacritic_insensitive_search: bool,
+    pub word_search: bool,
+    pub regex_search: bool,
+    pub language: Language,
+    pub url_strictness: UrlStrictness,
     pub private_new_pin: bool,
     pub toread_new_pin: bool,
+    /// Leaves private tags out of `list_tag_pairs` and `search_list_of_tags`.
+    pub hide_private_tags: bool,
+    /// Splits tags into a hierarchy, e.g. `lang/rust`.
+    pub tag_separator: String,
+    /// Time to wait between the requests of a bulk tag edit.
+    pub bulk_edit_interval: Duration,
     _private: (), // Force instantiation through Config::new()
 }
 
@@ -12,8 +28,16 @@ impl Config {
         Config {
             tag_only_search: false,
             fuzzy_search: false,
+            diacritic_insensitive_search: false,
+            word_search: false,
+            regex_search: false,
+            language: Language::English,
+            url_strictness: UrlStrictness::Normal,
             private_new_pin: true,
             toread_new_pin: false,
+            hide_private_tags: false,
+            tag_separator: "/".to_string(),
+            bulk_edit_interval: Duration::from_secs(3),
             _private: (),
         }
     }
@@ -0,0 +1,206 @@
+//! Authenticated encryption of cache files.
+//!
+//! Sealed files have the following layout:
+//!
+//! `MAGIC | kdf (1 byte) | salt (16 bytes) | nonce (24 bytes) | ciphertext`
+//!
+//! The header (everything before the nonce) is authenticated along with the ciphertext,
+//! so tampering with it will make decryption fail.
+use std::fmt;
+
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use zeroize::Zeroizing;
+
+use failure::Error;
+
+use super::cached_data::CacheError;
+
+const MAGIC: &[u8] = b"RPCACHE\x01";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const HEADER_LEN: usize = MAGIC.len() + 1 + SALT_LEN;
+
+const KDF_RAW: u8 = 0;
+const KDF_ARGON2: u8 = 1;
+
+/// Key used to encrypt the cache files.
+///
+/// It is either a raw 256-bit key supplied by the caller, or a passphrase that is
+/// stretched with Argon2 (using a random per-file salt) whenever a file is sealed or opened.
+#[derive(Clone)]
+pub enum CacheKey {
+    Raw(Zeroizing<[u8; KEY_LEN]>),
+    Passphrase(Zeroizing<String>),
+}
+
+impl CacheKey {
+    pub fn from_bytes(key: [u8; KEY_LEN]) -> Self {
+        CacheKey::Raw(Zeroizing::new(key))
+    }
+
+    pub fn from_passphrase<S: Into<String>>(passphrase: S) -> Self {
+        CacheKey::Passphrase(Zeroizing::new(passphrase.into()))
+    }
+
+    fn kdf(&self) -> u8 {
+        match *self {
+            CacheKey::Raw(_) => KDF_RAW,
+            CacheKey::Passphrase(_) => KDF_ARGON2,
+        }
+    }
+
+    fn derive(&self, salt: &[u8]) -> Result<Zeroizing<[u8; KEY_LEN]>, Error> {
+        match *self {
+            CacheKey::Raw(ref k) => Ok(k.clone()),
+            CacheKey::Passphrase(ref p) => {
+                let mut key = Zeroizing::new([0u8; KEY_LEN]);
+                argon2::Argon2::default()
+                    .hash_password_into(p.as_bytes(), salt, &mut *key)
+                    .map_err(|e| CacheError::KeyDerivation(e.to_string()))?;
+                Ok(key)
+            }
+        }
+    }
+}
+
+// Never print key material.
+impl fmt::Debug for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CacheKey::Raw(_) => write!(f, "CacheKey::Raw(..)"),
+            CacheKey::Passphrase(_) => write!(f, "CacheKey::Passphrase(..)"),
+        }
+    }
+}
+
+/// Returns true if `data` looks like the output of [`seal`].
+pub fn is_sealed(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypts `plain` with `key`, returning the sealed bytes ready to be written to disk.
+pub fn seal(key: &CacheKey, plain: &[u8]) -> Result<Vec<u8>, Error> {
+    debug!("seal: starting");
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(key.kdf());
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    header.extend_from_slice(&salt);
+
+    let derived = key.derive(&salt)?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&*derived));
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plain,
+                aad: &header,
+            },
+        )
+        .map_err(|_| CacheError::Encryption)?;
+
+    let mut out = header;
+    out.reserve(NONCE_LEN + ciphertext.len());
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypts and authenticates data previously produced by [`seal`].
+pub fn open(key: &CacheKey, data: &[u8]) -> Result<Vec<u8>, Error> {
+    debug!("open: starting");
+    if !is_sealed(data) || data.len() < HEADER_LEN + NONCE_LEN {
+        return Err(CacheError::Decryption("not an encrypted cache file".to_string()).into());
+    }
+    let (header, rest) = data.split_at(HEADER_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    if header[MAGIC.len()] != key.kdf() {
+        return Err(CacheError::Decryption(
+            "cache was encrypted with a different kind of key".to_string(),
+        )
+        .into());
+    }
+    let salt = &header[MAGIC.len() + 1..];
+
+    let derived = key.derive(salt)?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&*derived));
+    cipher
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| CacheError::Decryption("wrong key or corrupted cache file".to_string()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger;
+
+    #[test]
+    fn seal_open_raw_key() {
+        let _ = env_logger::try_init();
+        debug!("seal_open_raw_key: starting");
+        let key = CacheKey::from_bytes([7u8; KEY_LEN]);
+        let sealed = seal(&key, b"private bookmarks").expect("couldn't seal");
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.windows(b"private".len()).any(|w| w == b"private"));
+        let plain = open(&key, &sealed).expect("couldn't open");
+        assert_eq!(b"private bookmarks".to_vec(), plain);
+
+        // Two seals of the same data must not produce the same output.
+        let sealed2 = seal(&key, b"private bookmarks").expect("couldn't seal");
+        assert_ne!(sealed, sealed2);
+    }
+
+    #[test]
+    fn seal_open_passphrase() {
+        let _ = env_logger::try_init();
+        debug!("seal_open_passphrase: starting");
+        let key = CacheKey::from_passphrase("correct horse battery staple");
+        let sealed = seal(&key, b"\xd0\x97\xd0\xb4").expect("couldn't seal");
+        let plain = open(&key, &sealed).expect("couldn't open");
+        assert_eq!(b"\xd0\x97\xd0\xb4".to_vec(), plain);
+
+        let wrong = CacheKey::from_passphrase("wrong horse");
+        let e = open(&wrong, &sealed).expect_err("opened with the wrong passphrase");
+        match e.downcast_ref::<CacheError>() {
+            Some(CacheError::Decryption(_)) => (),
+            _ => panic!("unexpected error: {:?}", e),
+        }
+
+        let raw = CacheKey::from_bytes([0u8; KEY_LEN]);
+        assert!(open(&raw, &sealed).is_err());
+    }
+
+    #[test]
+    fn tampered_data_is_rejected() {
+        let _ = env_logger::try_init();
+        debug!("tampered_data_is_rejected: starting");
+        let key = CacheKey::from_bytes([1u8; KEY_LEN]);
+        let mut sealed = seal(&key, b"some data").expect("couldn't seal");
+        let last = sealed.len() - 1;
+        sealed[last] ^= 0x01;
+        assert!(open(&key, &sealed).is_err());
+
+        let mut sealed = seal(&key, b"some data").expect("couldn't seal");
+        sealed[MAGIC.len() + 2] ^= 0x01; // flip a salt bit
+        assert!(open(&key, &sealed).is_err());
+
+        assert!(open(&key, b"plain text").is_err());
+    }
+
+    #[test]
+    fn debug_hides_key() {
+        let key = CacheKey::from_passphrase("secret");
+        assert!(!format!("{:?}", key).contains("secret"));
+    }
+}
@@ -0,0 +1,286 @@
+//! Detection and merging of bookmarks saved more than once.
+//!
+//! Two pins are duplicates if their urls are the same at [`UrlStrictness::Loose`], i.e. they
+//! only differ by `http` vs `https`, a leading `www.`, fragments, trailing slashes or tracking
+//! parameters. Optionally, pins on the same host whose titles only differ by case,
+//! punctuation or diacritics are duplicates too.
+use std::collections::HashMap;
+use std::fmt;
+
+use url::Url;
+
+use super::cached_data::CachedPin;
+use super::canonical::{canonicalize, UrlStrictness};
+use super::normalize::{self, normalize};
+use super::pin::{Pin, PinBuilder};
+
+/// Pins that are the same bookmark, oldest first.
+#[derive(Debug)]
+pub struct DuplicateGroup<'a, 'pin: 'a> {
+    pub pins: Vec<&'a Pin<'pin>>,
+}
+
+impl<'a, 'pin> DuplicateGroup<'a, 'pin> {
+    /// The pin kept when the group is merged, i.e. the oldest one.
+    pub fn survivor(&self) -> &'a Pin<'pin> {
+        self.pins[0]
+    }
+
+    /// Single pin combining all pins of the group:
+    ///
+    /// - url and title of the survivor (or the first non-empty title)
+    /// - tags of all pins, without duplicates
+    /// - time of the oldest pin
+    /// - distinct descriptions, oldest first, separated by blank lines
+    /// - private if any pin is private, to read if any pin is
+    pub fn merged(&self) -> Pin<'static> {
+        let survivor = self.survivor();
+        let title = self
+            .pins
+            .iter()
+            .map(|p| p.title.as_ref())
+            .find(|t| !t.trim().is_empty())
+            .unwrap_or("");
+        let mut tags: Vec<&str> = Vec::new();
+        for tag in self.pins.iter().flat_map(|p| p.tags.split_whitespace()) {
+            if !tags.iter().any(|t| normalize::eq(t, tag)) {
+                tags.push(tag);
+            }
+        }
+        let mut descriptions: Vec<&str> = Vec::new();
+        for d in self.pins.iter().filter_map(|p| p.extended.as_ref()) {
+            let d = d.trim();
+            if !d.is_empty() && !descriptions.iter().any(|e| normalize::eq(e, d)) {
+                descriptions.push(d);
+            }
+        }
+        let flag = |any: bool, own: &str, value: &str| {
+            if any {
+                value.to_string()
+            } else {
+                own.to_string()
+            }
+        };
+        let mut builder = PinBuilder::new(survivor.url.to_string(), title.to_string())
+            .tags(tags.join(" "))
+            .shared(flag(
+                self.pins.iter().any(|p| p.shared == "no"),
+                &survivor.shared,
+                "no",
+            ))
+            .toread(flag(
+                self.pins.iter().any(|p| p.toread == "yes"),
+                &survivor.toread,
+                "yes",
+            ));
+        if !descriptions.is_empty() {
+            builder = builder.description(descriptions.join("\n\n"));
+        }
+        let mut pin = builder.into_pin();
+        pin.time = self
+            .pins
+            .iter()
+            .map(|p| p.time)
+            .min()
+            .unwrap_or(survivor.time);
+        pin
+    }
+}
+
+/// Outcome of merging a group of duplicates.
+#[derive(Debug)]
+pub struct MergeReport {
+    /// Bookmark replacing the whole group.
+    pub merged: Pin<'static>,
+    /// Urls of the bookmarks deleted, or that would be in a dry run.
+    pub deleted: Vec<String>,
+    /// True if nothing was actually changed.
+    pub dry_run: bool,
+}
+
+impl fmt::Display for MergeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (keep, delete) = if self.dry_run {
+            ("would keep", "would delete")
+        } else {
+            ("kept", "deleted")
+        };
+        writeln!(f, "{} {} [{}]", keep, self.merged.url, self.merged.tags)?;
+        for url in &self.deleted {
+            writeln!(f, "  {} {}", delete, url)?;
+        }
+        Ok(())
+    }
+}
+
+/// Key of a pin's title, made of its normalized words, or `None` if it has none.
+fn title_key(cached_pin: &CachedPin, url: &str) -> Option<String> {
+    let host = Url::parse(url).ok()?.host_str()?.to_string();
+    let words = normalize(&cached_pin.pin.title, true)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if words.is_empty() {
+        None
+    } else {
+        Some([host, words].join("\n"))
+    }
+}
+
+fn root(parents: &mut [usize], mut i: usize) -> usize {
+    while parents[i] != i {
+        parents[i] = parents[parents[i]];
+        i = parents[i];
+    }
+    i
+}
+
+/// Groups of duplicates among `pins`, in the order their newest pin appears in `pins`.
+pub fn find<'a, 'pin>(
+    pins: &'a [CachedPin<'pin>],
+    similar_titles: bool,
+) -> Vec<DuplicateGroup<'a, 'pin>> {
+    let mut parents = (0..pins.len()).collect::<Vec<usize>>();
+    let mut first_with_key: HashMap<String, usize> = HashMap::new();
+    for (i, cached_pin) in pins.iter().enumerate() {
+        let url = canonicalize(&cached_pin.pin.url, UrlStrictness::Loose);
+        let title = if similar_titles {
+            title_key(cached_pin, &url)
+        } else {
+            None
+        };
+        for key in Some(url).into_iter().chain(title) {
+            let j = *first_with_key.entry(key).or_insert(i);
+            let (ri, rj) = (root(&mut parents, i), root(&mut parents, j));
+            parents[ri] = rj;
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut group_of_root: HashMap<usize, usize> = HashMap::new();
+    for i in 0..pins.len() {
+        let r = root(&mut parents, i);
+        let g = *group_of_root.entry(r).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[g].push(i);
+    }
+    groups
+        .into_iter()
+        .filter(|g| g.len() > 1)
+        .map(|g| {
+            let mut group = g.into_iter().map(|i| &pins[i].pin).collect::<Vec<&Pin>>();
+            group.sort_by_key(|p| p.time);
+            DuplicateGroup { pins: group }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::*;
+    use env_logger;
+
+    fn cached_pin(url: &str, title: &str, tags: &str, desc: &str, day: u32) -> CachedPin<'static> {
+        let mut builder = PinBuilder::new(url.to_string(), title.to_string())
+            .tags(tags.to_string())
+            .shared("yes")
+            .toread("no");
+        if !desc.is_empty() {
+            builder = builder.description(desc.to_string());
+        }
+        let mut pin = builder.into_pin();
+        pin.time = Utc.with_ymd_and_hms(2020, 1, day, 0, 0, 0).unwrap();
+        CachedPin::new(pin)
+    }
+
+    fn urls(group: &DuplicateGroup) -> Vec<String> {
+        group.pins.iter().map(|p| p.url.to_string()).collect()
+    }
+
+    #[test]
+    fn groups_by_url() {
+        let _ = env_logger::try_init();
+        debug!("groups_by_url: starting");
+        let pins = vec![
+            cached_pin("https://www.rust-lang.org/?utm_source=x", "Rust", "", "", 3),
+            cached_pin("https://golang.org/", "Go", "", "", 2),
+            cached_pin("http://rust-lang.org", "Rust Lang", "", "", 1),
+            cached_pin("https://rust-lang.org/#news", "Rust", "", "", 4),
+            cached_pin("https://rust-lang.org/learn", "Learn Rust", "", "", 5),
+        ];
+        let groups = find(&pins, false);
+        assert_eq!(1, groups.len());
+        assert_eq!(
+            vec![
+                "http://rust-lang.org",
+                "https://www.rust-lang.org/?utm_source=x",
+                "https://rust-lang.org/#news",
+            ],
+            urls(&groups[0])
+        );
+        assert_eq!("http://rust-lang.org", groups[0].survivor().url);
+        assert!(find(&pins[..2], false).is_empty());
+    }
+
+    #[test]
+    fn groups_by_title() {
+        let _ = env_logger::try_init();
+        debug!("groups_by_title: starting");
+        let pins = vec![
+            cached_pin("https://a.com/post?id=1", "Café: the Menu", "", "", 1),
+            cached_pin("https://a.com/post?id=2", "cafe - THE menu!", "", "", 2),
+            cached_pin("https://b.com/post", "Cafe the menu", "", "", 3),
+            cached_pin("https://a.com/other", "", "", "", 4),
+            cached_pin("https://a.com/another", "", "", "", 5),
+        ];
+        assert!(find(&pins, false).is_empty());
+        let groups = find(&pins, true);
+        assert_eq!(1, groups.len());
+        assert_eq!(
+            vec!["https://a.com/post?id=1", "https://a.com/post?id=2"],
+            urls(&groups[0])
+        );
+    }
+
+    #[test]
+    fn merges() {
+        let _ = env_logger::try_init();
+        debug!("merges: starting");
+        let mut pins = vec![
+            cached_pin("https://a.com/", "", "rust Tools", "second", 2),
+            cached_pin("http://www.a.com", "A", "tools news", "first", 1),
+            cached_pin("https://a.com/#x", "A!", "RUST", "First", 3),
+        ];
+        pins[2].pin.shared = "no".into();
+        let groups = find(&pins, false);
+        let merged = groups[0].merged();
+        assert_eq!("http://www.a.com", merged.url);
+        assert_eq!("A", merged.title);
+        assert_eq!("tools news rust", merged.tags);
+        assert_eq!(
+            Some("first\n\nsecond"),
+            merged.extended.as_ref().map(|e| e.as_ref())
+        );
+        assert_eq!(
+            Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap(),
+            merged.time
+        );
+        assert_eq!("no", merged.shared);
+        assert_eq!("no", merged.toread);
+
+        let report = MergeReport {
+            merged,
+            deleted: vec!["https://a.com/".into(), "https://a.com/#x".into()],
+            dry_run: true,
+        };
+        assert_eq!(
+            "would keep http://www.a.com [tools news rust]\n  would delete https://a.com/\n  \
+             would delete https://a.com/#x\n",
+            report.to_string()
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Restrictions on bookmark attributes, applied on top of a search.
+//!
+//! Dates follow the [`Query`](../query/enum.Query.html) `before:`/`after:` semantics: a pin
+//! created exactly at the `created_after` time is kept, one created at `created_before` isn't.
+use chrono::prelude::*;
+
+use super::pin::Pin;
+
+/// Attributes bookmarks must have to be returned by a search. Every criterion is optional
+/// and the default filter keeps all bookmarks.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SearchFilter {
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    /// `Some(true)` keeps only unread bookmarks, `Some(false)` only read ones.
+    pub toread: Option<bool>,
+    /// `Some(true)` keeps only private bookmarks, `Some(false)` only public ones.
+    pub private: Option<bool>,
+    /// `Some(true)` keeps only bookmarks with a (non blank) description.
+    pub has_description: Option<bool>,
+    pub min_tags: Option<usize>,
+    pub max_tags: Option<usize>,
+}
+
+impl SearchFilter {
+    pub fn new() -> Self {
+        SearchFilter::default()
+    }
+
+    /// Keeps bookmarks created at or after `time`.
+    pub fn created_after(mut self, time: DateTime<Utc>) -> Self {
+        self.created_after = Some(time);
+        self
+    }
+
+    /// Keeps bookmarks created before `time`.
+    pub fn created_before(mut self, time: DateTime<Utc>) -> Self {
+        self.created_before = Some(time);
+        self
+    }
+
+    pub fn unread_only(mut self) -> Self {
+        self.toread = Some(true);
+        self
+    }
+
+    pub fn private_only(mut self) -> Self {
+        self.private = Some(true);
+        self
+    }
+
+    pub fn public_only(mut self) -> Self {
+        self.private = Some(false);
+        self
+    }
+
+    pub fn has_description(mut self, v: bool) -> Self {
+        self.has_description = Some(v);
+        self
+    }
+
+    /// Keeps bookmarks with at least `min` and at most `max` tags.
+    pub fn tag_count(mut self, min: Option<usize>, max: Option<usize>) -> Self {
+        self.min_tags = min;
+        self.max_tags = max;
+        self
+    }
+
+    /// Returns true if `pin` meets all the criteria.
+    pub fn matches(&self, pin: &Pin) -> bool {
+        let tags = pin.tags.split_whitespace().count();
+        let has_description = pin.extended.as_ref().map_or(false, |e| !e.trim().is_empty());
+        self.created_after.map_or(true, |t| pin.time >= t)
+            && self.created_before.map_or(true, |t| pin.time < t)
+            && self.toread.map_or(true, |v| (pin.toread == "yes") == v)
+            && self.private.map_or(true, |v| (pin.shared == "no") == v)
+            && self.has_description.map_or(true, |v| has_description == v)
+            && self.min_tags.map_or(true, |n| tags >= n)
+            && self.max_tags.map_or(true, |n| tags <= n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use env_logger;
+
+    fn pin(tags: &str, toread: &str, shared: &str, desc: &str, day: u32) -> Pin<'static> {
+        let mut pin = PinBuilder::new("https://a.com/".to_string(), "A".to_string())
+            .tags(tags.to_string())
+            .toread(toread.to_string())
+            .shared(shared.to_string())
+            .description(desc.to_string())
+            .into_pin();
+        pin.time = Utc.with_ymd_and_hms(2020, 1, day, 0, 0, 0).unwrap();
+        pin
+    }
+
+    #[test]
+    fn filters_attributes() {
+        let _ = env_logger::try_init();
+        debug!("filters_attributes: starting");
+        let unread = pin("rust async", "yes", "no", "", 10);
+        let read = pin("", "no", "yes", "  notes ", 20);
+        assert!(SearchFilter::new().matches(&unread));
+        assert!(SearchFilter::new().matches(&read));
+
+        let unread_only = SearchFilter::new().unread_only();
+        assert!(unread_only.matches(&unread));
+        assert!(!unread_only.matches(&read));
+        assert!(SearchFilter::new().private_only().matches(&unread));
+        assert!(!SearchFilter::new().public_only().matches(&unread));
+        assert!(SearchFilter::new().public_only().matches(&read));
+
+        assert!(SearchFilter::new().has_description(true).matches(&read));
+        assert!(!SearchFilter::new().has_description(true).matches(&unread));
+        assert!(SearchFilter::new().has_description(false).matches(&unread));
+
+        let two_tags = SearchFilter::new().tag_count(Some(2), Some(2));
+        assert!(two_tags.matches(&unread));
+        assert!(!two_tags.matches(&read));
+        assert!(SearchFilter::new().tag_count(None, Some(0)).matches(&read));
+
+        // Combined criteria must all hold
+        let f = SearchFilter::new().unread_only().has_description(true);
+        assert!(!f.matches(&unread) && !f.matches(&read));
+    }
+
+    #[test]
+    fn filters_dates() {
+        let _ = env_logger::try_init();
+        debug!("filters_dates: starting");
+        let day = |d| Utc.with_ymd_and_hms(2020, 1, d, 0, 0, 0).unwrap();
+        let p = pin("", "no", "yes", "", 10);
+        assert!(SearchFilter::new().created_after(day(10)).matches(&p));
+        assert!(!SearchFilter::new().created_after(day(11)).matches(&p));
+        assert!(!SearchFilter::new().created_before(day(10)).matches(&p));
+        assert!(SearchFilter::new().created_before(day(11)).matches(&p));
+        let range = SearchFilter::new()
+            .created_after(day(1))
+            .created_before(day(31));
+        assert!(range.matches(&p));
+    }
+}
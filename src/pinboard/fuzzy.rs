@@ -0,0 +1,339 @@
+//! Fuzzy matching of a pattern against a text.
+//!
+//! A pattern matches a text if all of its characters appear in the text in the same order,
+//! ignoring case. Among all the ways a pattern can match, the best one is found with a
+//! Smith-Waterman like dynamic programming, scored the way fzf does:
+//!
+//! - every matched character is worth the same
+//! - characters matched at the start of a word or where letters and digits meet get a bonus
+//! - characters matched right after the previous one get a bonus, at least as big as the
+//!   one of the first character of their run
+//! - gaps between matched characters are penalized, the first skipped character more
+//!   than the following ones
+//!
+//! Texts are matched once normalized, so there is no bonus for camelCase humps. Texts are
+//! never interpreted, so any character (including regex meta characters such as
+//! `( ) | ? * + [ ]`) is matched literally.
+use std::mem;
+
+const SCORE_MATCH: i64 = 16;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_DIGIT: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 4;
+/// Bonus of the first matched character is multiplied by this.
+const FIRST_CHAR_MULTIPLIER: i64 = 2;
+/// Texts needing a bigger backtracking matrix than this are matched greedily instead.
+const MAX_MATRIX_SIZE: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CharClass {
+    NonWord,
+    Digit,
+    Letter,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        if c.is_numeric() {
+            CharClass::Digit
+        } else if c.is_alphabetic() {
+            CharClass::Letter
+        } else {
+            CharClass::NonWord
+        }
+    }
+}
+
+/// Bonus for matching a character of class `class` that follows one of class `prev`.
+fn bonus(prev: CharClass, class: CharClass) -> i64 {
+    match (prev, class) {
+        (_, CharClass::NonWord) => 0,
+        (CharClass::NonWord, _) => BONUS_BOUNDARY,
+        (CharClass::Digit, _) | (_, CharClass::Digit) if prev != class => BONUS_DIGIT,
+        _ => 0,
+    }
+}
+
+/// Case folds a single character, keeping a one to one mapping between characters.
+pub fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Best way a pattern matched a text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzyMatch {
+    /// Higher is better. Only comparable between matches of the same pattern.
+    pub score: i64,
+    /// Byte offsets of the matched characters within the text, in increasing order.
+    pub positions: Vec<usize>,
+    /// Highest score the pattern can get.
+    max_score: i64,
+}
+
+impl FuzzyMatch {
+    /// Score relative to the best possible one, between 0 and 1.
+    pub fn quality(&self) -> f64 {
+        if self.max_score <= 0 {
+            1.0
+        } else {
+            (self.score.max(0) as f64 / self.max_score as f64).min(1.0)
+        }
+    }
+}
+
+/// Case insensitive subsequence matcher for a single pattern.
+#[derive(Debug, Clone)]
+pub struct FuzzyMatcher {
+    pattern: Vec<char>,
+}
+
+impl FuzzyMatcher {
+    pub fn new(pattern: &str) -> Self {
+        FuzzyMatcher {
+            pattern: pattern.chars().map(fold).collect(),
+        }
+    }
+
+    /// Returns true if all characters of the pattern appear in `text` in order.
+    pub fn is_match(&self, text: &str) -> bool {
+        let mut wanted = self.pattern.iter().peekable();
+        for c in text.chars() {
+            match wanted.peek() {
+                Some(&&w) if w == fold(c) => {
+                    wanted.next();
+                }
+                Some(_) => (),
+                None => break,
+            }
+        }
+        wanted.peek().is_none()
+    }
+
+    /// Finds the best scoring match of the pattern within `text`.
+    pub fn find(&self, text: &str) -> Option<FuzzyMatch> {
+        if !self.is_match(text) {
+            return None;
+        }
+        let max_score = self.max_score();
+        if self.pattern.is_empty() {
+            return Some(FuzzyMatch {
+                score: 0,
+                positions: Vec::new(),
+                max_score,
+            });
+        }
+
+        let chars = text.char_indices().collect::<Vec<(usize, char)>>();
+        let (n, m) = (chars.len(), self.pattern.len());
+        if n.saturating_mul(m) > MAX_MATRIX_SIZE {
+            return Some(self.find_greedy(&chars, max_score));
+        }
+
+        let mut folded = Vec::with_capacity(n);
+        let mut bonuses = Vec::with_capacity(n);
+        let mut prev = CharClass::NonWord;
+        for &(_, c) in &chars {
+            let class = CharClass::of(c);
+            folded.push(fold(c));
+            bonuses.push(bonus(prev, class));
+            prev = class;
+        }
+
+        // Only two rows of scores are kept, row i being for pattern[..=i]:
+        // score[j]: best score of matching pattern[..=i] with pattern[i] at text[j].
+        // run[j]: bonus of the first character of the run of consecutive matches.
+        // from[i * n + j]: where pattern[i - 1] was matched in that case, to backtrack.
+        let mut prev_score: Vec<Option<i64>> = vec![None; n];
+        let mut prev_run = vec![0i64; n];
+        let mut score: Vec<Option<i64>> = vec![None; n];
+        let mut run = vec![0i64; n];
+        let mut from = vec![0u32; m * n];
+        for (i, &p) in self.pattern.iter().enumerate() {
+            // Best (score, position) of the previous row that leaves a gap before j.
+            let mut gapped: Option<(i64, usize)> = None;
+            for j in 0..n {
+                score[j] = None;
+                if i > 0 && j >= 2 {
+                    let extended = gapped.map(|(s, k)| (s - PENALTY_GAP_EXTENSION, k));
+                    let started = prev_score[j - 2].map(|s| (s - PENALTY_GAP_START, j - 2));
+                    gapped = match (extended, started) {
+                        (Some(e), Some(s)) if s.0 >= e.0 => Some(s),
+                        (Some(e), _) => Some(e),
+                        (None, s) => s,
+                    };
+                }
+                if folded[j] != p {
+                    continue;
+                }
+                if i == 0 {
+                    score[j] = Some(SCORE_MATCH + bonuses[j] * FIRST_CHAR_MULTIPLIER);
+                    run[j] = bonuses[j];
+                    continue;
+                }
+                let consecutive = if j >= 1 {
+                    prev_score[j - 1].map(|s| {
+                        let b = bonuses[j].max(BONUS_CONSECUTIVE).max(prev_run[j - 1]);
+                        (s + b, j - 1)
+                    })
+                } else {
+                    None
+                };
+                let gap = gapped.map(|(s, k)| (s + bonuses[j], k));
+                let best = match (consecutive, gap) {
+                    (Some(c), Some(g)) if g.0 > c.0 => Some(g),
+                    (Some(c), _) => Some(c),
+                    (None, g) => g,
+                };
+                if let Some((s, k)) = best {
+                    score[j] = Some(s + SCORE_MATCH);
+                    from[i * n + j] = k as u32;
+                    run[j] = if k + 1 == j { prev_run[k] } else { bonuses[j] };
+                }
+            }
+            mem::swap(&mut score, &mut prev_score);
+            mem::swap(&mut run, &mut prev_run);
+        }
+
+        let (best, mut j) = prev_score
+            .iter()
+            .enumerate()
+            .filter_map(|(j, s)| s.map(|s| (s, j)))
+            .fold(None, |acc: Option<(i64, usize)>, (s, j)| match acc {
+                Some((a, _)) if a >= s => acc,
+                _ => Some((s, j)),
+            })?;
+        let mut positions = vec![0; m];
+        for i in (0..m).rev() {
+            positions[i] = chars[j].0;
+            j = from[i * n + j] as usize;
+        }
+        Some(FuzzyMatch {
+            score: best,
+            positions,
+            max_score,
+        })
+    }
+
+    /// Leftmost match, used when the text is too long to be scored properly.
+    fn find_greedy(&self, chars: &[(usize, char)], max_score: i64) -> FuzzyMatch {
+        let mut positions = Vec::with_capacity(self.pattern.len());
+        let mut wanted = self.pattern.iter().peekable();
+        for &(pos, c) in chars {
+            match wanted.peek() {
+                Some(&&w) if w == fold(c) => {
+                    positions.push(pos);
+                    wanted.next();
+                }
+                Some(_) => (),
+                None => break,
+            }
+        }
+        FuzzyMatch {
+            score: SCORE_MATCH * positions.len() as i64,
+            positions,
+            max_score,
+        }
+    }
+
+    /// Score of the pattern matched at the start of a word, all in one run.
+    fn max_score(&self) -> i64 {
+        let m = self.pattern.len() as i64;
+        if m == 0 {
+            return 0;
+        }
+        SCORE_MATCH * m
+            + BONUS_BOUNDARY * FIRST_CHAR_MULTIPLIER
+            + BONUS_BOUNDARY.max(BONUS_CONSECUTIVE) * (m - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger;
+
+    fn matched<'a>(text: &'a str, m: &FuzzyMatch) -> Vec<&'a str> {
+        m.positions
+            .iter()
+            .map(|&p| &text[p..p + text[p..].chars().next().unwrap().len_utf8()])
+            .collect()
+    }
+
+    #[test]
+    fn subsequence() {
+        let _ = env_logger::try_init();
+        debug!("subsequence: starting");
+        let m = FuzzyMatcher::new("HAMID");
+        assert!(m.is_match("hamid"));
+        assert!(m.is_match("Hello, Any Mail Is Delivered"));
+        assert!(!m.is_match("diham"));
+        assert!(!m.is_match(""));
+        assert!(FuzzyMatcher::new("").is_match(""));
+        assert!(FuzzyMatcher::new("").find("anything").is_some());
+        assert!(FuzzyMatcher::new("здрав").is_match("https://githuуй.com/Здравствуйт"));
+    }
+
+    #[test]
+    fn special_characters() {
+        let _ = env_logger::try_init();
+        debug!("special_characters: starting");
+        for pattern in &[
+            "(", ")", "|", "?", "*", "+", "[", "]", "()|?*+[]", ".*", "\\", "^$",
+        ] {
+            let m = FuzzyMatcher::new(pattern);
+            let text = format!("a{}b", pattern);
+            assert!(m.is_match(&text), "{} didn't match {}", pattern, text);
+            assert!(m.find(&text).is_some());
+            assert!(!m.is_match("ab"), "{} matched ab", pattern);
+        }
+        assert!(FuzzyMatcher::new("c++").is_match("C/C++ reference"));
+        assert!(!FuzzyMatcher::new("c++").is_match("ccc"));
+        assert!(FuzzyMatcher::new("a|b").is_match("a | b"));
+        assert!(!FuzzyMatcher::new("a|b").is_match("a"));
+        assert!(FuzzyMatcher::new("[rust]").is_match("[tag: rust]"));
+    }
+
+    #[test]
+    fn prefers_word_starts_and_runs() {
+        let _ = env_logger::try_init();
+        debug!("prefers_word_starts_and_runs: starting");
+        let m = FuzzyMatcher::new("rust");
+        // Leftmost matching would pick the scattered characters of "trust us"
+        let text = "trust us - rust";
+        let found = m.find(text).unwrap();
+        assert_eq!(vec![11, 12, 13, 14], found.positions);
+        assert_eq!(1.0, found.quality());
+
+        let m = FuzzyMatcher::new("fb");
+        let text = "xfoo_bar";
+        assert_eq!(vec!["f", "b"], matched(text, &m.find(text).unwrap()));
+        assert_eq!(vec![1, 5], m.find(text).unwrap().positions);
+
+        let m = FuzzyMatcher::new("gh");
+        assert!(m.find("GitHub").unwrap().score > m.find("laughing").unwrap().score);
+        assert!(m.find("gh").unwrap().score > m.find("g----h").unwrap().score);
+        let q = m.find("g--------------h").unwrap().quality();
+        assert!(q > 0.0 && q < m.find("g-h").unwrap().quality());
+    }
+
+    #[test]
+    fn non_ascii_positions() {
+        let _ = env_logger::try_init();
+        debug!("non_ascii_positions: starting");
+        let text = "https://githuуй.com/Здравствуйт?q=13#fragment";
+        let found = FuzzyMatcher::new("ЗДРТ").find(text).unwrap();
+        assert_eq!(vec!["З", "д", "р", "т"], matched(text, &found));
+        assert_eq!(22, found.positions[0]);
+    }
+
+    #[test]
+    fn long_text() {
+        let _ = env_logger::try_init();
+        debug!("long_text: starting");
+        let text = format!("{}needle", "x".repeat(MAX_MATRIX_SIZE));
+        let found = FuzzyMatcher::new("needle").find(&text).unwrap();
+        assert_eq!(MAX_MATRIX_SIZE, found.positions[0]);
+    }
+}
@@ -0,0 +1,317 @@
+//! Report on tags that probably need cleaning up.
+//!
+//! - tags that look like another, more used tag: same but for case (`Rust`, `rust`), plural
+//!   (`tutorial`, `tutorials`), punctuation (`cross-compile`, `crosscompile`) or a typo
+//!   (`javascript`, `javasript`)
+//! - tags used on a single bookmark
+//! - tags only used on bookmarks without any other tag
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::cached_data::{CachedPin, CachedTag};
+use super::normalize::normalize;
+use super::tag::{Tag, TagFreq};
+
+/// Shortest tag, in letters and digits, compared for punctuation, plurals and typos.
+const MIN_SIMILAR_LEN: usize = 3;
+/// Shortest tag, in letters and digits, that may contain a typo.
+const MIN_TYPO_LEN: usize = 5;
+/// Shortest tag, in letters and digits, that may contain two typos.
+const MIN_TWO_TYPOS_LEN: usize = 9;
+
+/// How a tag looks like another one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Similarity {
+    Case,
+    Plural,
+    Punctuation,
+    Typo,
+}
+
+impl fmt::Display for Similarity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Similarity::Case => write!(f, "case"),
+            Similarity::Plural => write!(f, "plural"),
+            Similarity::Punctuation => write!(f, "punctuation"),
+            Similarity::Typo => write!(f, "typo"),
+        }
+    }
+}
+
+/// A tag that should be renamed to the one it looks like.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagRename {
+    pub from: Tag,
+    pub to: Tag,
+    pub reason: Similarity,
+}
+
+impl fmt::Display for TagRename {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}) -> {} ({}): {}",
+            self.from.0, self.from.1, self.to.0, self.to.1, self.reason
+        )
+    }
+}
+
+/// Outcome of checking the tags of all bookmarks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HygieneReport {
+    /// Near-duplicate tags, each to be renamed to the most used tag it looks like.
+    pub renames: Vec<TagRename>,
+    /// Tags used on a single bookmark.
+    pub singletons: Vec<Tag>,
+    /// Tags never used together with another tag.
+    pub isolated: Vec<Tag>,
+}
+
+impl HygieneReport {
+    /// Returns true if nothing needs cleaning up.
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty() && self.singletons.is_empty() && self.isolated.is_empty()
+    }
+}
+
+impl fmt::Display for HygieneReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "near-duplicates: {}", self.renames.len())?;
+        for rename in &self.renames {
+            writeln!(f, "  {}", rename)?;
+        }
+        let names = |tags: &[Tag]| {
+            tags.iter()
+                .map(|t| t.0.as_str())
+                .collect::<Vec<&str>>()
+                .join(" ")
+        };
+        writeln!(f, "used once: {}", names(&self.singletons))?;
+        writeln!(f, "never used with other tags: {}", names(&self.isolated))
+    }
+}
+
+/// Letters and digits of a normalized tag.
+fn alphanumeric(key: &str) -> String {
+    key.chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+/// Returns true if `plural` is the English plural of `word`, more or less.
+fn is_plural(word: &str, plural: &str) -> bool {
+    match plural.strip_prefix(word) {
+        Some("s") => !word.ends_with('s'),
+        Some("es") => true,
+        _ => {
+            word.ends_with('y')
+                && plural.ends_with("ies")
+                && word[..word.len() - 1] == plural[..plural.len() - 3]
+        }
+    }
+}
+
+/// Number of edits (insertion, deletion, substitution or swap of two adjacent chars) to go
+/// from `a` to `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<char>>();
+    let b = b.chars().collect::<Vec<char>>();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+/// How `tag` looks like the more used tag `other`, if it does. Both are normalized.
+fn similarity(tag: &str, other: &str, rarer: bool) -> Option<Similarity> {
+    if tag == other {
+        return Some(Similarity::Case);
+    }
+    let (a, b) = (alphanumeric(tag), alphanumeric(other));
+    let len = a.chars().count().min(b.chars().count());
+    if len < MIN_SIMILAR_LEN {
+        return None;
+    }
+    if a == b {
+        Some(Similarity::Punctuation)
+    } else if is_plural(&a, &b) || is_plural(&b, &a) {
+        Some(Similarity::Plural)
+    } else if rarer && len >= MIN_TYPO_LEN {
+        let max = if len >= MIN_TWO_TYPOS_LEN { 2 } else { 1 };
+        let diff = a.chars().count().max(b.chars().count()) - len;
+        if diff <= max && edit_distance(&a, &b) <= max {
+            Some(Similarity::Typo)
+        } else {
+            None
+        }
+    } else {
+        None
+    }
+}
+
+fn count(tag: &Tag) -> usize {
+    match tag.1 {
+        TagFreq::Used(n) => n,
+        _ => 0,
+    }
+}
+
+/// Checks `tags`, as used by `pins`.
+pub fn check(tags: &[CachedTag], pins: &[CachedPin]) -> HygieneReport {
+    let mut report = HygieneReport::default();
+
+    // Most used first, so that tags are renamed to the most used one they look like.
+    let mut by_use = tags.iter().collect::<Vec<&CachedTag>>();
+    by_use.sort_by_key(|t| Reverse(count(&t.tag)));
+    let mut kept: Vec<(&CachedTag, String)> = Vec::new();
+    for cached_tag in by_use {
+        let key = normalize(&cached_tag.tag.0, false);
+        let found = kept.iter().find_map(|(other, other_key)| {
+            let rarer = count(&cached_tag.tag) < count(&other.tag);
+            similarity(&key, other_key, rarer).map(|reason| (*other, reason))
+        });
+        match found {
+            Some((other, reason)) => report.renames.push(TagRename {
+                from: cached_tag.tag.clone(),
+                to: other.tag.clone(),
+                reason,
+            }),
+            None => kept.push((cached_tag, key)),
+        }
+    }
+
+    // Whether each tag was ever used along with another one.
+    let mut paired: HashMap<&str, bool> = HashMap::new();
+    for cached_pin in pins {
+        let alone = cached_pin.tag_list.len() == 1;
+        for tag in &cached_pin.tag_list {
+            *paired.entry(tag.as_str()).or_insert(false) |= !alone;
+        }
+    }
+    for cached_tag in tags {
+        if count(&cached_tag.tag) == 1 {
+            report.singletons.push(cached_tag.tag.clone());
+        }
+        if paired.get(cached_tag.tag_lowered.as_str()) == Some(&false) {
+            report.isolated.push(cached_tag.tag.clone());
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use env_logger;
+
+    fn cached_tags(tags: &[(&str, usize)]) -> Vec<CachedTag> {
+        tags.iter()
+            .map(|&(t, n)| CachedTag::new(Tag::new(t.to_string(), n), false))
+            .collect()
+    }
+
+    fn renames(report: &HygieneReport) -> Vec<(&str, &str, Similarity)> {
+        report
+            .renames
+            .iter()
+            .map(|r| (r.from.0.as_str(), r.to.0.as_str(), r.reason))
+            .collect()
+    }
+
+    #[test]
+    fn finds_near_duplicates() {
+        let _ = env_logger::try_init();
+        debug!("finds_near_duplicates: starting");
+        let tags = cached_tags(&[
+            ("Rust", 2),
+            ("rust", 10),
+            ("tutorials", 3),
+            ("tutorial", 1),
+            ("cross-compile", 1),
+            ("crosscompile", 4),
+            ("javasript", 1),
+            ("javascript", 8),
+            ("library", 2),
+            ("libraries", 1),
+            ("c", 5),
+            ("c++", 3),
+            ("go", 2),
+            ("gos", 1),
+            ("macos", 3),
+            ("macro", 3),
+        ]);
+        let report = check(&tags, &[]);
+        assert_eq!(
+            vec![
+                ("Rust", "rust", Similarity::Case),
+                ("tutorial", "tutorials", Similarity::Plural),
+                ("cross-compile", "crosscompile", Similarity::Punctuation),
+                ("javasript", "javascript", Similarity::Typo),
+                ("libraries", "library", Similarity::Plural),
+            ],
+            renames(&report)
+        );
+        assert_eq!(
+            "javasript (1) -> javascript (8): typo",
+            report.renames[3].to_string()
+        );
+    }
+
+    #[test]
+    fn finds_singletons_and_isolated_tags() {
+        let _ = env_logger::try_init();
+        debug!("finds_singletons_and_isolated_tags: starting");
+        let pins = [("a", "rust async"), ("b", "rust"), ("c", "misc"), ("d", "misc")]
+            .iter()
+            .map(|&(url, tags)| {
+                CachedPin::new(
+                    PinBuilder::new(format!("https://{}.com/", url), url.to_string())
+                        .tags(tags.to_string())
+                        .into_pin(),
+                )
+            })
+            .collect::<Vec<CachedPin>>();
+        let tags = cached_tags(&[("rust", 2), ("Async", 1), ("misc", 2)]);
+        let report = check(&tags, &pins);
+        assert!(report.renames.is_empty());
+        assert_eq!(vec![Tag::new("Async".into(), 1)], report.singletons);
+        assert_eq!(vec![Tag::new("misc".into(), 2)], report.isolated);
+        assert!(!report.is_empty());
+        assert!(check(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn detects_plurals() {
+        let _ = env_logger::try_init();
+        debug!("detects_plurals: starting");
+        assert!(is_plural("case", "cases"));
+        assert!(is_plural("box", "boxes"));
+        assert!(is_plural("library", "libraries"));
+        assert!(!is_plural("rust", "rusty"));
+        assert!(!is_plural("class", "classs"));
+        assert!(!is_plural("tutorials", "tutorial"));
+    }
+
+    #[test]
+    fn measures_edit_distance() {
+        let _ = env_logger::try_init();
+        debug!("measures_edit_distance: starting");
+        assert_eq!(0, edit_distance("rust", "rust"));
+        assert_eq!(1, edit_distance("rust", "rusty"));
+        assert_eq!(1, edit_distance("rust", "rsut"));
+        assert_eq!(3, edit_distance("kitten", "sitting"));
+        assert_eq!(1, edit_distance("café", "cafe"));
+    }
+}
@@ -0,0 +1,298 @@
+//! Inverted index of the searchable text of cached pins and tags.
+//!
+//! For each field, every trigram (three consecutive chars) and every (case folded) char of
+//! the field's text is mapped to the sorted ids of the pins having it, an id being the
+//! position of the pin in the cache. A pin can only contain a query if it has all of the
+//! query's trigrams and chars, and can only fuzzily match it if it has all of its chars.
+//! Intersecting those lists gives the few pins worth checking.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use super::cached_data::{CachedPin, CachedTag};
+use super::fuzzy::fold;
+use super::normalize::normalize;
+use super::search::Field;
+
+type Gram = u64;
+type Postings = HashMap<Gram, Vec<u32>>;
+
+const UNIGRAM_FLAG: Gram = 1 << 63;
+
+fn unigram(c: char) -> Gram {
+    UNIGRAM_FLAG | u64::from(fold(c))
+}
+
+// A char fits in 21 bits.
+fn trigram(a: char, b: char, c: char) -> Gram {
+    u64::from(a) << 42 | u64::from(b) << 21 | u64::from(c)
+}
+
+/// Grams of `text`: its chars and, unless `chars_only`, its trigrams.
+fn grams(text: &str, chars_only: bool) -> Vec<Gram> {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut grams = chars.iter().map(|&c| unigram(c)).collect::<Vec<Gram>>();
+    if !chars_only {
+        grams.extend(chars.windows(3).map(|w| trigram(w[0], w[1], w[2])));
+    }
+    grams.sort_unstable();
+    grams.dedup();
+    grams
+}
+
+fn add(postings: &mut Postings, id: u32, texts: &[&str]) {
+    let mut all = texts
+        .iter()
+        .flat_map(|t| grams(t, false))
+        .collect::<Vec<Gram>>();
+    all.sort_unstable();
+    all.dedup();
+    // Ids are added in increasing order, so lists stay sorted.
+    for g in all {
+        postings.entry(g).or_default().push(id);
+    }
+}
+
+/// Ids (out of `count`) that may have all `grams`, or `None` if the grams are too common to
+/// narrow anything down. Grams found in more than half of the ids are ignored: checking
+/// those ids directly is cheaper than intersecting such long lists.
+fn lookup(postings: &Postings, grams: &[Gram], count: usize) -> Option<Vec<u32>> {
+    let mut lists = grams
+        .iter()
+        .map(|g| postings.get(g).map_or(&[][..], |ids| ids.as_slice()))
+        .filter(|ids| ids.len() <= count / 2)
+        .collect::<Vec<&[u32]>>();
+    lists.sort_by_key(|ids| ids.len());
+    let (first, rest) = lists.split_first()?;
+    let mut result = first.to_vec();
+    for ids in rest {
+        if result.is_empty() {
+            break;
+        }
+        result.retain(|id| ids.binary_search(id).is_ok());
+    }
+    Some(result)
+}
+
+/// Hash of the indexed text of `pins` and `tags`, which tells whether an index was built from
+/// them.
+pub fn fingerprint(pins: &[CachedPin], tags: &[CachedTag]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    pins.len().hash(&mut hasher);
+    for cached_pin in pins {
+        cached_pin.title_lowered.hash(&mut hasher);
+        cached_pin.tag_list.hash(&mut hasher);
+        cached_pin.url_lowered.hash(&mut hasher);
+        cached_pin.extended_lowered.hash(&mut hasher);
+    }
+    tags.len().hash(&mut hasher);
+    for cached_tag in tags {
+        cached_tag.tag_lowered.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct SearchIndex {
+    pin_count: usize,
+    tag_count: usize,
+    /// Fingerprint of the indexed pins and tags.
+    fingerprint: u64,
+    strip_diacritics: bool,
+    title: Postings,
+    tags: Postings,
+    url: Postings,
+    description: Postings,
+    tag_names: Postings,
+}
+
+impl SearchIndex {
+    /// Indexes the normalized fields of `pins` and `tags`, which were computed with the given
+    /// `strip_diacritics` setting.
+    pub fn new(pins: &[CachedPin], tags: &[CachedTag], strip_diacritics: bool) -> Self {
+        debug!("SearchIndex::new: starting");
+        let mut index = SearchIndex {
+            pin_count: pins.len(),
+            tag_count: tags.len(),
+            fingerprint: fingerprint(pins, tags),
+            strip_diacritics,
+            ..Default::default()
+        };
+        for (id, cached_pin) in pins.iter().enumerate() {
+            let id = id as u32;
+            // Index the normalized fields that searches look into.
+            add(&mut index.title, id, &[&cached_pin.title_lowered]);
+            let tags = cached_pin
+                .tag_list
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<&str>>();
+            add(&mut index.tags, id, &tags);
+            add(&mut index.url, id, &[&cached_pin.url_lowered]);
+            if let Some(ref extended) = cached_pin.extended_lowered {
+                add(&mut index.description, id, &[extended]);
+            }
+        }
+        for (id, cached_tag) in tags.iter().enumerate() {
+            add(&mut index.tag_names, id as u32, &[&cached_tag.tag_lowered]);
+        }
+        index
+    }
+
+    /// Returns true if this index was built from the given number of pins and tags, whose
+    /// text has the given [`fingerprint`](fn.fingerprint.html).
+    pub fn is_built_for(&self, pin_count: usize, tag_count: usize, fingerprint: u64) -> bool {
+        self.pin_count == pin_count && self.tag_count == tag_count && self.fingerprint == fingerprint
+    }
+
+    /// Fingerprint of the pins and tags this index was built from.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Returns true if diacritics were stripped from the indexed text.
+    pub fn strips_diacritics(&self) -> bool {
+        self.strip_diacritics
+    }
+
+    fn query_grams(&self, query: &str, fuzzy: bool) -> Vec<Gram> {
+        grams(&normalize(query, self.strip_diacritics), fuzzy)
+    }
+
+    /// Ids of the pins that may contain (or fuzzily match) `query` in any of `fields`, sorted.
+    /// Returns `None` if the query can't narrow down the pins, e.g. it is empty.
+    pub fn pins(&self, query: &str, fuzzy: bool, fields: &[Field]) -> Option<Vec<u32>> {
+        let grams = self.query_grams(query, fuzzy);
+        let mut ids = Vec::new();
+        for field in fields {
+            let postings = match *field {
+                Field::Title => &self.title,
+                Field::Tags => &self.tags,
+                Field::Url => &self.url,
+                Field::Description => &self.description,
+            };
+            ids.extend(lookup(postings, &grams, self.pin_count)?);
+        }
+        ids.sort_unstable();
+        ids.dedup();
+        Some(ids)
+    }
+
+    /// Ids of the tags that may contain (or fuzzily match) `query`, sorted.
+    /// Returns `None` if the query can't narrow down the tags, e.g. it is empty.
+    pub fn tags(&self, query: &str, fuzzy: bool) -> Option<Vec<u32>> {
+        let grams = self.query_grams(query, fuzzy);
+        lookup(&self.tag_names, &grams, self.tag_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use crate::pinboard::tag::{Tag, TagFreq};
+    use env_logger;
+
+    fn cached_pin(url: &str, title: &str, tags: &str) -> CachedPin<'static> {
+        CachedPin::new(
+            PinBuilder::new(url.to_string(), title.to_string())
+                .tags(tags.to_string())
+                .into_pin(),
+        )
+    }
+
+    #[test]
+    fn narrows_candidates() {
+        let _ = env_logger::try_init();
+        debug!("narrows_candidates: starting");
+        let mut pins = vec![
+            cached_pin(
+                "https://www.rust-lang.org/",
+                "Rust Programming Language",
+                "rust",
+            ),
+            cached_pin("https://golang.org/", "The Go Programming Language", "go"),
+            cached_pin("https://githuуй.com/Здравствуйт", "Hello", "Cyrillic"),
+        ];
+        for i in 0..5 {
+            pins.push(cached_pin(
+                &format!("https://example.com/{}", i),
+                "Filler",
+                "misc",
+            ));
+        }
+        let tags = vec![
+            CachedTag {
+                tag: Tag("Rust".to_string(), TagFreq::Used(1)),
+                tag_lowered: "rust".to_string(),
+            },
+            CachedTag {
+                tag: Tag("go".to_string(), TagFreq::Used(1)),
+                tag_lowered: "go".to_string(),
+            },
+        ];
+        let index = SearchIndex::new(&pins, &tags, false);
+        let all = Field::from_search_types(&[]);
+        assert!(index.is_built_for(8, 2, fingerprint(&pins, &tags)));
+
+        assert_eq!(Some(vec![0, 1]), index.pins("programming", false, &all));
+        assert_eq!(Some(vec![0]), index.pins("RUST", false, &all));
+        assert_eq!(Some(vec![0]), index.pins("rust", false, &[Field::Tags]));
+        assert_eq!(
+            Some(vec![]),
+            index.pins("rust", false, &[Field::Description])
+        );
+        assert_eq!(Some(vec![2]), index.pins("здрав", false, &[Field::Url]));
+        assert_eq!(Some(vec![]), index.pins("ZZZ", false, &all));
+        assert_eq!(None, index.pins("", false, &all));
+        // Too common to be useful
+        assert_eq!(None, index.pins("l", false, &[Field::Title]));
+
+        // Fuzzy queries only need all chars
+        assert_eq!(Some(vec![0, 1]), index.pins("PGL", true, &[Field::Title]));
+        assert_eq!(Some(vec![0]), index.pins("rst", true, &[Field::Title]));
+        // Short queries are narrowed by chars
+        assert_eq!(
+            Some(vec![0]),
+            index.pins("w", false, &[Field::Url, Field::Tags])
+        );
+        assert_eq!(
+            Some(vec![2]),
+            index.pins("Y", false, &[Field::Url, Field::Tags])
+        );
+
+        assert_eq!(Some(vec![0]), index.tags("RU", false));
+        assert_eq!(None, index.tags("", false));
+        assert_eq!(Some(vec![]), index.tags("gor", false));
+        assert_eq!(Some(vec![1]), index.tags("o", true));
+
+        // Same counts, different text
+        pins[7] = cached_pin("https://example.com/4", "Edited", "misc");
+        assert!(!index.is_built_for(8, 2, fingerprint(&pins, &tags)));
+    }
+
+    #[test]
+    fn candidates_contain_all_matches() {
+        let _ = env_logger::try_init();
+        debug!("candidates_contain_all_matches: starting");
+        let pins = vec![
+            cached_pin("https://a.com/", "C++ (and C) tips", "c++ Lang"),
+            cached_pin("https://b.com/", "[Rust] *news*", "rust"),
+        ];
+        let index = SearchIndex::new(&pins, &[], false);
+        let all = Field::from_search_types(&[]);
+        for q in &["c++", "(and", "*", "[rust]", "lang", "LANG", "s*"] {
+            let ids = match index.pins(q, false, &all) {
+                Some(ids) => ids,
+                None => continue,
+            };
+            for (id, p) in pins.iter().enumerate() {
+                if p.title_lowered.contains(&q.to_lowercase())
+                    || p.pin.tags.to_lowercase().contains(&q.to_lowercase())
+                {
+                    assert!(ids.contains(&(id as u32)), "{} missed {}", q, id);
+                }
+            }
+        }
+    }
+}
@@ -1,23 +1,45 @@
 use std::borrow::Cow;
 use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
 
 use crate::rmps::Serializer;
 // use rmps::{Deserializer, Serializer};
 // use serde::Deserialize;
 
 use chrono::prelude::*;
+use regex::Regex;
 use url::Url;
 
 use failure::Error;
 
-use regex::Regex;
-
 use env_logger;
 
+mod alias;
 mod api;
+mod bulk;
+mod bundle;
 mod cached_data;
+mod canonical;
 mod config;
+mod crypto;
+mod duplicates;
+mod filter;
+mod fuzzy;
+mod hygiene;
+mod index;
+mod normalize;
+mod options;
+mod pattern;
+mod query;
+mod saved;
+mod search;
+mod stats;
+mod suggest;
+mod tree;
+mod validate;
+mod words;
 
 #[cfg(test)]
 mod mockito_helper;
@@ -28,10 +50,32 @@ pub mod pin;
 pub mod tag;
 
 use self::cached_data::*;
+use self::canonical::canonicalize;
 use self::config::Config;
+use self::normalize::normalize;
 
+pub use self::alias::TagAlias;
+pub use self::bulk::{BulkTagEdit, TagChange, TagEdit};
+pub use self::bundle::TagBundle;
+pub use self::cached_data::CacheError;
+pub use self::canonical::UrlStrictness;
+pub use self::crypto::CacheKey;
+pub use self::duplicates::{DuplicateGroup, MergeReport};
+pub use self::filter::SearchFilter;
+pub use self::fuzzy::{FuzzyMatch, FuzzyMatcher};
+pub use self::hygiene::{HygieneReport, Similarity, TagRename};
+pub use self::options::{Pins, SearchOptions, SortKey, SortOrder, Tags};
+pub use self::pattern::{validate_regex, RegexError};
 pub use self::pin::{Pin, PinBuilder};
-pub use self::tag::{Tag, TagFreq};
+pub use self::query::{Query, QueryError};
+pub use self::saved::{SavedSearch, SavedSearchReport};
+pub use self::search::{Field, FieldMatch, SearchResult};
+pub use self::stats::{MonthlyCount, NamedCount, TagStats};
+pub use self::suggest::TagSuggester;
+pub use self::tag::{Tag, TagFreq, TagSuggestions};
+pub use self::tree::TagNode;
+pub use self::validate::{InvalidPin, ValidationError};
+pub use self::words::Language;
 
 #[derive(Debug)]
 pub struct Pinboard<'api, 'pin> {
@@ -42,6 +86,23 @@ pub struct Pinboard<'api, 'pin> {
 
 impl<'api, 'pin> Pinboard<'api, 'pin> {
     pub fn new<S, P>(auth_token: S, cached_dir: Option<P>) -> Result<Self, Error>
+    where
+        S: Into<Cow<'api, str>>,
+        P: AsRef<Path>,
+    {
+        Pinboard::with_cache_key(auth_token, cached_dir, None)
+    }
+
+    /// Same as `new` but the cache files are encrypted with `key`.
+    ///
+    /// Existing cache files that were written without encryption are accepted and
+    /// encrypted on the next write. Returns an error if the existing cache can't be
+    /// decrypted with the given key (or no key is given for an encrypted cache).
+    pub fn with_cache_key<S, P>(
+        auth_token: S,
+        cached_dir: Option<P>,
+        key: Option<CacheKey>,
+    ) -> Result<Self, Error>
     where
         S: Into<Cow<'api, str>>,
         P: AsRef<Path>,
@@ -50,8 +111,8 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         let api = api::Api::new(auth_token);
         let cfg = Config::new();
 
-        debug!("pinb::new: calling CachedData::new");
-        let mut cached_data = CachedData::new(cached_dir)?;
+        debug!("pinb::new: calling CachedData::with_key");
+        let mut cached_data = CachedData::with_key(cached_dir, key)?;
         if !cached_data.cache_ok() {
             debug!("pinb::new: cache file missing, calling update");
             cached_data.update_cache(&api)?;
@@ -74,6 +135,13 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         self.cached_data.load_cache_data_from_file()
     }
 
+    /// Changes the key used to encrypt the cache files and rewrites them accordingly.
+    /// Passing `None` stores the cache unencrypted.
+    pub fn set_cache_key(&mut self, key: Option<CacheKey>) -> Result<(), Error> {
+        debug!("set_cache_key: starting.");
+        self.cached_data.set_key(key)
+    }
+
     pub fn enable_tag_only_search(&mut self, v: bool) {
         debug!("enable_tag_only_search: starting.");
         self.cfg.tag_only_search = v;
@@ -84,6 +152,74 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         self.cfg.fuzzy_search = v;
     }
 
+    /// Makes searches ignore diacritics, so that e.g. `cafe` finds `Café`.
+    /// Searches always ignore case and Unicode normalization form.
+    pub fn enable_diacritic_insensitive_search(&mut self, v: bool) {
+        debug!("enable_diacritic_insensitive_search: starting.");
+        self.cfg.diacritic_insensitive_search = v;
+        self.cached_data.set_strip_diacritics(v);
+    }
+
+    /// Makes `search_items` and `search` match titles and descriptions word by word instead of
+    /// as substrings: `runs` finds `running` but `run` no longer finds `brunch`. Words are
+    /// stemmed and stop words ignored according to the [`set_language`](#method.set_language)
+    /// setting. Fuzzy search takes precedence over word search.
+    pub fn enable_word_search(&mut self, v: bool) {
+        debug!("enable_word_search: starting.");
+        self.cfg.word_search = v;
+        self.update_word_language();
+    }
+
+    /// Makes `search`, `search_items` and `search_list_of_tags` treat queries as case
+    /// insensitive regular expressions, matched against the selected fields as they are
+    /// stored. Invalid or overly complex patterns make searches fail with a [`RegexError`].
+    /// Regex search takes precedence over fuzzy and word search.
+    pub fn enable_regex_search(&mut self, v: bool) {
+        debug!("enable_regex_search: starting.");
+        self.cfg.regex_search = v;
+    }
+
+    /// Sets the language used by word search, English by default.
+    pub fn set_language(&mut self, language: Language) {
+        debug!("set_language: starting.");
+        self.cfg.language = language;
+        self.update_word_language();
+    }
+
+    fn update_word_language(&mut self) {
+        let language = if self.cfg.word_search {
+            Some(self.cfg.language)
+        } else {
+            None
+        };
+        self.cached_data.set_word_language(language);
+    }
+
+    /// Sets how different urls can be and still be found by [`find_url`](#method.find_url).
+    pub fn set_url_strictness(&mut self, strictness: UrlStrictness) {
+        debug!("set_url_strictness: starting.");
+        self.cfg.url_strictness = strictness;
+        self.cached_data.set_url_strictness(strictness);
+    }
+
+    /// Sets the string splitting tags into a hierarchy, `/` by default. It can't be empty.
+    pub fn set_tag_separator<S: Into<String>>(&mut self, separator: S) -> Result<(), Error> {
+        debug!("set_tag_separator: starting.");
+        let separator = separator.into();
+        if separator.is_empty() {
+            bail!("Tag separator can't be empty.");
+        }
+        self.cfg.tag_separator = separator;
+        Ok(())
+    }
+
+    /// Sets the time waited between two requests of a bulk tag edit, 3 seconds by default as
+    /// asked by Pinboard.
+    pub fn set_bulk_edit_interval(&mut self, interval: Duration) {
+        debug!("set_bulk_edit_interval: starting.");
+        self.cfg.bulk_edit_interval = interval;
+    }
+
     pub fn enable_private_new_pin(&mut self, v: bool) {
         debug!("enable_private_new_pin: starting.");
         self.cfg.private_new_pin = v;
@@ -94,9 +230,24 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         self.cfg.toread_new_pin = v;
     }
 
+    /// Leaves private tags, i.e. starting with a dot, out of
+    /// [`list_tag_pairs`](#method.list_tag_pairs) and
+    /// [`search_list_of_tags`](#method.search_list_of_tags).
+    pub fn enable_hide_private_tags(&mut self, v: bool) {
+        debug!("enable_hide_private_tags: starting.");
+        self.cfg.hide_private_tags = v;
+    }
+
+    /// Adds or replaces `p`. An [`InvalidPin`] error is returned without sending anything if
+    /// it doesn't fit Pinboard's limits. A warning is logged if it is public but all its tags
+    /// are private, as others will then see it untagged.
     pub fn add_pin(&self, p: Pin) -> Result<(), Error> {
         debug!("add_pin: starting.");
         let _ = Url::parse(&p.url)?;
+        p.validate()?;
+        if p.shared == "yes" && p.has_only_private_tags() {
+            warn!("public bookmark {} only has private tags: {}", p.url, p.tags);
+        }
         self.api.add_url(p)
     }
 
@@ -136,27 +287,110 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         self.cached_data
             .tags
             .as_ref()
-            .map(|t| t.iter().map(|d| &d.tag).collect())
+            .map(|t| {
+                t.iter()
+                    .map(|d| &d.tag)
+                    .filter(|tag| !(self.cfg.hide_private_tags && tag.is_private()))
+                    .collect()
+            })
     }
 
     /// Returns list of all bookmarks
     pub fn list_bookmarks(&self) -> Option<Vec<&Pin>> {
         debug!("list_bookmarks: starting.");
+        self.list_bookmarks_with(&SearchOptions::default())
+    }
+
+    /// Same as [`list_bookmarks`](#method.list_bookmarks) but only returns the bookmarks
+    /// selected by `options`, in the order they ask for.
+    pub fn list_bookmarks_with(&self, options: &SearchOptions) -> Option<Vec<&Pin>> {
+        debug!("list_bookmarks_with: starting.");
         self.cached_data
             .pins
             .as_ref()
-            .map(|v| v.iter().map(|p| &p.pin).collect())
+            .map(|_| self.list_bookmarks_iter(options).collect())
+    }
+
+    /// Lazy form of [`list_bookmarks_with`](#method.list_bookmarks_with).
+    pub fn list_bookmarks_iter<'a>(&'a self, options: &SearchOptions) -> Pins<'a, 'pin> {
+        debug!("list_bookmarks_iter: starting.");
+        let filter = options.filter.clone();
+        let pins = self
+            .cached_data
+            .pins
+            .iter()
+            .flatten()
+            .filter(move |p| filter.matches(&p.pin));
+        let key = options::pin_key(options, Vec::new(), Vec::new());
+        Box::new(options::page(pins, options, key).map(|p| &p.pin))
     }
 
     /// Suggest a list of tags based on the provided URL
     pub fn popular_tags<T: AsRef<str>>(&self, url: T) -> Result<Vec<String>, Error> {
         debug!("popular_tags: starting.");
         let _ = Url::parse(url.as_ref())?;
-        self.api.suggest_tags(url)
+        Ok(self
+            .api
+            .suggest_tags(url)?
+            .popular
+            .into_iter()
+            .map(|t| t.0)
+            .collect())
+    }
+
+    /// Tags the server suggests for `url`. Recommended tags the user already has carry their
+    /// [`TagFreq::Used`] counts, the rest are marked with [`TagFreq::Popular`].
+    pub fn tag_suggestions<T: AsRef<str>>(&self, url: T) -> Result<TagSuggestions, Error> {
+        debug!("tag_suggestions: starting.");
+        let _ = Url::parse(url.as_ref())?;
+        let mut suggestions = self.api.suggest_tags(url)?;
+        if let Some(tags) = self.cached_data.tags.as_ref() {
+            for tag in &mut suggestions.recommended {
+                if let Some(cached) = tags.iter().find(|c| c.tag == *tag) {
+                    *tag = cached.tag.clone();
+                }
+            }
+        }
+        Ok(suggestions)
+    }
+
+    /// Suggests up to `limit` tags for a bookmark of `url` titled `title`, learned from the
+    /// cached bookmarks when they were loaded so it works offline.
+    ///
+    /// If `use_server` is true the server's suggestions are merged in when it can be reached;
+    /// tags the user never used are then marked with [`TagFreq::New`].
+    pub fn suggest_tags<T: AsRef<str>>(
+        &self,
+        url: T,
+        title: &str,
+        limit: usize,
+        use_server: bool,
+    ) -> Result<Vec<Tag>, Error> {
+        debug!("suggest_tags: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        let url = url.as_ref();
+        let _ = Url::parse(url)?;
+        let server_tags = if use_server {
+            match self.api.suggest_tags(url) {
+                Ok(s) => s.recommended.into_iter().chain(s.popular).map(|t| t.0).collect(),
+                Err(e) => {
+                    info!("suggest_tags: server suggestions unavailable: {}", e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        Ok(self
+            .cached_data
+            .suggester()
+            .suggest_with(url, title, &server_tags, limit))
     }
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SearchType {
     TitleOnly,
     TagOnly,
@@ -167,62 +401,204 @@ pub enum SearchType {
 
 // Search functions
 impl<'api, 'pin> Pinboard<'api, 'pin> {
+    /// Stemmed words of the normalized query `q` if titles and descriptions should be
+    /// searched word by word, i.e. word search is enabled and the search isn't `fuzzy`.
+    /// Queries made of stop words only are still searched as substrings.
+    fn query_words(&self, q: &str, fuzzy: bool) -> Option<Vec<String>> {
+        if fuzzy || !self.cfg.word_search {
+            return None;
+        }
+        self.cached_data
+            .analyzer()
+            .map(|a| a.words(q))
+            .filter(|words| !words.is_empty())
+    }
+
+    /// Fields searched by [`search_items`](#method.search_items).
+    fn item_fields(&self) -> Vec<search::Field> {
+        if self.cfg.tag_only_search {
+            vec![search::Field::Tags]
+        } else {
+            search::Field::from_search_types(&[])
+        }
+    }
+
+    /// Ranking term for `query` according to current settings.
+    fn term(&self, query: &str) -> search::Term {
+        search::Term::new(
+            query,
+            self.cfg.fuzzy_search,
+            self.cfg.diacritic_insensitive_search,
+        )
+    }
+
+    /// Cached pins matching `query` as in [`search_items`](#method.search_items), lazily
+    /// and in cache order.
+    fn matching_items<'a>(
+        &'a self,
+        query: &str,
+    ) -> Box<dyn Iterator<Item = &'a CachedPin<'pin>> + 'a> {
+        let tag_only = self.cfg.tag_only_search;
+        let q = normalize(query, self.cfg.diacritic_insensitive_search);
+        let words = match self.query_words(&q, self.cfg.fuzzy_search) {
+            Some(_) if tag_only => None,
+            words => words,
+        };
+        let candidates = if words.is_some() {
+            self.cached_data.pins.iter().flatten().collect()
+        } else {
+            self.cached_data
+                .candidate_pins(&[query], self.cfg.fuzzy_search, &self.item_fields())
+        };
+        if !self.cfg.fuzzy_search {
+            Box::new(candidates.into_iter().filter(move |item: &&CachedPin| {
+                if tag_only {
+                    item.tag_list.iter().any(|t| t.contains(q.as_str()))
+                } else if let Some(ref words) = words {
+                    item.tag_list.iter().any(|t| t.contains(q.as_str()))
+                        || item.url_lowered.contains(q.as_str())
+                        || item.has_words(words)
+                } else {
+                    item.contains(&q)
+                }
+            }))
+        } else {
+            let matcher = FuzzyMatcher::new(&q);
+            Box::new(candidates.into_iter().filter(move |item| {
+                if tag_only {
+                    item.tag_list.iter().any(|t| matcher.is_match(t))
+                } else {
+                    item.contains_fuzzy(&matcher)
+                }
+            }))
+        }
+    }
+
+    /// Cached pins whose `fields` all match every regular expression of `patterns`, lazily
+    /// and in cache order. All fields are searched if `fields` is empty.
+    fn regex_pins<'a, S>(
+        &'a self,
+        patterns: &[S],
+        fields: &[SearchType],
+    ) -> Result<Box<dyn Iterator<Item = &'a CachedPin<'pin>> + 'a>, Error>
+    where
+        S: AsRef<str>,
+    {
+        let regexes = patterns
+            .iter()
+            .map(|p| pattern::compile(p.as_ref()))
+            .collect::<Result<Vec<Regex>, RegexError>>()?;
+        let fields = if fields.is_empty() {
+            vec![
+                SearchType::TitleOnly,
+                SearchType::TagOnly,
+                SearchType::UrlOnly,
+                SearchType::DescriptionOnly,
+            ]
+        } else {
+            fields.to_vec()
+        };
+        Ok(Box::new(self.cached_data.pins.iter().flatten().filter(
+            move |cached_pin| {
+                regexes.iter().all(|re| {
+                    fields
+                        .iter()
+                        .any(|f| pattern::matches_field(&cached_pin.pin, re, *f))
+                })
+            },
+        )))
+    }
+
     /// Searches all the fields within bookmarks to filter them.
     /// This function honors [pinboard::config::Config] settings for fuzzy search & tag_only search.
     pub fn search_items(&self, query: &str) -> Result<Option<Vec<&Pin>>, Error> {
         debug!("search_items: starting.");
-        if self.cached_data.cache_ok() {
-            let r = if !self.cfg.fuzzy_search {
-                let q = &query.to_lowercase();
-                self.cached_data
-                    .pins
-                    .as_ref()
-                    .map(|p| {
-                        p.iter()
-                            .filter(|item: &&CachedPin| {
-                                if self.cfg.tag_only_search {
-                                    item.pin.tag_contains(q, None)
-                                } else {
-                                    item.pin.contains(q)
-                                }
-                            })
-                            .map(|item| &item.pin)
-                            .collect::<Vec<&Pin>>()
-                    })
-                    .unwrap_or_default()
+        self.search_items_with(query, &SearchOptions::default())
+    }
+
+    /// Same as [`search_items`](#method.search_items) but only returns the results selected
+    /// by `options`, in the order they ask for.
+    pub fn search_items_with(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<Option<Vec<&Pin>>, Error> {
+        debug!("search_items_with: starting.");
+        let r = self
+            .search_items_iter(query, options)?
+            .collect::<Vec<&Pin>>();
+        match r.len() {
+            0 => Ok(None),
+            _ => Ok(Some(r)),
+        }
+    }
+
+    /// Lazy form of [`search_items_with`](#method.search_items_with): without a sort key,
+    /// matches are only looked for as the iterator is advanced.
+    pub fn search_items_iter<'a>(
+        &'a self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<Pins<'a, 'pin>, Error> {
+        debug!("search_items_iter: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Tags cache data is invalid")
+        }
+        let key = options::pin_key(options, vec![self.term(query)], self.item_fields());
+        let items = if self.cfg.regex_search {
+            let fields = if self.cfg.tag_only_search {
+                vec![SearchType::TagOnly]
             } else {
-                // Build a string for regex: "HAMID" => "H.*A.*M.*I.*D"
-                let mut fuzzy_string = query
-                    .chars()
-                    .map(|c| c.to_string())
-                    .collect::<Vec<String>>()
-                    .join(r".*");
-                // Set case-insensitive regex option.
-                fuzzy_string.insert_str(0, "(?i)");
-                let re = Regex::new(&fuzzy_string)?;
-                self.cached_data
-                    .pins
-                    .as_ref()
-                    .map(|p| {
-                        p.iter()
-                            .filter(|item| {
-                                if self.cfg.tag_only_search {
-                                    item.pin.tag_contains("", Some(&re))
-                                } else {
-                                    item.pin.contains_fuzzy(&re)
-                                }
-                            })
-                            .map(|item| &item.pin)
-                            .collect::<Vec<&Pin>>()
-                    })
-                    .unwrap_or_default()
+                Vec::new()
             };
-            match r.len() {
-                0 => Ok(None),
-                _ => Ok(Some(r)),
-            }
+            self.regex_pins(&[query], &fields)?
         } else {
-            bail!("Tags cache data is invalid")
+            self.matching_items(query)
+        };
+        let filter = options.filter.clone();
+        let items = items.filter(move |p| filter.matches(&p.pin));
+        Ok(Box::new(options::page(items, options, key).map(|p| &p.pin)))
+    }
+
+    /// Same as [`search_items`](#method.search_items) but results are sorted by relevance,
+    /// best match first.
+    pub fn search_items_ranked(&self, query: &str) -> Result<Option<Vec<SearchResult>>, Error> {
+        debug!("search_items_ranked: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        let terms = vec![self.term(query)];
+        let fields = self.item_fields();
+        let candidates = self
+            .cached_data
+            .candidate_pins(&[query], self.cfg.fuzzy_search, &fields);
+        let results = search::rank(candidates, &terms, &fields);
+        match results.len() {
+            0 => Ok(None),
+            _ => Ok(Some(results)),
+        }
+    }
+
+    /// Cached tags matching `query` as in
+    /// [`search_list_of_tags`](#method.search_list_of_tags), lazily and in cache order.
+    fn matching_tags<'a>(&'a self, query: &str) -> Box<dyn Iterator<Item = &'a CachedTag> + 'a> {
+        let candidates = self
+            .cached_data
+            .candidate_tags(query, self.cfg.fuzzy_search);
+        let q = normalize(query, self.cfg.diacritic_insensitive_search);
+        if !self.cfg.fuzzy_search {
+            Box::new(
+                candidates
+                    .into_iter()
+                    .filter(move |item| item.tag_lowered.contains(q.as_str())),
+            )
+        } else {
+            let matcher = FuzzyMatcher::new(&q);
+            Box::new(
+                candidates
+                    .into_iter()
+                    .filter(move |item| matcher.is_match(&item.tag_lowered)),
+            )
         }
     }
 
@@ -230,60 +606,67 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
     /// This function honors [pinboard::config::Config] settings for fuzzy search.
     pub fn search_list_of_tags(&self, query: &str) -> Result<Option<Vec<&Tag>>, Error> {
         debug!("search_list_of_tags: starting.");
-        if self.cached_data.cache_ok() {
-            let r = if !self.cfg.fuzzy_search {
-                let q = &query.to_lowercase();
-                self.cached_data
-                    .tags
-                    .as_ref()
-                    .map(|t| {
-                        t.iter()
-                            .filter(|item| item.tag_lowered.contains(q))
-                            .map(|ct| &ct.tag)
-                            .collect::<Vec<&Tag>>()
-                    })
-                    .unwrap_or_default()
-            } else {
-                // Build a string for regex: "HAMID" => "H.*A.*M.*I.*D"
-                let mut fuzzy_string = query
-                    .chars()
-                    .map(|c| format!("{}", c))
-                    .collect::<Vec<String>>()
-                    .join(r".*");
-                // Set case-insensitive regex option.
-                fuzzy_string.insert_str(0, "(?i)");
-                let re = Regex::new(&fuzzy_string)?;
+        self.search_list_of_tags_with(query, &SearchOptions::default())
+    }
+
+    /// Same as [`search_list_of_tags`](#method.search_list_of_tags) but only returns the
+    /// tags selected by `options`, in the order they ask for.
+    pub fn search_list_of_tags_with(
+        &self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<Option<Vec<&Tag>>, Error> {
+        debug!("search_list_of_tags_with: starting.");
+        let r = self
+            .search_list_of_tags_iter(query, options)?
+            .collect::<Vec<&Tag>>();
+        match r.len() {
+            0 => Ok(None),
+            _ => Ok(Some(r)),
+        }
+    }
+
+    /// Lazy form of [`search_list_of_tags_with`](#method.search_list_of_tags_with).
+    pub fn search_list_of_tags_iter<'a>(
+        &'a self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<Tags<'a>, Error> {
+        debug!("search_list_of_tags_iter: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Tags cache data is invalid")
+        }
+        let key = options::tag_key(options, self.term(query));
+        let tags: Box<dyn Iterator<Item = &CachedTag>> = if self.cfg.regex_search {
+            let re = pattern::compile(query)?;
+            Box::new(
                 self.cached_data
                     .tags
-                    .as_ref()
-                    .map(|t| {
-                        t.iter()
-                            .filter(|item| re.is_match(&item.tag.0))
-                            .map(|ct| &ct.tag)
-                            .collect::<Vec<&Tag>>()
-                    })
-                    .unwrap_or_default()
-            };
-            match r.len() {
-                0 => Ok(None),
-                _ => Ok(Some(r)),
-            }
+                    .iter()
+                    .flatten()
+                    .filter(move |ct| re.is_match(&ct.tag.0)),
+            )
         } else {
-            bail!("Tags cache data is invalid")
-        }
+            self.matching_tags(query)
+        };
+        let hide_private = self.cfg.hide_private_tags;
+        let tags = tags.filter(move |ct| !(hide_private && ct.tag.is_private()));
+        Ok(Box::new(
+            options::page(tags, options, key).map(|ct| &ct.tag),
+        ))
     }
 
-    // TODO: find_url should return pins that match `q` barring their fragment //
-    // https://github.com/sharkdp/hexyl#preview  and
-    // https://github.com/sharkdp/hexyl
-    // should be considered identical (?!)
-
-    /// Finds all pins whose url is an exact match of the `q`
+    /// Finds all pins whose url is the same as `q`, as decided by the
+    /// [`set_url_strictness`](#method.set_url_strictness) setting. Urls are always compared
+    /// case insensitively.
     ///
-    /// find_url("http://google.com/public") will match following
+    /// With the default [`UrlStrictness::Normal`], find_url("http://google.com/public") will
+    /// match following
     /// http://google.com/public
+    /// http://google.com/public/#fragment
+    /// http://google.com/public?utm_source=feed
     /// but not following
-    /// http://google.com/public#fragment
+    /// https://google.com/public
     pub fn find_url<S>(&'pin self, q: S) -> Result<Option<Vec<&'pin Pin<'pin>>>, Error>
     where
         S: AsRef<str>,
@@ -292,16 +675,14 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         if !self.cached_data.cache_ok() {
             bail!("Cache data is invalid.");
         }
-        let query = &q.as_ref().to_lowercase();
+        let query = &canonicalize(q.as_ref(), self.cached_data.url_strictness());
         let results = self
             .cached_data
             .pins
             .as_ref()
             .map(|p: &Vec<CachedPin<'pin>>| {
                 p.iter()
-                    .filter(|cached_pin: &&CachedPin<'pin>| {
-                        cached_pin.pin.url.to_lowercase().as_str() == query
-                    })
+                    .filter(|cached_pin: &&CachedPin<'pin>| cached_pin.url_normalized == *query)
                     .map(|p| &p.pin)
                     .collect::<Vec<&'pin Pin>>()
             })
@@ -312,21 +693,81 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         }
     }
 
-    /// Searches the selected `fields` within bookmarks to filter them.
-    /// This function honors [pinboard::config::Config] settings for fuzzy search only.
-    pub fn search<'b, I, S>(
-        &'pin self,
-        q: &'b I,
-        fields: &[SearchType],
-    ) -> Result<Option<Vec<&'pin Pin<'pin>>>, Error>
+    /// Finds all pins matching a query written in the [`Query`] language, e.g.
+    ///
+    /// `tag:rust -tag:old title:async OR title:tokio site:github.com toread:yes "some phrase"`
+    ///
+    /// Returns a [`QueryError`] pointing at the offending token if `q` can't be parsed.
+    pub fn search_query<S>(&'pin self, q: S) -> Result<Option<Vec<&'pin Pin<'pin>>>, Error>
     where
-        &'b I: IntoIterator<Item = S>,
         S: AsRef<str>,
     {
-        debug!("search: starting.");
+        debug!("search_query: starting.");
+        let query = Query::parse(q.as_ref())?;
+        self.search_by_query(&query, &SearchOptions::default())
+    }
+
+    /// Finds all pins matching `query`, which can be built programmatically to combine
+    /// required, excluded and alternative terms, each restricted to some field or not. Only
+    /// the results selected by `options` are returned, in the order they ask for.
+    pub fn search_by_query(
+        &'pin self,
+        query: &Query,
+        options: &SearchOptions,
+    ) -> Result<Option<Vec<&'pin Pin<'pin>>>, Error> {
+        debug!("search_by_query: starting.");
+        let results = self
+            .search_by_query_iter(query, options)?
+            .collect::<Vec<&Pin>>();
+        match results.len() {
+            0 => Ok(None),
+            _ => Ok(Some(results)),
+        }
+    }
+
+    /// Lazy form of [`search_by_query`](#method.search_by_query).
+    pub fn search_by_query_iter<'a>(
+        &'a self,
+        query: &Query,
+        options: &SearchOptions,
+    ) -> Result<Pins<'a, 'pin>, Error> {
+        debug!("search_by_query_iter: starting.");
         if !self.cached_data.cache_ok() {
             bail!("Cache data is invalid.");
         }
+        let query = query
+            .clone()
+            .normalize(self.cfg.diacritic_insensitive_search);
+        let terms = query
+            .texts()
+            .into_iter()
+            .map(|s| search::Term::new(s, false, self.cfg.diacritic_insensitive_search))
+            .collect::<Vec<search::Term>>();
+        let key = options::pin_key(options, terms, search::Field::from_search_types(&[]));
+        let filter = options.filter.clone();
+        let pins = self
+            .cached_data
+            .pins
+            .iter()
+            .flatten()
+            .filter(move |p| filter.matches(&p.pin) && query.matches(p));
+        Ok(Box::new(options::page(pins, options, key).map(|p| &p.pin)))
+    }
+
+    /// Cached pins matching all `queries` within `fields` as in
+    /// [`search`](#method.search), lazily and in cache order.
+    ///
+    /// Outside of fuzzy search, tags equal to one of the `synonyms` of a query match it too.
+    fn matching_pins<'a, S>(
+        &'a self,
+        queries: &[S],
+        fields: &[SearchType],
+        fuzzy: bool,
+        synonyms: Vec<Vec<String>>,
+    ) -> Box<dyn Iterator<Item = &'a CachedPin<'pin>> + 'a>
+    where
+        S: AsRef<str>,
+    {
         // When no field is specified, search everywhere
         let all_fields = vec![
             SearchType::TitleOnly,
@@ -335,106 +776,824 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
             SearchType::DescriptionOnly,
         ];
         let search_fields = if fields.is_empty() {
-            all_fields.as_slice()
+            all_fields
         } else {
-            fields
+            fields.to_vec()
         };
 
-        let results = if !self.cfg.fuzzy_search {
-            self.cached_data
-                .pins
-                .as_ref()
-                .map(|p: &Vec<CachedPin<'pin>>| {
-                    p.iter()
-                        .filter(|cached_pin: &&CachedPin<'pin>| {
-                            q.into_iter().all(|s| {
-                                let query = &s.as_ref().to_lowercase();
-                                search_fields.iter().any(|search_type| match *search_type {
-                                    SearchType::TitleOnly => {
-                                        cached_pin.title_lowered.contains(query)
-                                    }
-                                    SearchType::TagOnly => {
-                                        cached_pin.tag_list.iter().any(|tag| tag.contains(query))
-                                    }
-                                    SearchType::UrlOnly => {
-                                        cached_pin.pin.url.as_ref().contains(query)
-                                    }
-                                    SearchType::DescriptionOnly => {
-                                        if let Some(ref extended) = cached_pin.extended_lowered {
-                                            extended.contains(query)
-                                        } else {
-                                            false
-                                        }
-                                    }
-                                    SearchType::TagTitleOnly => {
-                                        cached_pin.title_lowered.contains(query)
-                                            || cached_pin.tag_list.contains(query)
-                                    }
-                                })
+        let lowered = queries
+            .iter()
+            .map(|s| normalize(s.as_ref(), self.cfg.diacritic_insensitive_search))
+            .collect::<Vec<String>>();
+        // Words of each query, if titles and descriptions are searched word by word.
+        let words = lowered
+            .iter()
+            .map(|q| self.query_words(q, fuzzy))
+            .collect::<Vec<Option<Vec<String>>>>();
+        let word_fields = search_fields.iter().any(|f| match *f {
+            SearchType::TitleOnly | SearchType::DescriptionOnly | SearchType::TagTitleOnly => true,
+            SearchType::TagOnly | SearchType::UrlOnly => false,
+        });
+        // The index only knows about substrings, so it can't narrow down word searches, nor
+        // find the synonyms of a query.
+        let candidates = if (word_fields && words.iter().any(Option::is_some))
+            || synonyms.iter().any(|s| !s.is_empty())
+        {
+            self.cached_data.pins.iter().flatten().collect()
+        } else {
+            self.cached_data.candidate_pins(
+                queries,
+                fuzzy,
+                &search::Field::from_search_types(fields),
+            )
+        };
+        if !fuzzy {
+            Box::new(
+                candidates
+                    .into_iter()
+                    .filter(move |cached_pin: &&CachedPin<'pin>| {
+                        lowered.iter().enumerate().all(|(i, query)| {
+                            let query_words = words[i].as_ref();
+                            let in_title = || match query_words {
+                                Some(w) => words::contains_all(&cached_pin.title_words, w),
+                                None => cached_pin.title_lowered.contains(query),
+                            };
+                            search_fields.iter().any(|search_type| match *search_type {
+                                SearchType::TitleOnly => in_title(),
+                                SearchType::TagOnly => cached_pin.tag_list.iter().any(|tag| {
+                                    tag.contains(query)
+                                        || synonyms[i].contains(tag)
+                                }),
+                                SearchType::UrlOnly => cached_pin.url_lowered.contains(query),
+                                SearchType::DescriptionOnly => match query_words {
+                                    Some(w) => words::contains_all(&cached_pin.extended_words, w),
+                                    None => cached_pin
+                                        .extended_lowered
+                                        .as_ref()
+                                        .map_or(false, |e| e.contains(query)),
+                                },
+                                SearchType::TagTitleOnly => {
+                                    in_title() || cached_pin.tag_list.contains(query)
+                                }
                             })
                         })
-                        .map(|p| &p.pin)
-                        .collect::<Vec<&'pin Pin>>()
-                })
-                .unwrap_or_default()
+                    }),
+            )
         } else {
-            let regex_queries = q
-                .into_iter()
-                .map(|s| {
-                    let query = &s.as_ref().to_lowercase();
-                    // Build a string for regex: "HAMID" => "H.*A.*M.*I.*D"
-                    let mut fuzzy_string = String::with_capacity(query.len() * query.len() * 2);
-                    fuzzy_string.push_str(
-                        &query
-                            .chars()
-                            .map(|c| c.to_string())
-                            .collect::<Vec<String>>()
-                            .join(r".*"),
-                    );
-                    // Set case-insensitive regex option.
-                    let mut fuzzy_regex: String = String::with_capacity(fuzzy_string.len() + 2);
-                    fuzzy_regex.extend("(?i)".chars().chain(fuzzy_string.chars()));
-                    Regex::new(&fuzzy_string)
-                        .map_err(|e| format!("{:?}", e))
-                        .expect("Couldn't build regex using given search query")
-                })
-                .collect::<Vec<Regex>>();
-            self.cached_data
-                .pins
-                .as_ref()
-                .map(|p| {
-                    p.iter()
-                        .filter(|cached_pin: &&CachedPin| {
-                            regex_queries.iter().all(|re| {
-                                search_fields.iter().any(|search_type| match *search_type {
-                                    SearchType::TitleOnly => re.is_match(&cached_pin.title_lowered),
-                                    SearchType::TagOnly => {
-                                        cached_pin.tag_list.iter().any(|t| re.is_match(t))
-                                    }
-                                    SearchType::UrlOnly => re.is_match(cached_pin.pin.url.as_ref()),
-                                    SearchType::DescriptionOnly => {
-                                        if let Some(ref extended) = cached_pin.extended_lowered {
-                                            re.is_match(extended)
-                                        } else {
-                                            false
-                                        }
-                                    }
-                                    SearchType::TagTitleOnly => {
-                                        re.is_match(&cached_pin.title_lowered)
-                                            || cached_pin.tag_list.iter().any(|t| re.is_match(t))
+            let matchers = lowered
+                .iter()
+                .map(|s| FuzzyMatcher::new(s))
+                .collect::<Vec<FuzzyMatcher>>();
+            Box::new(
+                candidates
+                    .into_iter()
+                    .filter(move |cached_pin: &&CachedPin| {
+                        matchers.iter().all(|m| {
+                            search_fields.iter().any(|search_type| match *search_type {
+                                SearchType::TitleOnly => m.is_match(&cached_pin.title_lowered),
+                                SearchType::TagOnly => {
+                                    cached_pin.tag_list.iter().any(|t| m.is_match(t))
+                                }
+                                SearchType::UrlOnly => m.is_match(&cached_pin.url_lowered),
+                                SearchType::DescriptionOnly => {
+                                    if let Some(ref extended) = cached_pin.extended_lowered {
+                                        m.is_match(extended)
+                                    } else {
+                                        false
                                     }
-                                })
+                                }
+                                SearchType::TagTitleOnly => {
+                                    m.is_match(&cached_pin.title_lowered)
+                                        || cached_pin.tag_list.iter().any(|t| m.is_match(t))
+                                }
                             })
                         })
-                        .map(|p| &p.pin)
-                        .collect::<Vec<&Pin>>()
-                })
-                .unwrap_or_default()
+                    }),
+            )
+        }
+    }
+
+    /// Searches the selected `fields` within bookmarks to filter them.
+    /// This function honors [pinboard::config::Config] settings for fuzzy search only.
+    ///
+    /// Unless searching fuzzily, a query searched in tags also finds its aliases, see
+    /// [`add_tag_alias`](#method.add_tag_alias).
+    pub fn search<'b, I, S>(
+        &'pin self,
+        q: &'b I,
+        fields: &[SearchType],
+    ) -> Result<Option<Vec<&'pin Pin<'pin>>>, Error>
+    where
+        &'b I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        debug!("search: starting.");
+        self.search_with(q, fields, &SearchOptions::default())
+    }
+
+    /// Same as [`search`](#method.search) but only returns the results selected by
+    /// `options`, in the order they ask for. Its [`SearchFilter`] narrows results down by
+    /// date, unread and private status, description or number of tags.
+    pub fn search_with<'b, I, S>(
+        &'pin self,
+        q: &'b I,
+        fields: &[SearchType],
+        options: &SearchOptions,
+    ) -> Result<Option<Vec<&'pin Pin<'pin>>>, Error>
+    where
+        &'b I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        debug!("search_with: starting.");
+        let results = self.search_iter(q, fields, options)?.collect::<Vec<&Pin>>();
+        match results.len() {
+            0 => Ok(None),
+            _ => Ok(Some(results)),
+        }
+    }
+
+    /// Lazy form of [`search_with`](#method.search_with): without a sort key, matches are
+    /// only looked for as the iterator is advanced.
+    pub fn search_iter<'b, I, S>(
+        &'pin self,
+        q: &'b I,
+        fields: &[SearchType],
+        options: &SearchOptions,
+    ) -> Result<Pins<'pin, 'pin>, Error>
+    where
+        &'b I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        debug!("search_iter: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        let queries = q.into_iter().collect::<Vec<S>>();
+        self.paged_pins(
+            &queries,
+            fields,
+            self.cfg.fuzzy_search,
+            self.cfg.regex_search,
+            options,
+        )
+    }
+
+    /// Pins matching `queries` within `fields` as selected by `options`, searching fuzzily
+    /// or with regular expressions regardless of the current settings.
+    fn paged_pins<'a, S>(
+        &'a self,
+        queries: &[S],
+        fields: &[SearchType],
+        fuzzy: bool,
+        regex: bool,
+        options: &SearchOptions,
+    ) -> Result<Pins<'a, 'pin>, Error>
+    where
+        S: AsRef<str>,
+    {
+        let terms = queries
+            .iter()
+            .map(|s| search::Term::new(s.as_ref(), fuzzy, self.cfg.diacritic_insensitive_search))
+            .collect::<Vec<search::Term>>();
+        let key = options::pin_key(options, terms, search::Field::from_search_types(fields));
+        let pins = if regex {
+            self.regex_pins(queries, fields)?
+        } else {
+            let synonyms = self.query_synonyms(queries, fields, fuzzy);
+            self.matching_pins(queries, fields, fuzzy, synonyms)
         };
+        let filter = options.filter.clone();
+        let pins = pins.filter(move |p| filter.matches(&p.pin));
+        Ok(Box::new(options::page(pins, options, key).map(|p| &p.pin)))
+    }
+
+    /// Synonyms of each query according to the tag aliases, if they are used in a tag search.
+    fn query_synonyms<S>(
+        &self,
+        queries: &[S],
+        fields: &[SearchType],
+        fuzzy: bool,
+    ) -> Vec<Vec<String>>
+    where
+        S: AsRef<str>,
+    {
+        let in_tags = fields.is_empty() || fields.contains(&SearchType::TagOnly);
+        let aliases = if in_tags && !fuzzy {
+            self.cached_data.tag_aliases()
+        } else {
+            &[]
+        };
+        let strip = self.cfg.diacritic_insensitive_search;
+        queries
+            .iter()
+            .map(|q| alias::synonyms(aliases, &normalize(q.as_ref(), strip), strip))
+            .collect()
+    }
+
+    /// Same as [`search`](#method.search) but results are sorted by relevance, best match
+    /// first, along with their score.
+    ///
+    /// Hits in title weigh more than in tags, which weigh more than in url and then
+    /// description. Whole-field and start-of-word matches weigh more than matches inside
+    /// words, which weigh more than fuzzy ones. Earlier and newer is better.
+    pub fn search_ranked<'b, I, S>(
+        &'pin self,
+        q: &'b I,
+        fields: &[SearchType],
+    ) -> Result<Option<Vec<SearchResult<'pin, 'pin>>>, Error>
+    where
+        &'b I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        debug!("search_ranked: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        let queries = q.into_iter().collect::<Vec<S>>();
+        let terms = queries
+            .iter()
+            .map(|s| self.term(s.as_ref()))
+            .collect::<Vec<search::Term>>();
+        let fields = search::Field::from_search_types(fields);
+        let candidates = self
+            .cached_data
+            .candidate_pins(&queries, self.cfg.fuzzy_search, &fields);
+        let results = search::rank(candidates, &terms, &fields);
+        match results.len() {
+            0 => Ok(None),
+            _ => Ok(Some(results)),
+        }
+    }
+}
 
+// Saved search functions
+impl<'api, 'pin> Pinboard<'api, 'pin> {
+    /// Saves `search` in the cache folder, replacing any saved search with the same name.
+    pub fn save_search(&self, search: SavedSearch) -> Result<(), Error> {
+        debug!("save_search: starting.");
+        if search.name.trim().is_empty() {
+            bail!("Saved search name can't be empty.");
+        }
+        self.cached_data.save_named(SEARCHES_CACHE_FN, vec![search])
+    }
+
+    /// Returns all saved searches, in the order they were first saved.
+    pub fn saved_searches(&self) -> Result<Vec<SavedSearch>, Error> {
+        debug!("saved_searches: starting.");
+        self.cached_data.read_list(SEARCHES_CACHE_FN)
+    }
+
+    /// Deletes the saved search called `name`. Returns false if there was none.
+    pub fn delete_saved_search(&self, name: &str) -> Result<bool, Error> {
+        debug!("delete_saved_search: starting.");
+        self.cached_data.delete_named::<SavedSearch>(SEARCHES_CACHE_FN, name)
+    }
+
+    /// Runs the saved search called `name` and remembers the bookmarks found, see
+    /// [`saved_search_news`](#method.saved_search_news).
+    pub fn run_saved_search(&'pin self, name: &str) -> Result<Option<Vec<&'pin Pin<'pin>>>, Error> {
+        debug!("run_saved_search: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        let mut searches = self.cached_data.read_list::<SavedSearch>(SEARCHES_CACHE_FN)?;
+        let results = match searches.iter_mut().find(|s| s.name == name) {
+            Some(search) => {
+                let results = self.run_search(search)?.collect::<Vec<&Pin>>();
+                search.mark_seen(&results);
+                results
+            }
+            None => bail!("No saved search named {}.", name),
+        };
+        self.cached_data.write_store(SEARCHES_CACHE_FN, &searches)?;
         match results.len() {
             0 => Ok(None),
             _ => Ok(Some(results)),
         }
     }
+
+    /// Runs every saved search and reports the bookmarks it finds that it didn't find when it
+    /// last ran (all of them for a search that never ran), whatever their creation time.
+    /// Searches without new bookmarks are left out.
+    pub fn saved_search_news(&'pin self) -> Result<Vec<SavedSearchReport<'pin, 'pin>>, Error> {
+        debug!("saved_search_news: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        let mut searches = self.cached_data.read_list::<SavedSearch>(SEARCHES_CACHE_FN)?;
+        let mut reports = Vec::new();
+        for search in &mut searches {
+            let results = self.run_search(search)?.collect::<Vec<&Pin>>();
+            let new_pins = results
+                .iter()
+                .filter(|p| search.is_new(p))
+                .cloned()
+                .collect::<Vec<&Pin>>();
+            if !new_pins.is_empty() {
+                reports.push(SavedSearchReport {
+                    name: search.name.clone(),
+                    new_pins,
+                });
+            }
+            search.mark_seen(&results);
+        }
+        self.cached_data.write_store(SEARCHES_CACHE_FN, &searches)?;
+        Ok(reports)
+    }
+
+    fn run_search<'a>(&'a self, search: &SavedSearch) -> Result<Pins<'a, 'pin>, Error> {
+        let options = SearchOptions::new().filter(search.filter.clone());
+        self.paged_pins(
+            &search.queries,
+            &search.fields,
+            search.fuzzy,
+            false,
+            &options,
+        )
+    }
+}
+
+// Tag alias functions
+impl<'api, 'pin> Pinboard<'api, 'pin> {
+    /// Makes `alias` an alias of `canonical`, e.g. `js` of `javascript`, so that searching
+    /// tags for one finds the other. An alias belongs to a single canonical tag. Returns false
+    /// if it already was an alias of `canonical`.
+    pub fn add_tag_alias(&mut self, alias: &str, canonical: &str) -> Result<bool, Error> {
+        debug!("add_tag_alias: starting.");
+        if alias.trim().is_empty() || canonical.trim().is_empty() {
+            bail!("Tag aliases can't be empty.");
+        }
+        let mut aliases = self.cached_data.tag_aliases().to_vec();
+        if !alias::add(&mut aliases, alias, canonical) {
+            return Ok(false);
+        }
+        self.cached_data.set_tag_aliases(aliases)?;
+        Ok(true)
+    }
+
+    /// Removes `alias` from the tag aliases. Returns false if it wasn't an alias.
+    pub fn remove_tag_alias(&mut self, alias: &str) -> Result<bool, Error> {
+        debug!("remove_tag_alias: starting.");
+        let mut aliases = self.cached_data.tag_aliases().to_vec();
+        if !alias::remove(&mut aliases, alias) {
+            return Ok(false);
+        }
+        self.cached_data.set_tag_aliases(aliases)?;
+        Ok(true)
+    }
+
+    /// Returns all tag aliases, grouped by canonical tag.
+    pub fn tag_aliases(&self) -> Result<Vec<TagAlias>, Error> {
+        debug!("tag_aliases: starting.");
+        Ok(self.cached_data.tag_aliases().to_vec())
+    }
+
+    /// Same as [`list_tag_pairs`](#method.list_tag_pairs) but aliases are replaced by their
+    /// canonical tag, whose frequency is the sum of theirs.
+    pub fn list_merged_tag_pairs(&self) -> Result<Option<Vec<Tag>>, Error> {
+        debug!("list_merged_tag_pairs: starting.");
+        let aliases = self.cached_data.tag_aliases();
+        Ok(self
+            .list_tag_pairs()
+            .map(|tags| alias::merge(aliases, &tags)))
+    }
+
+    /// Renames every cached tag of an alias group that isn't spelled as its canonical tag,
+    /// e.g. `js` and `JavaScript` to `javascript`. Returns the renamed tags with their new
+    /// names. Nothing is changed if `dry_run` is true.
+    ///
+    /// Local cache isn't updated, call [`update_cache`](#method.update_cache) afterwards.
+    pub fn apply_tag_aliases(&self, dry_run: bool) -> Result<Vec<(String, String)>, Error> {
+        debug!("apply_tag_aliases: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Tags cache data is invalid")
+        }
+        let aliases = self.cached_data.tag_aliases();
+        let renames = self
+            .cached_tags()
+            .map(|t| (t.0.clone(), alias::canonical(aliases, &t.0).to_string()))
+            .filter(|(tag, canonical)| tag != canonical)
+            .collect::<Vec<(String, String)>>();
+        if !dry_run {
+            for (from, to) in &renames {
+                self.api.tag_rename(from, to)?;
+            }
+        }
+        Ok(renames)
+    }
+}
+
+// Tag bundle functions
+impl<'api, 'pin> Pinboard<'api, 'pin> {
+    /// Saves `bundle` in the cache folder, replacing any bundle with the same name.
+    pub fn save_tag_bundle(&self, bundle: TagBundle) -> Result<(), Error> {
+        debug!("save_tag_bundle: starting.");
+        if bundle.name.trim().is_empty() {
+            bail!("Tag bundle name can't be empty.");
+        }
+        self.cached_data.save_named(BUNDLES_CACHE_FN, vec![bundle])
+    }
+
+    /// Returns all tag bundles, in the order they were first saved.
+    pub fn tag_bundles(&self) -> Result<Vec<TagBundle>, Error> {
+        debug!("tag_bundles: starting.");
+        self.cached_data.read_list(BUNDLES_CACHE_FN)
+    }
+
+    /// Returns the tag bundle called `name`, if any.
+    pub fn tag_bundle(&self, name: &str) -> Result<Option<TagBundle>, Error> {
+        debug!("tag_bundle: starting.");
+        Ok(self
+            .cached_data
+            .read_list::<TagBundle>(BUNDLES_CACHE_FN)?
+            .into_iter()
+            .find(|b| b.name == name))
+    }
+
+    /// Deletes the tag bundle called `name`. Returns false if there was none.
+    pub fn delete_tag_bundle(&self, name: &str) -> Result<bool, Error> {
+        debug!("delete_tag_bundle: starting.");
+        self.cached_data.delete_named::<TagBundle>(BUNDLES_CACHE_FN, name)
+    }
+
+    /// Adds `tags` to the tag bundle called `name`. Returns false if it already had them all.
+    pub fn add_tags_to_bundle(&self, name: &str, tags: &[String]) -> Result<bool, Error> {
+        debug!("add_tags_to_bundle: starting.");
+        self.edit_tag_bundle(name, |b| b.add_tags(tags))
+    }
+
+    /// Removes `tags` from the tag bundle called `name`. Returns false if it had none of them.
+    pub fn remove_tags_from_bundle(&self, name: &str, tags: &[String]) -> Result<bool, Error> {
+        debug!("remove_tags_from_bundle: starting.");
+        self.edit_tag_bundle(name, |b| b.remove_tags(tags))
+    }
+
+    fn edit_tag_bundle<F>(&self, name: &str, edit: F) -> Result<bool, Error>
+    where
+        F: FnOnce(&mut TagBundle) -> bool,
+    {
+        let mut bundles = self.cached_data.read_list::<TagBundle>(BUNDLES_CACHE_FN)?;
+        let changed = match bundles.iter_mut().find(|b| b.name == name) {
+            Some(bundle) => edit(bundle),
+            None => bail!("No tag bundle named {}.", name),
+        };
+        if changed {
+            self.cached_data.write_store(BUNDLES_CACHE_FN, &bundles)?;
+        }
+        Ok(changed)
+    }
+
+    /// Searches the cached bookmarks having any tag of the tag bundle called `name`.
+    pub fn search_tag_bundle(
+        &'pin self,
+        name: &str,
+    ) -> Result<Option<Vec<&'pin Pin<'pin>>>, Error> {
+        debug!("search_tag_bundle: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        let bundle = match self.tag_bundle(name)? {
+            Some(bundle) => bundle,
+            None => bail!("No tag bundle named {}.", name),
+        };
+        let keys = bundle
+            .tags
+            .iter()
+            .map(|t| normalize(t, self.cfg.diacritic_insensitive_search))
+            .collect::<Vec<String>>();
+        let r = self
+            .cached_data
+            .pins
+            .iter()
+            .flatten()
+            .filter(|p| p.tag_list.iter().any(|t| keys.contains(t)))
+            .map(|p| &p.pin)
+            .collect::<Vec<&Pin>>();
+        match r.len() {
+            0 => Ok(None),
+            _ => Ok(Some(r)),
+        }
+    }
+
+    /// All tag bundles as JSON, to be shared and read back by
+    /// [`import_tag_bundles`](#method.import_tag_bundles).
+    pub fn export_tag_bundles(&self) -> Result<String, Error> {
+        debug!("export_tag_bundles: starting.");
+        bundle::to_json(&self.tag_bundles()?)
+    }
+
+    /// Saves the tag bundles of `json`, as written by
+    /// [`export_tag_bundles`](#method.export_tag_bundles), replacing the bundles with the same
+    /// names. Nothing is saved if any of them is invalid. Returns the number of bundles read.
+    pub fn import_tag_bundles(&self, json: &str) -> Result<usize, Error> {
+        debug!("import_tag_bundles: starting.");
+        let imported = bundle::from_json(json)?;
+        let count = imported.len();
+        self.cached_data.save_named(BUNDLES_CACHE_FN, imported)?;
+        Ok(count)
+    }
+}
+
+// Bulk tag edit functions
+impl<'api, 'pin> Pinboard<'api, 'pin> {
+    /// Changes `edits` would make to the tags of the bookmarks matching `query`, applied in
+    /// order. Bookmarks whose tags wouldn't change are left out.
+    pub fn tag_changes(&self, query: &Query, edits: &[TagEdit]) -> Result<Vec<TagChange>, Error> {
+        debug!("tag_changes: starting.");
+        Ok(self
+            .search_by_query_iter(query, &SearchOptions::default())?
+            .filter_map(|p| bulk::edited(&p.tags, edits).map(|tags| TagChange::new(p, tags)))
+            .collect())
+    }
+
+    /// Applies `edits` to the tags of every bookmark matching `query`, e.g. adds `archive` to
+    /// the bookmarks matching `tag:todo before:2018-01-01`. Changed bookmarks are posted again
+    /// one by one, waiting for the [`set_bulk_edit_interval`](#method.set_bulk_edit_interval)
+    /// setting between requests.
+    ///
+    /// If a request fails, the error is returned and the edit can be finished later with
+    /// [`resume_tag_edit`](#method.resume_tag_edit); no other edit can be started meanwhile.
+    /// An [`InvalidPin`] error is returned before any request if a changed bookmark wouldn't
+    /// fit Pinboard's limits. Nothing is changed if `dry_run` is true, the returned edit lists
+    /// what would be.
+    ///
+    /// Local cache isn't updated, call [`update_cache`](#method.update_cache) afterwards.
+    pub fn edit_tags(
+        &self,
+        query: &Query,
+        edits: &[TagEdit],
+        dry_run: bool,
+    ) -> Result<BulkTagEdit, Error> {
+        debug!("edit_tags: starting.");
+        let edit = BulkTagEdit::new(self.tag_changes(query, edits)?);
+        Pinboard::validate_tag_edit(&edit)?;
+        if dry_run {
+            return Ok(edit);
+        }
+        if self.unfinished_tag_edit()?.is_some() {
+            bail!("A bulk tag edit is unfinished, resume or discard it first.");
+        }
+        self.run_tag_edit(edit)
+    }
+
+    /// Finishes the bulk tag edit interrupted by an error, if any.
+    pub fn resume_tag_edit(&self) -> Result<Option<BulkTagEdit>, Error> {
+        debug!("resume_tag_edit: starting.");
+        match self.unfinished_tag_edit()? {
+            Some(edit) => {
+                Pinboard::validate_tag_edit(&edit)?;
+                self.run_tag_edit(edit).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The bulk tag edit interrupted by an error, if any.
+    pub fn unfinished_tag_edit(&self) -> Result<Option<BulkTagEdit>, Error> {
+        debug!("unfinished_tag_edit: starting.");
+        self.cached_data.read_store(BULK_CACHE_FN)
+    }
+
+    /// Gives up on the bulk tag edit interrupted by an error. Returns false if there was none.
+    pub fn discard_tag_edit(&self) -> Result<bool, Error> {
+        debug!("discard_tag_edit: starting.");
+        self.cached_data.remove_store(BULK_CACHE_FN)
+    }
+
+    fn validate_tag_edit(edit: &BulkTagEdit) -> Result<(), Error> {
+        for change in edit.pending() {
+            change.pin.validate()?;
+        }
+        Ok(())
+    }
+
+    fn run_tag_edit(&self, mut edit: BulkTagEdit) -> Result<BulkTagEdit, Error> {
+        self.cached_data.write_store(BULK_CACHE_FN, &edit)?;
+        let start = edit.done;
+        while !edit.is_done() {
+            if edit.done > start {
+                thread::sleep(self.cfg.bulk_edit_interval);
+            }
+            self.api.add_url(edit.pending()[0].pin.clone())?;
+            edit.done += 1;
+            self.cached_data.write_store(BULK_CACHE_FN, &edit)?;
+        }
+        self.cached_data.remove_store(BULK_CACHE_FN)?;
+        Ok(edit)
+    }
+}
+
+// Tag hygiene functions
+impl<'api, 'pin> Pinboard<'api, 'pin> {
+    /// Checks cached tags for near-duplicates (tags differing only by case, plural,
+    /// punctuation or a typo), tags used once and tags never used along with another one.
+    pub fn tag_hygiene(&self) -> Result<HygieneReport, Error> {
+        debug!("tag_hygiene: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        Ok(hygiene::check(
+            self.cached_data.tags.as_ref().map_or(&[], |t| t),
+            self.cached_data.pins.as_ref().map_or(&[], |p| p),
+        ))
+    }
+
+    /// Applies the renames proposed by [`tag_hygiene`](#method.tag_hygiene) through
+    /// [`rename_tag`](#method.rename_tag), stopping at the first failing one.
+    ///
+    /// Local cache isn't updated, call [`update_cache`](#method.update_cache) afterwards.
+    pub fn apply_tag_renames(&self, renames: &[TagRename]) -> Result<(), Error> {
+        debug!("apply_tag_renames: starting.");
+        for rename in renames {
+            self.rename_tag(&rename.from.0, &rename.to.0)?;
+        }
+        Ok(())
+    }
+}
+
+// Tag hierarchy functions
+impl<'api, 'pin> Pinboard<'api, 'pin> {
+    fn cached_tags(&self) -> impl Iterator<Item = &Tag> {
+        self.cached_data.tags.iter().flatten().map(|ct| &ct.tag)
+    }
+
+    /// Cached tags arranged in trees according to the
+    /// [`set_tag_separator`](#method.set_tag_separator) setting.
+    pub fn tag_tree(&self) -> Option<Vec<TagNode>> {
+        debug!("tag_tree: starting.");
+        let roots = tree::build(
+            self.cached_tags(),
+            &self.cfg.tag_separator,
+            self.cfg.diacritic_insensitive_search,
+        );
+        match roots.len() {
+            0 => None,
+            _ => Some(roots),
+        }
+    }
+
+    /// The node of the tag tree at `path`, e.g. `lang` for all of `lang/rust`, `lang/go`...
+    pub fn tag_subtree(&self, path: &str) -> Option<TagNode> {
+        debug!("tag_subtree: starting.");
+        tree::subtree(
+            self.cached_tags(),
+            path,
+            &self.cfg.tag_separator,
+            self.cfg.diacritic_insensitive_search,
+        )
+    }
+
+    /// Nodes directly under `path` in the tag tree.
+    pub fn tag_children(&self, path: &str) -> Option<Vec<TagNode>> {
+        debug!("tag_children: starting.");
+        self.tag_subtree(path)
+            .map(|node| node.children)
+            .filter(|children| !children.is_empty())
+    }
+
+    /// Same as [`search_list_of_tags`](#method.search_list_of_tags) but only returns tags
+    /// that are `path` or nested under it.
+    pub fn search_list_of_tags_under(
+        &self,
+        path: &str,
+        query: &str,
+    ) -> Result<Option<Vec<&Tag>>, Error> {
+        debug!("search_list_of_tags_under: starting.");
+        let separator = &self.cfg.tag_separator;
+        let strip = self.cfg.diacritic_insensitive_search;
+        let r = self
+            .search_list_of_tags_iter(query, &SearchOptions::default())?
+            .filter(|t| tree::is_under(&t.0, path, separator, strip))
+            .collect::<Vec<&Tag>>();
+        match r.len() {
+            0 => Ok(None),
+            _ => Ok(Some(r)),
+        }
+    }
+
+    /// Renames `old` and every tag nested under it, e.g. `lang/rust` to `code/rust` when
+    /// renaming `lang` to `code`, with one `tags/rename` call per tag. Returns the renamed
+    /// tags with their new names.
+    ///
+    /// Fails without any call if `new` is blank, contains whitespace, starts or ends with the
+    /// separator, or is `old`. Otherwise stops at the first failing call, leaving the tags
+    /// renamed so far renamed.
+    /// Local cache isn't updated, call [`update_cache`](#method.update_cache) afterwards.
+    pub fn rename_tag_subtree(&self, old: &str, new: &str) -> Result<Vec<(String, String)>, Error> {
+        debug!("rename_tag_subtree: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Tags cache data is invalid")
+        }
+        let separator = &self.cfg.tag_separator;
+        if new.trim().is_empty() || new.contains(char::is_whitespace) {
+            bail!("New tag name can't be blank or contain whitespace: {:?}", new);
+        }
+        if new.starts_with(separator.as_str()) || new.ends_with(separator.as_str()) {
+            bail!("New tag name can't start or end with {}: {}", separator, new);
+        }
+        if old == new {
+            bail!("Tag {} would be renamed to itself.", old);
+        }
+        let strip = self.cfg.diacritic_insensitive_search;
+        let renames = self
+            .cached_tags()
+            .filter(|t| tree::is_under(&t.0, old, separator, strip))
+            .map(|t| (t.0.clone(), tree::renamed(&t.0, old, new, separator)))
+            .collect::<Vec<(String, String)>>();
+        for (from, to) in &renames {
+            self.api.tag_rename(from, to)?;
+        }
+        Ok(renames)
+    }
+}
+
+// Duplicate functions
+impl<'api, 'pin> Pinboard<'api, 'pin> {
+    /// Finds bookmarks saved more than once: under http and https, with and without `www`,
+    /// with tracking parameters and so on. If `similar_titles` is true, bookmarks on the same
+    /// host with near-identical titles are considered the same too.
+    pub fn find_duplicates(
+        &'pin self,
+        similar_titles: bool,
+    ) -> Result<Option<Vec<DuplicateGroup<'pin, 'pin>>>, Error> {
+        debug!("find_duplicates: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        let groups = self
+            .cached_data
+            .pins
+            .as_ref()
+            .map(|pins| duplicates::find(pins, similar_titles))
+            .unwrap_or_default();
+        match groups.len() {
+            0 => Ok(None),
+            _ => Ok(Some(groups)),
+        }
+    }
+
+    /// Replaces a group of duplicates by a single bookmark: its oldest pin updated with the
+    /// tags and descriptions of all the others (see [`DuplicateGroup::merged`]), which are
+    /// then deleted. Nothing is changed if `dry_run` is true. An [`InvalidPin`] error is
+    /// returned before any request if the merged bookmark wouldn't fit Pinboard's limits.
+    ///
+    /// Local cache isn't updated, call [`update_cache`](#method.update_cache) afterwards.
+    pub fn merge_duplicates(
+        &self,
+        group: &DuplicateGroup,
+        dry_run: bool,
+    ) -> Result<MergeReport, Error> {
+        debug!("merge_duplicates: starting.");
+        let merged = group.merged();
+        merged.validate()?;
+        let deleted = group.pins[1..]
+            .iter()
+            .map(|p| p.url.to_string())
+            .filter(|url| *url != merged.url)
+            .collect::<Vec<String>>();
+        if !dry_run {
+            self.api.add_url(merged.clone())?;
+            for url in &deleted {
+                self.api.delete(url)?;
+            }
+        }
+        Ok(MergeReport {
+            merged,
+            deleted,
+            dry_run,
+        })
+    }
+}
+
+// Tag statistics functions
+impl<'api, 'pin> Pinboard<'api, 'pin> {
+    /// Usage statistics of `tag` over the cached bookmarks, with its `top` most related tags
+    /// and domains. Returns `None` if no bookmark uses it.
+    pub fn tag_stats(&self, tag: &str, top: usize) -> Result<Option<TagStats>, Error> {
+        debug!("tag_stats: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        let key = normalize(tag, self.cfg.diacritic_insensitive_search);
+        Ok(stats::compute(
+            self.cached_data.pins.as_ref().map_or(&[], |p| p),
+            |t| t == key,
+            top,
+        )
+        .pop())
+    }
+
+    /// Usage statistics of every tag of the cached bookmarks, most used first, each with its
+    /// `top` most related tags and domains.
+    pub fn all_tag_stats(&self, top: usize) -> Result<Vec<TagStats>, Error> {
+        debug!("all_tag_stats: starting.");
+        if !self.cached_data.cache_ok() {
+            bail!("Cache data is invalid.");
+        }
+        Ok(stats::compute(
+            self.cached_data.pins.as_ref().map_or(&[], |p| p),
+            |_| true,
+            top,
+        ))
+    }
 }
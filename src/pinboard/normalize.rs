@@ -0,0 +1,79 @@
+//! Normalization applied to texts before they are compared.
+//!
+//! Texts are put in NFKC form, so that e.g. full-width `Ｒｕｓｔ` or the `ﬁ` ligature compare
+//! equal to `Rust` and `fi`, and are case folded, which also maps `ß` to `ss`. Diacritics
+//! (`é`, `ä`, ...) can optionally be stripped too, so `cafe` finds `Café`.
+use caseless::Caseless;
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::UnicodeNormalization;
+
+/// Returns true if `c` combines with the char before it, like most diacritics do.
+pub fn is_combining(c: char) -> bool {
+    canonical_combining_class(c) != 0
+}
+
+/// Returns the normalized form of `text`.
+pub fn normalize(text: &str, strip_diacritics: bool) -> String {
+    if text.is_ascii() {
+        return text.to_ascii_lowercase();
+    }
+    if strip_diacritics {
+        text.nfkc()
+            .default_case_fold()
+            .nfd()
+            .filter(|&c| !is_combining(c))
+            .nfkc()
+            .collect()
+    } else {
+        text.nfkc().default_case_fold().nfkc().collect()
+    }
+}
+
+/// Returns true if `a` and `b` are the same once normalized, keeping diacritics.
+pub fn eq(a: &str, b: &str) -> bool {
+    if a.is_ascii() && b.is_ascii() {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        normalize(a, false) == normalize(b, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger;
+
+    #[test]
+    fn normalizes() {
+        let _ = env_logger::try_init();
+        debug!("normalizes: starting");
+        assert_eq!("rust", normalize("RuSt", false));
+        assert_eq!("rust", normalize("Ｒｕｓｔ", false));
+        assert_eq!("file", normalize("ﬁle", false));
+        assert_eq!("strasse", normalize("Straße", false));
+        assert_eq!("café", normalize("CAFÉ", false));
+        assert_eq!("cafe", normalize("CAFÉ", true));
+        // Decomposed and precomposed forms are the same
+        assert_eq!(normalize("cafe\u{301}", false), normalize("café", false));
+        assert_eq!("cafe", normalize("cafe\u{301}", true));
+        assert_eq!("ärger", normalize("Ärger", false));
+        assert_eq!("arger", normalize("Ärger", true));
+        // Cyrillic
+        assert_eq!("здравствуйт", normalize("Здравствуйт", false));
+        assert_eq!("еж", normalize("Ёж", true));
+        // Emoji and CJK are left alone
+        assert_eq!("tag👻2", normalize("TaG👻2", false));
+        assert_eq!("世", normalize("世", true));
+    }
+
+    #[test]
+    fn equality() {
+        let _ = env_logger::try_init();
+        debug!("equality: starting");
+        assert!(eq("Rust", "rUST"));
+        assert!(eq("Ärger", "ärger"));
+        assert!(!eq("Ärger", "arger"));
+        assert!(eq("ＴａＧ👻", "tag👻"));
+        assert!(!eq("tag👻1", "tag👻2"));
+    }
+}
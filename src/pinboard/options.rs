@@ -0,0 +1,271 @@
+//! Paging and ordering of search results.
+//!
+//! Without a sort key, results come in cache order (newest pins first, most used tags first)
+//! and are produced lazily, so a limit stops the search as soon as enough results are found.
+//! Sorting needs all the matches, but only the ones up to `offset + limit` are fully sorted.
+use std::cmp::Ordering;
+
+use chrono::prelude::*;
+
+use super::cached_data::{CachedPin, CachedTag};
+use super::filter::SearchFilter;
+use super::pin::Pin;
+use super::search::{score_pin, Field, Term};
+use super::tag::{Tag, TagFreq};
+
+/// Lazily produced bookmarks.
+pub type Pins<'a, 'pin> = Box<dyn Iterator<Item = &'a Pin<'pin>> + 'a>;
+
+/// Lazily produced tags.
+pub type Tags<'a> = Box<dyn Iterator<Item = &'a Tag> + 'a>;
+
+/// What results are sorted by. Keys that don't apply to what is searched (e.g. `Url` for
+/// tags) keep the cache order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    /// Creation time of bookmarks.
+    Time,
+    /// Title of bookmarks or name of tags, case insensitively.
+    Title,
+    /// Canonical url of bookmarks.
+    Url,
+    /// Relevance to the query, as for ranked searches.
+    Relevance,
+    /// Frequency of tags.
+    TagFrequency,
+}
+
+/// Direction of a sort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// Which results of a search to return and in what order, e.g.
+/// `SearchOptions::new().sort(SortKey::Title, SortOrder::Ascending).offset(20).limit(10)`
+/// for the third page of ten results in alphabetical order.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub sort: Option<(SortKey, SortOrder)>,
+    /// Attributes bookmarks must have, checked before paging. Ignored when searching tags.
+    pub filter: SearchFilter,
+}
+
+impl SearchOptions {
+    pub fn new() -> Self {
+        SearchOptions::default()
+    }
+
+    /// Returns at most `n` results.
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Skips the first `n` results.
+    pub fn offset(mut self, n: usize) -> Self {
+        self.offset = n;
+        self
+    }
+
+    /// Sorts results by `key` in `order`. Equal results keep their cache order.
+    pub fn sort(mut self, key: SortKey, order: SortOrder) -> Self {
+        self.sort = Some((key, order));
+        self
+    }
+
+    /// Only returns bookmarks accepted by `filter`.
+    pub fn filter(mut self, filter: SearchFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub(crate) fn sort_key(&self) -> Option<SortKey> {
+        self.sort.map(|(key, _)| key)
+    }
+}
+
+/// Value results are compared by, computed once per result.
+#[derive(Debug, PartialEq, PartialOrd)]
+pub(crate) enum SortValue<'a> {
+    Time(DateTime<Utc>),
+    Text(&'a str),
+    Score(f64),
+    Count(usize),
+}
+
+/// Function computing the sort value of a result.
+pub(crate) type KeyFn<'a, T> = Box<dyn Fn(T) -> SortValue<'a> + 'a>;
+
+/// Sort value of pins for `options`. Pins are ranked by relevance to `terms` within `fields`.
+pub(crate) fn pin_key<'a, 'pin: 'a>(
+    options: &SearchOptions,
+    terms: Vec<Term>,
+    fields: Vec<Field>,
+) -> Option<KeyFn<'a, &'a CachedPin<'pin>>> {
+    match options.sort_key()? {
+        SortKey::Time => Some(Box::new(|p: &CachedPin| SortValue::Time(p.pin.time))),
+        SortKey::Title => Some(Box::new(|p: &'a CachedPin<'pin>| {
+            SortValue::Text(&p.title_lowered)
+        })),
+        SortKey::Url => Some(Box::new(|p: &'a CachedPin<'pin>| {
+            SortValue::Text(&p.url_normalized)
+        })),
+        SortKey::Relevance => {
+            let now = Utc::now();
+            Some(Box::new(move |p: &CachedPin| {
+                SortValue::Score(score_pin(p, &terms, &fields, now).unwrap_or(0.0))
+            }))
+        }
+        SortKey::TagFrequency => None,
+    }
+}
+
+/// Sort value of tags for `options`. Tags are ranked by relevance to `term`.
+pub(crate) fn tag_key<'a>(options: &SearchOptions, term: Term) -> Option<KeyFn<'a, &'a CachedTag>> {
+    match options.sort_key()? {
+        SortKey::Title => Some(Box::new(|t: &'a CachedTag| SortValue::Text(&t.tag_lowered))),
+        SortKey::Relevance => Some(Box::new(move |t: &CachedTag| {
+            SortValue::Score(term.score(&t.tag_lowered).unwrap_or(0.0))
+        })),
+        SortKey::TagFrequency => Some(Box::new(|t: &CachedTag| match t.tag.1 {
+            TagFreq::Used(n) => SortValue::Count(n),
+            TagFreq::New | TagFreq::Popular => SortValue::Count(0),
+        })),
+        SortKey::Time | SortKey::Url => None,
+    }
+}
+
+/// Applies `options` to `items`, sorting them by `key` if there is one.
+pub(crate) fn page<'a, T, I>(
+    items: I,
+    options: &SearchOptions,
+    key: Option<KeyFn<'a, T>>,
+) -> Box<dyn Iterator<Item = T> + 'a>
+where
+    T: Copy + 'a,
+    I: Iterator<Item = T> + 'a,
+{
+    let key = match key {
+        Some(key) => key,
+        None => {
+            let items = items.skip(options.offset);
+            return match options.limit {
+                Some(limit) => Box::new(items.take(limit)),
+                None => Box::new(items),
+            };
+        }
+    };
+    let descending = options
+        .sort
+        .map_or(false, |(_, o)| o == SortOrder::Descending);
+    let compare = |a: &(SortValue, usize, T), b: &(SortValue, usize, T)| {
+        let ord = a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal);
+        let ord = if descending { ord.reverse() } else { ord };
+        ord.then(a.1.cmp(&b.1))
+    };
+
+    let mut keyed = items
+        .enumerate()
+        .map(|(i, item)| (key(item), i, item))
+        .collect::<Vec<(SortValue, usize, T)>>();
+    let end = options
+        .limit
+        .map_or(keyed.len(), |limit| options.offset.saturating_add(limit));
+    if end < keyed.len() {
+        keyed.select_nth_unstable_by(end, compare);
+        keyed.truncate(end);
+    }
+    // The position tie-break makes the unstable sort stable.
+    keyed.sort_unstable_by(compare);
+    Box::new(
+        keyed
+            .into_iter()
+            .skip(options.offset)
+            .map(|(_, _, item)| item),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger;
+
+    fn paged(
+        items: &'static [&'static str],
+        options: &SearchOptions,
+        sorted: bool,
+    ) -> Vec<&'static str> {
+        let key: Option<KeyFn<&'static str>> = if sorted {
+            Some(Box::new(SortValue::Text))
+        } else {
+            None
+        };
+        page(items.iter().cloned(), options, key).collect()
+    }
+
+    #[test]
+    fn pages_in_natural_order() {
+        let _ = env_logger::try_init();
+        debug!("pages_in_natural_order: starting");
+        let items: &[&str] = &["c", "a", "d", "b"];
+        assert_eq!(items.to_vec(), paged(items, &SearchOptions::new(), false));
+        let options = SearchOptions::new().offset(1).limit(2);
+        assert_eq!(vec!["a", "d"], paged(items, &options, false));
+        let options = SearchOptions::new().offset(3).limit(5);
+        assert_eq!(vec!["b"], paged(items, &options, false));
+        assert!(paged(items, &SearchOptions::new().offset(9), false).is_empty());
+        assert!(paged(items, &SearchOptions::new().limit(0), false).is_empty());
+    }
+
+    #[test]
+    fn sorts_then_pages() {
+        let _ = env_logger::try_init();
+        debug!("sorts_then_pages: starting");
+        let items: &[&str] = &["c", "a", "d", "b", "a2", "e"];
+        let asc = SearchOptions::new().sort(SortKey::Title, SortOrder::Ascending);
+        assert_eq!(
+            vec!["a", "a2", "b", "c", "d", "e"],
+            paged(items, &asc, true)
+        );
+        assert_eq!(
+            vec!["b", "c"],
+            paged(items, &asc.clone().offset(2).limit(2), true)
+        );
+        let desc = SearchOptions::new().sort(SortKey::Title, SortOrder::Descending);
+        assert_eq!(
+            vec!["e", "d", "c"],
+            paged(items, &desc.clone().limit(3), true)
+        );
+        assert_eq!(
+            vec!["a2", "a"],
+            paged(items, &desc.offset(4).limit(10), true)
+        );
+    }
+
+    #[test]
+    fn keeps_order_of_equal_items() {
+        let _ = env_logger::try_init();
+        debug!("keeps_order_of_equal_items: starting");
+        let items = (0..50).collect::<Vec<usize>>();
+        for &order in &[SortOrder::Ascending, SortOrder::Descending] {
+            let options = SearchOptions::new()
+                .sort(SortKey::TagFrequency, order)
+                .offset(5)
+                .limit(10);
+            let key: KeyFn<usize> = Box::new(|i| SortValue::Count(i % 2));
+            let r = page(items.iter().cloned(), &options, Some(key)).collect::<Vec<usize>>();
+            let first = if order == SortOrder::Ascending { 0 } else { 1 };
+            let expected = (0..50)
+                .filter(|i| i % 2 == first)
+                .skip(5)
+                .take(10)
+                .collect::<Vec<usize>>();
+            assert_eq!(expected, r);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! User supplied regular expressions for regex search.
+//!
+//! Patterns are matched case insensitively against the fields as they are stored, without
+//! normalization, so that escapes such as `\D` keep their meaning. The size of the compiled
+//! expression is limited so a pathological pattern can't exhaust memory or time.
+use regex::{self, Regex, RegexBuilder};
+
+use super::pin::Pin;
+use super::SearchType;
+
+/// Longest pattern accepted, in bytes.
+const MAX_PATTERN_LEN: usize = 1000;
+/// Limit on the size of the compiled program and of the lazy DFA cache, in bytes.
+const SIZE_LIMIT: usize = 1 << 20;
+/// Limit on the nesting depth of groups and repetitions.
+const NEST_LIMIT: u32 = 50;
+
+/// Why a regular expression was refused.
+#[derive(Debug, Fail, PartialEq)]
+pub enum RegexError {
+    #[fail(display = "invalid regular expression `{}`: {}", pattern, reason)]
+    Invalid { pattern: String, reason: String },
+    #[fail(display = "regular expression `{}` is too complex", pattern)]
+    TooComplex { pattern: String },
+}
+
+/// Checks that `pattern` can be used for regex search.
+pub fn validate_regex(pattern: &str) -> Result<(), RegexError> {
+    compile(pattern).map(|_| ())
+}
+
+/// Compiles `pattern` for case insensitive matching within the size limits.
+pub fn compile(pattern: &str) -> Result<Regex, RegexError> {
+    let too_complex = || RegexError::TooComplex {
+        pattern: pattern.to_string(),
+    };
+    if pattern.len() > MAX_PATTERN_LEN {
+        return Err(too_complex());
+    }
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(SIZE_LIMIT)
+        .dfa_size_limit(SIZE_LIMIT)
+        .nest_limit(NEST_LIMIT)
+        .build()
+        .map_err(|e| match e {
+            regex::Error::CompiledTooBig(_) => too_complex(),
+            e => RegexError::Invalid {
+                pattern: pattern.to_string(),
+                reason: e.to_string(),
+            },
+        })
+}
+
+/// Returns true if `re` matches the field of `pin` selected by `search_type`. Tags are
+/// matched one at a time, so `^rust$` only finds the `rust` tag.
+pub fn matches_field(pin: &Pin, re: &Regex, search_type: SearchType) -> bool {
+    let in_tags = || pin.tags.split_whitespace().any(|t| re.is_match(t));
+    match search_type {
+        SearchType::TitleOnly => pin.title_contains("", Some(re)),
+        SearchType::TagOnly => in_tags(),
+        SearchType::UrlOnly => pin.url_contains("", Some(re)),
+        SearchType::DescriptionOnly => pin.extended_contains("", Some(re)),
+        SearchType::TagTitleOnly => pin.title_contains("", Some(re)) || in_tags(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use env_logger;
+
+    #[test]
+    fn compiles() {
+        let _ = env_logger::try_init();
+        debug!("compiles: starting");
+        let re = compile(r"^rust\b.*\d{4}$").expect("Valid pattern refused");
+        assert!(re.is_match("RUST book 2018"));
+        assert!(!re.is_match("rustacean 2018"));
+        assert!(compile("").is_ok());
+        assert!(compile("здравствуйт|café").unwrap().is_match("CAFÉ"));
+    }
+
+    #[test]
+    fn matches_fields() {
+        let _ = env_logger::try_init();
+        debug!("matches_fields: starting");
+        let pin = PinBuilder::new("https://docs.rs/tokio", "Tokio docs")
+            .tags("rust async-io")
+            .description("Runtime for 2018 edition")
+            .into_pin();
+        let re = compile("^async").unwrap();
+        assert!(matches_field(&pin, &re, SearchType::TagOnly));
+        assert!(!matches_field(&pin, &re, SearchType::TitleOnly));
+        let re = compile(r"\d{4}").unwrap();
+        assert!(matches_field(&pin, &re, SearchType::DescriptionOnly));
+        assert!(!matches_field(&pin, &re, SearchType::TagTitleOnly));
+        let re = compile(r"docs\.rs/").unwrap();
+        assert!(matches_field(&pin, &re, SearchType::UrlOnly));
+    }
+
+    #[test]
+    fn refuses_bad_patterns() {
+        let _ = env_logger::try_init();
+        debug!("refuses_bad_patterns: starting");
+        match validate_regex("rust(") {
+            Err(RegexError::Invalid { pattern, .. }) => assert_eq!("rust(", pattern),
+            r => panic!("unexpected {:?}", r),
+        }
+        assert!(validate_regex("[z-a]").is_err());
+        assert_eq!(
+            Err(RegexError::TooComplex {
+                pattern: "a{1000}{1000}".into()
+            }),
+            validate_regex("a{1000}{1000}")
+        );
+        let long = "a".repeat(MAX_PATTERN_LEN + 1);
+        assert_eq!(
+            Err(RegexError::TooComplex {
+                pattern: long.clone()
+            }),
+            validate_regex(&long)
+        );
+        let nested = format!("{}a{}", "(".repeat(100), ")".repeat(100));
+        assert!(validate_regex(&nested).is_err());
+    }
+}
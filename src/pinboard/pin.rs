@@ -5,7 +5,10 @@ use chrono::prelude::*;
 
 use regex::Regex;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+use super::tag;
+use super::validate::{self, InvalidPin};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Pin<'pin> {
     #[serde(rename = "href")]
     pub url: Cow<'pin, str>,
@@ -24,6 +27,17 @@ impl<'pin> Pin<'pin> {
         self.time
     }
 
+    /// Checks the pin against Pinboard's limits on titles, descriptions and tags.
+    pub fn validate(&self) -> Result<(), InvalidPin> {
+        validate::check(self)
+    }
+
+    /// Returns true if the pin has tags and they are all private.
+    pub fn has_only_private_tags(&self) -> bool {
+        let mut tags = self.tags.split_whitespace().peekable();
+        tags.peek().is_some() && tags.all(tag::is_private)
+    }
+
     pub fn contains(&self, q: &str) -> bool {
         self.title.to_lowercase().contains(q)
             || self.tags.to_lowercase().contains(q)
@@ -70,17 +84,6 @@ impl<'pin> Pin<'pin> {
             false
         }
     }
-
-    pub fn contains_fuzzy(&self, re: &Regex) -> bool {
-        re.is_match(&self.title)
-            || re.is_match(&self.tags)
-            || re.is_match(self.url.as_ref())
-            || if let Some(ref extended) = self.extended {
-                re.is_match(extended)
-            } else {
-                false
-            }
-    }
 }
 
 #[derive(Debug)]
@@ -107,8 +110,16 @@ impl<'pin> PinBuilder<'pin> {
 }
 
 impl<'pin> PinBuilder<'pin> {
+    /// Sets the tags, separated by whitespace or commas. Repeated tags are dropped, even if
+    /// they differ in case.
     pub fn tags<S: Into<Cow<'pin, str>>>(mut self, t: S) -> Self {
-        self.pin.tags = t.into();
+        let t = t.into();
+        let normalized = validate::normalize_tags(&t);
+        self.pin.tags = if normalized == t {
+            t
+        } else {
+            Cow::from(normalized)
+        };
         self
     }
 
@@ -130,6 +141,12 @@ impl<'pin> PinBuilder<'pin> {
     pub fn into_pin(self) -> Pin<'pin> {
         self.pin
     }
+
+    /// Same as `into_pin` but fails if the pin doesn't fit Pinboard's limits.
+    pub fn try_into_pin(self) -> Result<Pin<'pin>, InvalidPin> {
+        self.pin.validate()?;
+        Ok(self.pin)
+    }
 }
 
 #[cfg(test)]
@@ -139,6 +156,7 @@ mod tests {
     use env_logger;
 
     use crate::pinboard::mockito_helper::create_mockito_servers;
+    use crate::pinboard::validate::ValidationError;
 
     #[test]
     fn pin_builder_test() {
@@ -160,6 +178,34 @@ mod tests {
         assert_eq!(p.tags, "tag1 tag2");
     }
 
+    #[test]
+    fn validation_test() {
+        let _ = env_logger::try_init();
+        debug!("validation_test: starting");
+        let p = PinBuilder::new("https://a.com/", "title")
+            .tags("rust, async Rust")
+            .into_pin();
+        assert_eq!(p.tags, "rust async");
+        assert!(PinBuilder::new("https://a.com/", "title")
+            .tags("rust")
+            .try_into_pin()
+            .is_ok());
+        let e = PinBuilder::new("https://a.com/", "")
+            .try_into_pin()
+            .expect_err("Empty title is invalid");
+        assert_eq!(vec![ValidationError::EmptyTitle], e.errors);
+    }
+
+    #[test]
+    fn private_tags_test() {
+        let _ = env_logger::try_init();
+        debug!("private_tags_test: starting");
+        let pin = |tags: &'static str| PinBuilder::new("https://a.com/", "a").tags(tags).into_pin();
+        assert!(pin(".todo .later").has_only_private_tags());
+        assert!(!pin(".todo rust").has_only_private_tags());
+        assert!(!pin("").has_only_private_tags());
+    }
+
     #[test]
     fn test_pin_contain() {
         let _ = env_logger::try_init();
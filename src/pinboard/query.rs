@@ -0,0 +1,649 @@
+//! A small query language for searching bookmarks.
+//!
+//! A query is a list of space separated terms which all have to match:
+//!
+//! - `word` or `"exact phrase"`: text found in title, tags, url or description
+//! - `tag:rust`: pin has the tag `rust`
+//! - `title:async`, `url:docs`, `desc:guide`: text found in the given field
+//! - `site:github.com`: url's host is `github.com` or one of its subdomains
+//! - `toread:yes`, `private:no`: unread/private status (`yes|no|true|false`)
+//! - `before:2020-01-01`, `after:2019-12-31`: pin was created before the given day, or
+//!   after the end of it (UTC)
+//!
+//! Any term can be negated by prefixing it with `-`, e.g. `-tag:old`. Field values can be
+//! quoted: `title:"the book"`.
+//!
+//! Terms separated by `OR` are alternatives, only one of which has to match. `OR` binds
+//! tighter than the implicit AND: `rust async OR tokio` finds pins about rust that mention
+//! async or tokio.
+//!
+//! Queries can also be built programmatically, e.g.
+//! `Query::Tag("rust".into()).and(Query::Title("async".into()).or(Query::Url("tokio".into())))`.
+//!
+//! All text comparisons are case insensitive.
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use url::Url;
+
+use super::cached_data::CachedPin;
+use super::normalize::normalize;
+
+/// Parsed search query
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    /// Matches if all sub-queries match. An empty list matches everything.
+    All(Vec<Query>),
+    /// Matches if any of the sub-queries matches. An empty list matches nothing.
+    Any(Vec<Query>),
+    /// Matches if the sub-query doesn't match.
+    Not(Box<Query>),
+    /// Text found in any of title, tags, url or description.
+    Text(String),
+    /// Pin has exactly this tag.
+    Tag(String),
+    /// Text found in title.
+    Title(String),
+    /// Text found in url.
+    Url(String),
+    /// Text found in description (extended).
+    Description(String),
+    /// Host of pin's url is this domain or one of its subdomains.
+    Site(String),
+    ToRead(bool),
+    Private(bool),
+    /// Pin was created before this time.
+    Before(DateTime<Utc>),
+    /// Pin was created at or after this time.
+    After(DateTime<Utc>),
+}
+
+/// Errors from parsing a query.
+///
+/// `position` is the byte offset of the offending token within the query string.
+#[derive(Debug, Fail, PartialEq)]
+pub enum QueryError {
+    #[fail(display = "unknown field `{}` at position {}", field, position)]
+    UnknownField { field: String, position: usize },
+    #[fail(display = "missing value for `{}` at position {}", field, position)]
+    MissingValue { field: String, position: usize },
+    #[fail(
+        display = "invalid value `{}` for `{}` at position {}",
+        value, field, position
+    )]
+    InvalidValue {
+        field: String,
+        value: String,
+        position: usize,
+    },
+    #[fail(display = "unterminated quote at position {}", position)]
+    UnterminatedQuote { position: usize },
+}
+
+impl QueryError {
+    /// Byte offset of the offending token within the query string.
+    pub fn position(&self) -> usize {
+        match *self {
+            QueryError::UnknownField { position, .. }
+            | QueryError::MissingValue { position, .. }
+            | QueryError::InvalidValue { position, .. }
+            | QueryError::UnterminatedQuote { position } => position,
+        }
+    }
+}
+
+/// A single term as it appears in the query string.
+#[derive(Debug, PartialEq)]
+struct Token {
+    negated: bool,
+    quoted: bool,
+    field: Option<String>,
+    field_position: usize,
+    value: String,
+    value_position: usize,
+}
+
+impl Query {
+    pub fn parse(q: &str) -> Result<Query, QueryError> {
+        debug!("parse: starting.");
+        let tokens = tokenize(q)?;
+        let count = tokens.len();
+        // Terms joined by `OR`, the groups themselves being joined by AND.
+        let mut groups: Vec<Vec<Query>> = Vec::new();
+        let mut or_pending = false;
+        for (i, token) in tokens.into_iter().enumerate() {
+            // A leading, trailing or repeated `OR` is just text.
+            if token.is_or() && !groups.is_empty() && !or_pending && i + 1 < count {
+                or_pending = true;
+                continue;
+            }
+            let term = Query::from_token(token)?;
+            match groups.last_mut() {
+                Some(group) if or_pending => group.push(term),
+                _ => groups.push(vec![term]),
+            }
+            or_pending = false;
+        }
+        Ok(Query::All(
+            groups
+                .into_iter()
+                .map(|mut group| {
+                    if group.len() == 1 {
+                        group.remove(0)
+                    } else {
+                        Query::Any(group)
+                    }
+                })
+                .collect(),
+        ))
+    }
+
+    /// Query matching if both this one and `other` match.
+    pub fn and(self, other: Query) -> Query {
+        match self {
+            Query::All(mut queries) => {
+                queries.push(other);
+                Query::All(queries)
+            }
+            query => Query::All(vec![query, other]),
+        }
+    }
+
+    /// Query matching if this one or `other` matches.
+    pub fn or(self, other: Query) -> Query {
+        match self {
+            Query::Any(mut queries) => {
+                queries.push(other);
+                Query::Any(queries)
+            }
+            query => Query::Any(vec![query, other]),
+        }
+    }
+
+    /// Query matching if this one doesn't.
+    pub fn negate(self) -> Query {
+        match self {
+            Query::Not(query) => *query,
+            query => Query::Not(Box::new(query)),
+        }
+    }
+
+    /// Texts this query looks for in pins, leaving out excluded ones.
+    pub(crate) fn texts(&self) -> Vec<&str> {
+        match *self {
+            Query::All(ref queries) | Query::Any(ref queries) => {
+                queries.iter().flat_map(Query::texts).collect()
+            }
+            Query::Text(ref s)
+            | Query::Tag(ref s)
+            | Query::Title(ref s)
+            | Query::Url(ref s)
+            | Query::Description(ref s) => vec![s.as_str()],
+            _ => Vec::new(),
+        }
+    }
+
+    fn from_token(token: Token) -> Result<Query, QueryError> {
+        let Token {
+            negated,
+            field,
+            field_position,
+            value: raw_value,
+            value_position,
+            ..
+        } = token;
+        let value = raw_value.to_lowercase();
+        let term = match field {
+            None => Query::Text(value),
+            Some(field) => {
+                if value.is_empty() {
+                    return Err(QueryError::MissingValue {
+                        field,
+                        position: field_position,
+                    });
+                }
+                let invalid = |field: &str| QueryError::InvalidValue {
+                    field: field.to_string(),
+                    value: raw_value.clone(),
+                    position: value_position,
+                };
+                match field.to_lowercase().as_str() {
+                    "tag" => Query::Tag(value),
+                    "title" => Query::Title(value),
+                    "url" => Query::Url(value),
+                    "desc" | "description" => Query::Description(value),
+                    "site" => Query::Site(value.trim_start_matches("www.").to_string()),
+                    "toread" => Query::ToRead(parse_bool(&value).ok_or_else(|| invalid(&field))?),
+                    "private" => Query::Private(parse_bool(&value).ok_or_else(|| invalid(&field))?),
+                    "before" => Query::Before(parse_day(&value).ok_or_else(|| invalid(&field))?),
+                    "after" => Query::After(
+                        parse_day(&value).ok_or_else(|| invalid(&field))? + Duration::days(1),
+                    ),
+                    _ => {
+                        return Err(QueryError::UnknownField {
+                            field,
+                            position: field_position,
+                        });
+                    }
+                }
+            }
+        };
+        if negated {
+            Ok(Query::Not(Box::new(term)))
+        } else {
+            Ok(term)
+        }
+    }
+
+    /// Normalizes the text values of this query the same way the cached pins' fields are.
+    pub(crate) fn normalize(self, strip_diacritics: bool) -> Query {
+        let n = |s: String| normalize(&s, strip_diacritics);
+        match self {
+            Query::All(queries) => Query::All(
+                queries
+                    .into_iter()
+                    .map(|q| q.normalize(strip_diacritics))
+                    .collect(),
+            ),
+            Query::Any(queries) => Query::Any(
+                queries
+                    .into_iter()
+                    .map(|q| q.normalize(strip_diacritics))
+                    .collect(),
+            ),
+            Query::Not(query) => Query::Not(Box::new(query.normalize(strip_diacritics))),
+            Query::Text(s) => Query::Text(n(s)),
+            Query::Tag(s) => Query::Tag(n(s)),
+            Query::Title(s) => Query::Title(n(s)),
+            Query::Url(s) => Query::Url(n(s)),
+            Query::Description(s) => Query::Description(n(s)),
+            other => other,
+        }
+    }
+
+    /// Returns true if the cached pin satisfies this query.
+    pub(crate) fn matches(&self, cached_pin: &CachedPin) -> bool {
+        match *self {
+            Query::All(ref queries) => queries.iter().all(|q| q.matches(cached_pin)),
+            Query::Any(ref queries) => queries.iter().any(|q| q.matches(cached_pin)),
+            Query::Not(ref query) => !query.matches(cached_pin),
+            Query::Text(ref s) => {
+                cached_pin.title_lowered.contains(s)
+                    || cached_pin.tag_list.iter().any(|t| t.contains(s))
+                    || cached_pin.url_lowered.contains(s)
+                    || cached_pin
+                        .extended_lowered
+                        .as_ref()
+                        .map_or(false, |e| e.contains(s))
+            }
+            Query::Tag(ref s) => cached_pin.tag_list.iter().any(|t| t == s),
+            Query::Title(ref s) => cached_pin.title_lowered.contains(s),
+            Query::Url(ref s) => cached_pin.url_lowered.contains(s),
+            Query::Description(ref s) => cached_pin
+                .extended_lowered
+                .as_ref()
+                .map_or(false, |e| e.contains(s)),
+            Query::Site(ref s) => Url::parse(&cached_pin.pin.url)
+                .ok()
+                .and_then(|u| u.host_str().map(str::to_lowercase))
+                .map_or(false, |host| host == *s || host.ends_with(&[".", s.as_str()].concat())),
+            Query::ToRead(v) => (cached_pin.pin.toread == "yes") == v,
+            Query::Private(v) => (cached_pin.pin.shared == "no") == v,
+            Query::Before(t) => cached_pin.pin.time < t,
+            Query::After(t) => cached_pin.pin.time >= t,
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s)
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn quoted(s: &str) -> String {
+            if s.is_empty() || s.contains(char::is_whitespace) {
+                format!("\"{}\"", s)
+            } else {
+                s.to_string()
+            }
+        }
+        fn yes_no(v: bool) -> &'static str {
+            if v {
+                "yes"
+            } else {
+                "no"
+            }
+        }
+        match *self {
+            Query::All(ref queries) => {
+                let terms = queries.iter().map(|q| q.to_string()).collect::<Vec<_>>();
+                write!(f, "{}", terms.join(" "))
+            }
+            // Alternatives can't be grouped in the query language, so an `All` nested in an
+            // `Any`, or a negated `Any`, isn't parsed back the same.
+            Query::Any(ref queries) => {
+                let terms = queries.iter().map(|q| q.to_string()).collect::<Vec<_>>();
+                write!(f, "{}", terms.join(" OR "))
+            }
+            Query::Not(ref query) => write!(f, "-{}", query),
+            Query::Text(ref s) => write!(f, "{}", quoted(s)),
+            Query::Tag(ref s) => write!(f, "tag:{}", quoted(s)),
+            Query::Title(ref s) => write!(f, "title:{}", quoted(s)),
+            Query::Url(ref s) => write!(f, "url:{}", quoted(s)),
+            Query::Description(ref s) => write!(f, "desc:{}", quoted(s)),
+            Query::Site(ref s) => write!(f, "site:{}", quoted(s)),
+            Query::ToRead(v) => write!(f, "toread:{}", yes_no(v)),
+            Query::Private(v) => write!(f, "private:{}", yes_no(v)),
+            Query::Before(t) => write!(f, "before:{}", t.format("%Y-%m-%d")),
+            Query::After(t) => write!(f, "after:{}", (t - Duration::days(1)).format("%Y-%m-%d")),
+        }
+    }
+}
+
+impl Token {
+    /// Returns true if this token is the `OR` operator rather than a term.
+    fn is_or(&self) -> bool {
+        !self.negated && !self.quoted && self.field.is_none() && self.value == "OR"
+    }
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s {
+        "yes" | "true" => Some(true),
+        "no" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_day(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|t| Utc.from_utc_datetime(&t))
+}
+
+/// Splits the query into terms, honoring quotes and `field:` prefixes.
+fn tokenize(q: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = q.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut negated = false;
+        let mut quoted = false;
+        if c == '-' {
+            negated = true;
+            chars.next();
+        }
+
+        let mut field = None;
+        let mut field_position = position;
+        let mut value = String::new();
+        let mut value_position = chars.peek().map_or(q.len(), |&(i, _)| i);
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            if c == '"' {
+                quoted = true;
+                let mut closed = false;
+                for (_, c) in &mut chars {
+                    if c == '"' {
+                        closed = true;
+                        break;
+                    }
+                    value.push(c);
+                }
+                if !closed {
+                    return Err(QueryError::UnterminatedQuote { position: i });
+                }
+            } else if c == ':'
+                && field.is_none()
+                && !value.is_empty()
+                && value.chars().all(char::is_alphabetic)
+                && !q[i..].starts_with("://")
+            {
+                field_position = value_position;
+                field = Some(value);
+                value = String::new();
+                value_position = chars.peek().map_or(q.len(), |&(i, _)| i);
+            } else {
+                value.push(c);
+            }
+        }
+
+        // A lone `-` is just text.
+        if negated && field.is_none() && value.is_empty() {
+            negated = false;
+            value.push('-');
+        }
+        tokens.push(Token {
+            negated,
+            quoted,
+            field,
+            field_position,
+            value,
+            value_position,
+        });
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use env_logger;
+
+    fn at(y: i32, m: u32, d: u32, h: u32) -> DateTime<Utc> {
+        Utc.from_utc_datetime(
+            &NaiveDate::from_ymd_opt(y, m, d)
+                .unwrap()
+                .and_hms_opt(h, 0, 0)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn parse_query() {
+        let _ = env_logger::try_init();
+        debug!("parse_query: starting");
+        let q = Query::parse(
+            r#"tag:Rust -tag:old title:async site:www.github.com toread:yes private:no before:2020-01-01 "exact Phrase""#,
+        )
+        .expect("Couldn't parse query");
+        assert_eq!(
+            Query::All(vec![
+                Query::Tag("rust".into()),
+                Query::Not(Box::new(Query::Tag("old".into()))),
+                Query::Title("async".into()),
+                Query::Site("github.com".into()),
+                Query::ToRead(true),
+                Query::Private(false),
+                Query::Before(at(2020, 1, 1, 0)),
+                Query::Text("exact phrase".into()),
+            ]),
+            q
+        );
+
+        let q: Query = r#"-"two words" title:"the book" https://github.com - after:2019-12-31"#
+            .parse()
+            .expect("Couldn't parse query");
+        assert_eq!(
+            Query::All(vec![
+                Query::Not(Box::new(Query::Text("two words".into()))),
+                Query::Title("the book".into()),
+                Query::Text("https://github.com".into()),
+                Query::Text("-".into()),
+                Query::After(at(2020, 1, 1, 0)),
+            ]),
+            q
+        );
+
+        assert_eq!(Query::All(vec![]), Query::parse("   ").unwrap());
+    }
+
+    #[test]
+    fn parse_or_groups() {
+        let _ = env_logger::try_init();
+        debug!("parse_or_groups: starting");
+        let text = |s: &str| Query::Text(s.into());
+        assert_eq!(
+            Query::All(vec![
+                text("rust"),
+                Query::Any(vec![
+                    Query::Tag("async".into()),
+                    text("tokio"),
+                    Query::Not(Box::new(Query::Title("old".into()))),
+                ]),
+            ]),
+            Query::parse("rust tag:async OR tokio OR -title:old").unwrap()
+        );
+        // Only a bare, upper case OR between two terms is an operator
+        assert_eq!(
+            Query::All(vec![text("or"), text("rust"), text("or")]),
+            Query::parse("OR rust OR").unwrap()
+        );
+        assert_eq!(
+            Query::All(vec![Query::Any(vec![text("a"), text("or")]), text("b")]),
+            Query::parse("a OR OR b").unwrap()
+        );
+        assert_eq!(
+            Query::All(vec![text("a"), text("or"), text("b")]),
+            Query::parse(r#"a "OR" b"#).unwrap()
+        );
+        assert_eq!(
+            Query::All(vec![text("a"), text("or"), text("b")]),
+            Query::parse("a or b").unwrap()
+        );
+    }
+
+    #[test]
+    fn build_queries() {
+        let _ = env_logger::try_init();
+        debug!("build_queries: starting");
+        let q = Query::Tag("rust".into())
+            .and(Query::Title("async".into()).or(Query::Url("tokio".into())))
+            .and(Query::Tag("old".into()).negate());
+        assert_eq!(
+            Query::All(vec![
+                Query::Tag("rust".into()),
+                Query::Any(vec![
+                    Query::Title("async".into()),
+                    Query::Url("tokio".into())
+                ]),
+                Query::Not(Box::new(Query::Tag("old".into()))),
+            ]),
+            q
+        );
+        assert_eq!(Query::parse(&q.to_string()).unwrap(), q);
+        assert_eq!(
+            Query::Tag("a".into()),
+            Query::Tag("a".into()).negate().negate()
+        );
+        assert_eq!(vec!["rust", "async", "tokio"], q.texts());
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let _ = env_logger::try_init();
+        debug!("display_round_trip: starting");
+        let s = r#"tag:rust -tag:old title:"the book" toread:no after:2019-12-31 здравствуйт"#;
+        let q = Query::parse(s).expect("Couldn't parse query");
+        assert_eq!(s, q.to_string());
+        assert_eq!(q, Query::parse(&q.to_string()).unwrap());
+    }
+
+    #[test]
+    fn parse_errors() {
+        let _ = env_logger::try_init();
+        debug!("parse_errors: starting");
+        let e = Query::parse("rust colour:red").expect_err("Accepted unknown field");
+        assert_eq!(
+            QueryError::UnknownField {
+                field: "colour".into(),
+                position: 5
+            },
+            e
+        );
+
+        let e = Query::parse("rust -toread:maybe").expect_err("Accepted bad bool");
+        assert_eq!(
+            QueryError::InvalidValue {
+                field: "toread".into(),
+                value: "maybe".into(),
+                position: 13
+            },
+            e
+        );
+
+        let e = Query::parse("тег before:2020-13-01").expect_err("Accepted bad date");
+        assert_eq!(14, e.position());
+
+        let e = Query::parse("rust tag: go").expect_err("Accepted empty value");
+        assert_eq!(
+            QueryError::MissingValue {
+                field: "tag".into(),
+                position: 5
+            },
+            e
+        );
+
+        let e = Query::parse(r#"rust title:"the book"#).expect_err("Accepted open quote");
+        assert_eq!(QueryError::UnterminatedQuote { position: 11 }, e);
+        assert_eq!("unterminated quote at position 11", e.to_string());
+    }
+
+    #[test]
+    fn query_matches() {
+        let _ = env_logger::try_init();
+        debug!("query_matches: starting");
+        let mut pin = PinBuilder::new(
+            "https://docs.github.com/Здравствуйт?q=13#fragment",
+            "Async Rust book",
+        )
+        .tags("rust Async")
+        .toread("yes")
+        .shared("no")
+        .description("A guide to futures")
+        .into_pin();
+        pin.time = at(2019, 6, 1, 12);
+        let cached_pin = CachedPin::new(pin);
+
+        let matches = |q: &str| Query::parse(q).unwrap().matches(&cached_pin);
+        assert!(matches(""));
+        assert!(matches("tag:rust tag:ASYNC"));
+        assert!(!matches("tag:rus"));
+        assert!(matches("rus"));
+        assert!(matches("-tag:old title:async"));
+        assert!(!matches("-tag:rust"));
+        assert!(matches("site:github.com"));
+        assert!(matches("site:docs.github.com"));
+        assert!(!matches("site:hub.com"));
+        assert!(matches("url:здравствуйт"));
+        assert!(matches(r#"desc:"to futures""#));
+        assert!(!matches(r#""rust guide""#));
+        assert!(matches("toread:yes private:yes"));
+        assert!(!matches("toread:no"));
+        assert!(matches("before:2019-06-02 after:2019-05-31"));
+        assert!(!matches("before:2019-06-01"));
+        assert!(!matches("after:2019-06-01"));
+        assert!(matches("tag:go OR tag:rust"));
+        assert!(!matches("tag:go OR tag:python"));
+        assert!(matches("tag:go OR -tag:python"));
+        assert!(!matches("guide tag:go OR toread:no"));
+        assert!(!Query::Any(vec![]).matches(&cached_pin));
+    }
+}
@@ -0,0 +1,128 @@
+//! Named searches kept in the cache folder so they can be run again later.
+//!
+//! Each saved search remembers the urls of the bookmarks it found when it last ran, so that
+//! the bookmarks it finds since then can be reported as new, whatever their creation time:
+//! bookmarks imported or posted with an older date are new too.
+use super::cached_data::Named;
+use super::filter::SearchFilter;
+use super::pin::Pin;
+use super::SearchType;
+
+/// A search as done by [`Pinboard::search`](../struct.Pinboard.html#method.search), with its
+/// own fuzzy setting and filter.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SavedSearch {
+    pub name: String,
+    pub queries: Vec<String>,
+    /// Fields searched, all of them if empty.
+    pub fields: Vec<SearchType>,
+    pub fuzzy: bool,
+    pub filter: SearchFilter,
+    /// Sorted urls of the bookmarks found when the search last ran, `None` if it never ran.
+    pub seen_urls: Option<Vec<String>>,
+}
+
+impl Named for SavedSearch {
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl SavedSearch {
+    /// Creates a non fuzzy search of `queries` in all fields, without filter.
+    pub fn new<S: Into<String>>(name: S, queries: Vec<String>) -> Self {
+        SavedSearch {
+            name: name.into(),
+            queries,
+            fields: Vec::new(),
+            fuzzy: false,
+            filter: SearchFilter::default(),
+            seen_urls: None,
+        }
+    }
+
+    pub fn fields(mut self, fields: Vec<SearchType>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    pub fn fuzzy(mut self, v: bool) -> Self {
+        self.fuzzy = v;
+        self
+    }
+
+    pub fn filter(mut self, filter: SearchFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Returns true if `pin` wasn't found by the last run. Every pin is new to a search that
+    /// never ran.
+    pub fn is_new(&self, pin: &Pin) -> bool {
+        self.seen_urls.as_ref().map_or(true, |urls| {
+            urls.binary_search_by(|u| u.as_str().cmp(pin.url.as_ref())).is_err()
+        })
+    }
+
+    /// Remembers `pins` as the bookmarks found by the last run.
+    pub fn mark_seen(&mut self, pins: &[&Pin]) {
+        let mut urls = pins
+            .iter()
+            .map(|p| p.url.to_string())
+            .collect::<Vec<String>>();
+        urls.sort();
+        urls.dedup();
+        self.seen_urls = Some(urls);
+    }
+}
+
+/// Bookmarks matching a saved search that it didn't find when it last ran.
+#[derive(Debug)]
+pub struct SavedSearchReport<'a, 'pin: 'a> {
+    pub name: String,
+    pub new_pins: Vec<&'a Pin<'pin>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use crate::rmps::{self, Serializer};
+    use env_logger;
+    use serde::Serialize;
+
+    #[test]
+    fn round_trips() {
+        let _ = env_logger::try_init();
+        debug!("round_trips: starting");
+        let search = SavedSearch::new("rust", vec!["rust".into(), "async".into()])
+            .fields(vec![SearchType::TitleOnly, SearchType::TagOnly])
+            .fuzzy(true)
+            .filter(SearchFilter::new().unread_only().tag_count(Some(1), None));
+        let mut buf: Vec<u8> = Vec::new();
+        vec![search.clone()]
+            .serialize(&mut Serializer::new(&mut buf))
+            .expect("Can't serialize");
+        let read: Vec<SavedSearch> = rmps::from_slice(&buf).expect("Can't deserialize");
+        assert_eq!(vec![search], read);
+    }
+
+    #[test]
+    fn new_pins() {
+        let _ = env_logger::try_init();
+        debug!("new_pins: starting");
+        let a = PinBuilder::new("https://a.com/", "A").into_pin();
+        let b = PinBuilder::new("https://b.com/", "B").into_pin();
+        let mut search = SavedSearch::new("a", vec!["a".into()]);
+        assert!(search.is_new(&a));
+        search.mark_seen(&[]);
+        assert!(search.is_new(&a));
+        search.mark_seen(&[&b, &a, &b]);
+        assert_eq!(
+            Some(vec!["https://a.com/".to_string(), "https://b.com/".to_string()]),
+            search.seen_urls
+        );
+        assert!(!search.is_new(&a));
+        assert!(!search.is_new(&b));
+    }
+}
@@ -0,0 +1,614 @@
+//! Relevance scoring of search results.
+//!
+//! Each query term is looked up in every selected field of a pin. A hit is scored by
+//!
+//! - the field it was found in: title > tags > url > description
+//! - how it matched: the whole field/tag, the start of a word, anywhere, or fuzzily
+//! - how early in the field it was found
+//!
+//! A term's score is the sum of its best hit in each field and a pin's score is the sum of its
+//! terms' scores, slightly boosted for recently created pins. Pins that don't match every term
+//! get no score.
+//!
+//! Every result also carries the spans of its fields that matched, so they can be highlighted.
+use std::ops::Range;
+
+use chrono::prelude::*;
+
+use super::cached_data::CachedPin;
+use super::fuzzy::FuzzyMatcher;
+use super::normalize::{is_combining, normalize};
+use super::pin::Pin;
+use super::SearchType;
+
+/// How much newer pins are favored: a brand new pin gets this much extra (relative) score,
+/// a year old pin half of it, and so on.
+const RECENCY_BOOST: f64 = 0.25;
+
+/// A bookmark returned from a ranked search.
+#[derive(Debug)]
+pub struct SearchResult<'a, 'pin: 'a> {
+    pub pin: &'a Pin<'pin>,
+    /// Relevance of this result, higher is better.
+    pub score: f64,
+    /// Fields of the pin the query matched, in the order they were searched.
+    pub matches: Vec<FieldMatch>,
+}
+
+/// Parts of a pin's field matched by the query.
+///
+/// Ranges index into the field's original text (`Pin::title`, `Pin::tags`, `Pin::url` or
+/// `Pin::extended`), are sorted and don't overlap. A fuzzy match is reported as the
+/// individual characters that matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMatch {
+    pub field: Field,
+    /// Matched byte ranges, suitable for slicing the field's text.
+    pub byte_ranges: Vec<Range<usize>>,
+    /// The same ranges counted in `char`s.
+    pub char_ranges: Vec<Range<usize>>,
+}
+
+/// How a term matched a field, from worst to best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchKind {
+    /// Term's characters were found in order, but not next to each other. Weighed by how
+    /// good the fuzzy match is.
+    Fuzzy,
+    /// Term was found inside a word.
+    Substring,
+    /// Term was found at the start of a word.
+    Prefix,
+    /// Term is the whole field (or a whole tag).
+    Exact,
+}
+
+impl MatchKind {
+    fn weight(self) -> f64 {
+        match self {
+            MatchKind::Fuzzy => 0.25,
+            MatchKind::Substring => 0.5,
+            MatchKind::Prefix => 0.75,
+            MatchKind::Exact => 1.0,
+        }
+    }
+}
+
+/// Fields of a pin that can be searched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Title,
+    Tags,
+    Url,
+    Description,
+}
+
+impl Field {
+    fn weight(self) -> f64 {
+        match self {
+            Field::Title => 4.0,
+            Field::Tags => 3.0,
+            Field::Url => 2.0,
+            Field::Description => 1.0,
+        }
+    }
+
+    /// Expands the selected search types into a list of distinct fields.
+    /// No selection means all fields.
+    pub fn from_search_types(fields: &[SearchType]) -> Vec<Field> {
+        if fields.is_empty() {
+            return vec![Field::Title, Field::Tags, Field::Url, Field::Description];
+        }
+        let mut v = Vec::with_capacity(4);
+        for search_type in fields {
+            let expanded: &[Field] = match *search_type {
+                SearchType::TitleOnly => &[Field::Title],
+                SearchType::TagOnly => &[Field::Tags],
+                SearchType::UrlOnly => &[Field::Url],
+                SearchType::DescriptionOnly => &[Field::Description],
+                SearchType::TagTitleOnly => &[Field::Title, Field::Tags],
+            };
+            for f in expanded {
+                if !v.contains(f) {
+                    v.push(*f);
+                }
+            }
+        }
+        v
+    }
+}
+
+/// A single query term, normalized.
+#[derive(Debug)]
+pub struct Term {
+    text: String,
+    fuzzy: Option<FuzzyMatcher>,
+    strip_diacritics: bool,
+}
+
+impl Term {
+    /// Creates a term normalized the same way as the cached fields it is looked up in.
+    pub fn new(text: &str, fuzzy: bool, strip_diacritics: bool) -> Self {
+        let text = normalize(text, strip_diacritics);
+        let fuzzy = if fuzzy {
+            Some(FuzzyMatcher::new(&text))
+        } else {
+            None
+        };
+        Term {
+            text,
+            fuzzy,
+            strip_diacritics,
+        }
+    }
+
+    /// Finds the best way this term matches `text` (already normalized), returning
+    /// how it matched, the byte offset of the match and its quality (between 0 and 1,
+    /// always 1 unless fuzzy).
+    fn find_in(&self, text: &str) -> Option<(MatchKind, usize, f64)> {
+        if text == self.text {
+            return Some((MatchKind::Exact, 0, 1.0));
+        }
+        let mut first = None;
+        for (pos, _) in text.match_indices(self.text.as_str()) {
+            if is_word_start(text, pos) {
+                return Some((MatchKind::Prefix, pos, 1.0));
+            }
+            first = first.or(Some(pos));
+        }
+        if let Some(pos) = first {
+            return Some((MatchKind::Substring, pos, 1.0));
+        }
+        self.fuzzy.as_ref().and_then(|f| f.find(text)).map(|m| {
+            let pos = m.positions.first().cloned().unwrap_or(0);
+            (MatchKind::Fuzzy, pos, m.quality())
+        })
+    }
+
+    /// Score of the best hit of this term in `text` (already normalized), regardless of
+    /// the field it comes from.
+    pub fn score(&self, text: &str) -> Option<f64> {
+        self.find_in(text)
+            .map(|(kind, pos, quality)| kind.weight() * quality * position_factor(pos, text.len()))
+    }
+
+    /// Score of the best hit of this term in `field`, if any.
+    fn score_field(&self, cached_pin: &CachedPin, field: Field) -> Option<f64> {
+        let score = |text: &str| self.score(text).map(|s| field.weight() * s);
+        match field {
+            Field::Title => score(&cached_pin.title_lowered),
+            Field::Tags => cached_pin
+                .tag_list
+                .iter()
+                .filter_map(|t| score(t))
+                .fold(None, max_score),
+            Field::Url => score(&cached_pin.url_lowered),
+            Field::Description => cached_pin.extended_lowered.as_ref().and_then(|e| score(e)),
+        }
+    }
+
+    /// Byte ranges of `text` (already normalized) matched by this term: every occurrence of
+    /// the term or, if there is none, each character of its best fuzzy match.
+    fn ranges_in(&self, text: &str) -> Vec<Range<usize>> {
+        if self.text.is_empty() {
+            return Vec::new();
+        }
+        let ranges = text
+            .match_indices(self.text.as_str())
+            .map(|(pos, m)| pos..pos + m.len())
+            .collect::<Vec<Range<usize>>>();
+        if !ranges.is_empty() {
+            return ranges;
+        }
+        self.fuzzy
+            .as_ref()
+            .and_then(|f| f.find(text))
+            .map(|m| {
+                m.positions
+                    .iter()
+                    .map(|&pos| pos..pos + text[pos..].chars().next().map_or(0, char::len_utf8))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Normalized copy of a text that remembers which original chars each of its chars came from.
+/// Needed because normalizing may change the length of a char, in bytes or even in chars,
+/// and merges diacritics with the char they apply to.
+struct Lowered {
+    text: String,
+    /// (byte offset in `text`, range of the original chars) for each char of `text`.
+    origin: Vec<(usize, Range<usize>)>,
+}
+
+impl Lowered {
+    /// Normalizes `original` one cluster at a time, a cluster being a char along with the
+    /// diacritics following it.
+    fn new(original: &str, strip_diacritics: bool) -> Self {
+        let chars = original.char_indices().collect::<Vec<(usize, char)>>();
+        let mut text = String::with_capacity(original.len());
+        let mut origin = Vec::with_capacity(chars.len());
+        let mut start = 0;
+        while start < chars.len() {
+            let mut end = start + 1;
+            while end < chars.len() && is_combining(chars[end].1) {
+                end += 1;
+            }
+            let to = chars.get(end).map_or(original.len(), |&(b, _)| b);
+            for l in normalize(&original[chars[start].0..to], strip_diacritics).chars() {
+                origin.push((text.len(), start..end));
+                text.push(l);
+            }
+            start = end;
+        }
+        Lowered { text, origin }
+    }
+
+    /// Maps a non-empty byte range of the normalized text to a char range of the original.
+    fn original_chars(&self, r: &Range<usize>) -> Range<usize> {
+        let first = self.origin.partition_point(|&(b, _)| b < r.start);
+        let last = self.origin.partition_point(|&(b, _)| b < r.end) - 1;
+        self.origin[first].1.start..self.origin[last].1.end
+    }
+}
+
+/// Whitespace separated words of `text` along with the char offset each one starts at.
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (ci, (bi, c)) in text.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (true, Some((sc, sb))) => {
+                words.push((sc, &text[sb..bi]));
+                start = None;
+            }
+            (false, None) => start = Some((ci, bi)),
+            _ => (),
+        }
+    }
+    if let Some((sc, sb)) = start {
+        words.push((sc, &text[sb..]));
+    }
+    words
+}
+
+/// Sorts `ranges` and merges the overlapping or touching ones.
+fn merge(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|r| r.start);
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+    for r in ranges {
+        match merged.last_mut() {
+            Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+            _ => merged.push(r),
+        }
+    }
+    merged
+}
+
+/// Finds the spans of `pin` matched by any of `terms` within `fields`.
+pub fn highlight(pin: &Pin, terms: &[Term], fields: &[Field]) -> Vec<FieldMatch> {
+    fields
+        .iter()
+        .filter_map(|&field| {
+            let text: &str = match field {
+                Field::Title => &pin.title,
+                Field::Url => &pin.url,
+                Field::Tags => &pin.tags,
+                Field::Description => pin.extended.as_ref()?,
+            };
+            // Tags are matched one by one, just like when scoring.
+            let segments = match field {
+                Field::Tags => words(text),
+                _ => vec![(0, text)],
+            };
+            let strip_diacritics = terms.first().map_or(false, |t| t.strip_diacritics);
+            let mut char_ranges = Vec::new();
+            for (offset, segment) in segments {
+                let lowered = Lowered::new(segment, strip_diacritics);
+                for term in terms {
+                    char_ranges.extend(term.ranges_in(&lowered.text).iter().map(|r| {
+                        let c = lowered.original_chars(r);
+                        c.start + offset..c.end + offset
+                    }));
+                }
+            }
+            if char_ranges.is_empty() {
+                return None;
+            }
+            let char_ranges = merge(char_ranges);
+            let offsets = text
+                .char_indices()
+                .map(|(b, _)| b)
+                .chain(Some(text.len()))
+                .collect::<Vec<usize>>();
+            let byte_ranges = char_ranges
+                .iter()
+                .map(|r| offsets[r.start]..offsets[r.end])
+                .collect();
+            Some(FieldMatch {
+                field,
+                byte_ranges,
+                char_ranges,
+            })
+        })
+        .collect()
+}
+
+fn max_score(acc: Option<f64>, s: f64) -> Option<f64> {
+    Some(acc.map_or(s, |a| a.max(s)))
+}
+
+fn is_word_start(text: &str, pos: usize) -> bool {
+    text[..pos]
+        .chars()
+        .next_back()
+        .map_or(true, |c| !c.is_alphanumeric())
+}
+
+/// Matches near the start of a field score up to twice as much as those at its end.
+fn position_factor(pos: usize, len: usize) -> f64 {
+    if len == 0 {
+        1.0
+    } else {
+        1.0 - 0.5 * (pos as f64 / len as f64)
+    }
+}
+
+fn recency_factor(time: DateTime<Utc>, now: DateTime<Utc>) -> f64 {
+    let age_years = (now - time).num_days().max(0) as f64 / 365.0;
+    1.0 + RECENCY_BOOST * 0.5f64.powf(age_years)
+}
+
+/// Scores `cached_pin` against all `terms` within `fields`.
+/// Returns `None` if any of the terms doesn't match any of the fields.
+pub fn score_pin(
+    cached_pin: &CachedPin,
+    terms: &[Term],
+    fields: &[Field],
+    now: DateTime<Utc>,
+) -> Option<f64> {
+    let mut total = 0.0;
+    for term in terms {
+        let mut hits = fields
+            .iter()
+            .filter_map(|f| term.score_field(cached_pin, *f))
+            .peekable();
+        hits.peek()?;
+        total += hits.sum::<f64>();
+    }
+    Some(total * recency_factor(cached_pin.pin.time, now))
+}
+
+/// Scores all pins and returns the matching ones sorted by descending score.
+/// Pins with equal scores keep their original order.
+pub fn rank<'a, 'pin: 'a, I>(
+    pins: I,
+    terms: &[Term],
+    fields: &[Field],
+) -> Vec<SearchResult<'a, 'pin>>
+where
+    I: IntoIterator<Item = &'a CachedPin<'pin>>,
+{
+    let now = Utc::now();
+    let mut results = pins
+        .into_iter()
+        .filter_map(|cached_pin| {
+            score_pin(cached_pin, terms, fields, now).map(|score| SearchResult {
+                pin: &cached_pin.pin,
+                score,
+                matches: highlight(&cached_pin.pin, terms, fields),
+            })
+        })
+        .collect::<Vec<SearchResult>>();
+    results.sort_by(|r1, r2| r2.score.partial_cmp(&r1.score).expect("score is never NaN"));
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use env_logger;
+
+    fn cached_pin(url: &str, title: &str, tags: &str, desc: &str) -> CachedPin<'static> {
+        let mut pin = PinBuilder::new(url.to_string(), title.to_string())
+            .tags(tags.to_string())
+            .description(desc.to_string())
+            .into_pin();
+        pin.time = Utc::now();
+        CachedPin::new(pin)
+    }
+
+    fn terms(q: &[&str], fuzzy: bool) -> Vec<Term> {
+        q.iter().map(|t| Term::new(t, fuzzy, false)).collect()
+    }
+
+    #[test]
+    fn match_kinds() {
+        let _ = env_logger::try_init();
+        debug!("match_kinds: starting");
+        let t = Term::new("Rust", true, false);
+        assert_eq!(Some((MatchKind::Exact, 0, 1.0)), t.find_in("rust"));
+        assert_eq!(Some((MatchKind::Prefix, 6, 1.0)), t.find_in("trust rust"));
+        assert_eq!(
+            Some((MatchKind::Prefix, 9, 1.0)),
+            t.find_in("learning-rustlang")
+        );
+        assert_eq!(Some((MatchKind::Substring, 1, 1.0)), t.find_in("trust"));
+        let (kind, pos, quality) = t.find_in("r u s t").unwrap();
+        assert_eq!((MatchKind::Fuzzy, 0), (kind, pos));
+        assert!(quality > 0.0 && quality < 1.0);
+        assert_eq!(None, t.find_in("tsur"));
+        assert_eq!(None, Term::new("rust", false, false).find_in("r u s t"));
+
+        // Regex meta characters are matched literally
+        let t = Term::new("c++", true, false);
+        assert_eq!(Some((MatchKind::Exact, 0, 1.0)), t.find_in("c++"));
+        assert_eq!(None, t.find_in("ccc"));
+        assert_eq!(
+            Some((MatchKind::Fuzzy, 2)),
+            t.find_in("c/c+ +").map(|h| (h.0, h.1))
+        );
+    }
+
+    #[test]
+    fn field_order() {
+        let _ = env_logger::try_init();
+        debug!("field_order: starting");
+        let pins = vec![
+            cached_pin("http://a.com/", "Other", "x", "all about rust"),
+            cached_pin("http://rust.com/", "Other", "x", ""),
+            cached_pin("http://a.com/", "Other", "rust", ""),
+            cached_pin("http://a.com/", "Rust", "x", ""),
+        ];
+        let fields = Field::from_search_types(&[]);
+        let results = rank(&pins, &terms(&["rust"], false), &fields);
+        assert_eq!(4, results.len());
+        assert_eq!("Rust", results[0].pin.title);
+        assert_eq!("rust", results[1].pin.tags);
+        assert_eq!("http://rust.com/", results[2].pin.url);
+        assert_eq!(Some("all about rust"), results[3].pin.extended.as_deref());
+        assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+
+    #[test]
+    fn exact_title_beats_fuzzy_description() {
+        let _ = env_logger::try_init();
+        debug!("exact_title_beats_fuzzy_description: starting");
+        let pins = vec![
+            cached_pin("http://a.com/", "Notes", "", "h a m i d was here"),
+            cached_pin("http://a.com/", "Where is hamid", "", ""),
+            cached_pin("http://a.com/", "Hamid", "", ""),
+            cached_pin("http://a.com/", "Shamids", "", ""),
+        ];
+        let fields = Field::from_search_types(&[]);
+        let results = rank(&pins, &terms(&["hamid"], true), &fields);
+        let titles = results
+            .iter()
+            .map(|r| r.pin.title.as_ref())
+            .collect::<Vec<&str>>();
+        assert_eq!(vec!["Hamid", "Where is hamid", "Shamids", "Notes"], titles);
+    }
+
+    #[test]
+    fn all_terms_must_match() {
+        let _ = env_logger::try_init();
+        debug!("all_terms_must_match: starting");
+        let pins = vec![
+            cached_pin("http://a.com/", "Rust book", "", ""),
+            cached_pin("http://a.com/", "Rust", "book", ""),
+            cached_pin("http://a.com/", "Rust", "", ""),
+        ];
+        let results = rank(
+            &pins,
+            &terms(&["rust", "book"], false),
+            &Field::from_search_types(&[SearchType::TitleOnly]),
+        );
+        assert_eq!(1, results.len());
+        assert_eq!("Rust book", results[0].pin.title);
+
+        let results = rank(
+            &pins,
+            &terms(&["rust", "book"], false),
+            &Field::from_search_types(&[SearchType::TagTitleOnly]),
+        );
+        assert_eq!(2, results.len());
+    }
+
+    #[test]
+    fn newer_pins_rank_higher() {
+        let _ = env_logger::try_init();
+        debug!("newer_pins_rank_higher: starting");
+        let mut old = cached_pin("http://a.com/old", "Rust", "", "");
+        old.pin.time = Utc::now() - chrono::Duration::days(3 * 365);
+        let new = cached_pin("http://a.com/new", "Rust", "", "");
+        let pins = vec![old, new];
+        let results = rank(&pins, &terms(&["rust"], false), &[Field::Title]);
+        assert_eq!("http://a.com/new", results[0].pin.url);
+        assert!(results[0].score > results[1].score);
+    }
+
+    #[test]
+    fn highlight_spans() {
+        let _ = env_logger::try_init();
+        debug!("highlight_spans: starting");
+        let pin = cached_pin(
+            "https://githuуй.com/Здравствуйт?q=13#fragment",
+            "Rust and trust",
+            "rust programming",
+            "HAMID was here",
+        )
+        .pin;
+        let fields = Field::from_search_types(&[]);
+
+        let m = highlight(&pin, &terms(&["rust"], false), &fields);
+        assert_eq!(2, m.len());
+        assert_eq!(Field::Title, m[0].field);
+        assert_eq!(vec![0..4, 10..14], m[0].byte_ranges);
+        assert_eq!(m[0].byte_ranges, m[0].char_ranges);
+        assert_eq!(Field::Tags, m[1].field);
+        assert_eq!(vec![0..4], m[1].char_ranges);
+
+        // Non-ASCII: byte and char ranges differ
+        let m = highlight(&pin, &terms(&["здрав"], false), &[Field::Url]);
+        assert_eq!(1, m.len());
+        assert_eq!(vec![20..25], m[0].char_ranges);
+        let r = m[0].byte_ranges[0].clone();
+        assert_eq!(22..32, r);
+        assert_eq!("Здрав", &pin.url[r]);
+
+        // Fuzzy matches report each matched character
+        let m = highlight(&pin, &terms(&["hmd"], true), &[Field::Description]);
+        assert_eq!(vec![0..1, 2..3, 4..5], m[0].char_ranges);
+        let m = highlight(&pin, &terms(&["йзвт"], true), &[Field::Url]);
+        assert_eq!(vec![14..15, 20..21, 24..25, 26..27], m[0].char_ranges);
+        assert_eq!("й", &pin.url[m[0].byte_ranges[0].clone()]);
+        assert_eq!("З", &pin.url[m[0].byte_ranges[1].clone()]);
+
+        // A fuzzy match can't span several tags
+        assert!(highlight(&pin, &terms(&["tp"], true), &[Field::Tags]).is_empty());
+        let m = highlight(&pin, &terms(&["pgm"], true), &[Field::Tags]);
+        assert_eq!(vec![5..6, 8..9, 11..12], m[0].char_ranges);
+    }
+
+    #[test]
+    fn highlight_changing_lengths() {
+        let _ = env_logger::try_init();
+        debug!("highlight_changing_lengths: starting");
+        // 'İ' lower cases to two chars and the Kelvin sign to a one byte 'k'.
+        let pin = cached_pin("http://a.com/", "İstanbul \u{212A}elvin", "", "").pin;
+        let m = highlight(&pin, &terms(&["stan", "kel"], false), &[Field::Title]);
+        assert_eq!(vec![1..5, 9..12], m[0].char_ranges);
+        assert_eq!("stan", &pin.title[m[0].byte_ranges[0].clone()]);
+        assert_eq!("\u{212A}el", &pin.title[m[0].byte_ranges[1].clone()]);
+    }
+
+    #[test]
+    fn diacritics() {
+        let _ = env_logger::try_init();
+        debug!("diacritics: starting");
+        let strip = |q: &[&str]| {
+            q.iter()
+                .map(|t| Term::new(t, false, true))
+                .collect::<Vec<Term>>()
+        };
+        let mut pin = cached_pin("http://a.com/Café", "Café Crème", "Ärger", "");
+        let fields = Field::from_search_types(&[]);
+        let now = Utc::now();
+        assert!(score_pin(&pin, &terms(&["cafe"], false), &fields, now).is_none());
+        assert!(score_pin(&pin, &terms(&["CAFÉ"], false), &fields, now).is_some());
+        // Cached fields must be normalized the same way as the terms
+        pin.normalize(true);
+        assert!(score_pin(&pin, &strip(&["cafe", "arger"]), &fields, now).is_some());
+        assert!(score_pin(&pin, &strip(&["Ｃａｆé"]), &[Field::Url], now).is_some());
+
+        let m = highlight(&pin.pin, &strip(&["cafe", "creme"]), &[Field::Title]);
+        assert_eq!(vec![0..4, 5..10], m[0].char_ranges);
+        // A decomposed 'é' is highlighted along with its accent
+        let pin = cached_pin("http://a.com/", "Cafe\u{301}!", "", "").pin;
+        let m = highlight(&pin, &strip(&["cafe"]), &[Field::Title]);
+        assert_eq!(vec![0..5], m[0].char_ranges);
+        assert_eq!("Cafe\u{301}", &pin.title[m[0].byte_ranges[0].clone()]);
+    }
+}
@@ -0,0 +1,281 @@
+//! Usage statistics of tags, computed from the cached bookmarks.
+//!
+//! Statistics are serializable so that they can be handed over as is to whatever charts them.
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::prelude::*;
+
+use super::cached_data::CachedPin;
+use super::suggest::host_of;
+
+/// Something and how many bookmarks it is found on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NamedCount {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Number of bookmarks created in a month.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonthlyCount {
+    pub year: i32,
+    /// From 1 to 12.
+    pub month: u32,
+    pub count: usize,
+}
+
+/// How a tag is used.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TagStats {
+    pub tag: String,
+    /// Number of bookmarks using the tag.
+    pub count: usize,
+    /// Creation time of the oldest bookmark using the tag.
+    pub first_used: DateTime<Utc>,
+    /// Creation time of the newest bookmark using the tag.
+    pub last_used: DateTime<Utc>,
+    /// Bookmarks using the tag per month, from the first use to the last one, including the
+    /// months in between without any.
+    pub monthly: Vec<MonthlyCount>,
+    /// Share of the bookmarks using the tag that are still to read, from 0 to 1.
+    pub unread_ratio: f64,
+    /// Tags most often used along with the tag, most frequent first.
+    pub related_tags: Vec<NamedCount>,
+    /// Hosts of the bookmarks using the tag, most frequent first, without `www.`.
+    pub domains: Vec<NamedCount>,
+}
+
+#[derive(Debug)]
+struct Usage {
+    count: usize,
+    unread: usize,
+    first_used: DateTime<Utc>,
+    last_used: DateTime<Utc>,
+    months: BTreeMap<(i32, u32), usize>,
+    related: HashMap<String, usize>,
+    domains: HashMap<String, usize>,
+}
+
+impl Usage {
+    fn new(time: DateTime<Utc>) -> Self {
+        Usage {
+            count: 0,
+            unread: 0,
+            first_used: time,
+            last_used: time,
+            months: BTreeMap::new(),
+            related: HashMap::new(),
+            domains: HashMap::new(),
+        }
+    }
+}
+
+/// The `top` most frequent of `counts`, spelled as in `names`.
+fn top_counts(
+    counts: HashMap<String, usize>,
+    names: &HashMap<&str, &str>,
+    top: usize,
+) -> Vec<NamedCount> {
+    let mut counts = counts
+        .into_iter()
+        .map(|(key, count)| NamedCount {
+            name: names.get(key.as_str()).map_or(key.clone(), |n| n.to_string()),
+            count,
+        })
+        .collect::<Vec<NamedCount>>();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    counts.truncate(top);
+    counts
+}
+
+/// Every month from `first` to `last`, with the number of bookmarks created in it.
+fn timeline(
+    first: (i32, u32),
+    last: (i32, u32),
+    months: &BTreeMap<(i32, u32), usize>,
+) -> Vec<MonthlyCount> {
+    let mut timeline = Vec::new();
+    let (mut year, mut month) = first;
+    while (year, month) <= last {
+        timeline.push(MonthlyCount {
+            year,
+            month,
+            count: months.get(&(year, month)).cloned().unwrap_or(0),
+        });
+        if month == 12 {
+            year += 1;
+            month = 1;
+        } else {
+            month += 1;
+        }
+    }
+    timeline
+}
+
+/// Statistics of the tags of `pins` selected by `wanted`, given their normalized form, most
+/// used first. Related tags and domains are limited to the `top` most frequent.
+pub fn compute<F>(pins: &[CachedPin], wanted: F, top: usize) -> Vec<TagStats>
+where
+    F: Fn(&str) -> bool,
+{
+    // Tags as spelled on the first bookmark using them.
+    let mut names: HashMap<&str, &str> = HashMap::new();
+    let mut usages: HashMap<&str, Usage> = HashMap::new();
+    for cached_pin in pins {
+        let pin = &cached_pin.pin;
+        for (key, name) in cached_pin.tag_list.iter().zip(pin.tags.split_whitespace()) {
+            names.entry(key.as_str()).or_insert(name);
+        }
+        // A tag repeated in another case counts once.
+        let mut keys: Vec<&String> = Vec::new();
+        for key in &cached_pin.tag_list {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        let host = host_of(&pin.url);
+        for &key in keys.iter().filter(|k| wanted(k)) {
+            let usage = usages.entry(key.as_str()).or_insert_with(|| Usage::new(pin.time));
+            usage.count += 1;
+            if pin.toread == "yes" {
+                usage.unread += 1;
+            }
+            usage.first_used = usage.first_used.min(pin.time);
+            usage.last_used = usage.last_used.max(pin.time);
+            *usage
+                .months
+                .entry((pin.time.year(), pin.time.month()))
+                .or_insert(0) += 1;
+            for other in keys.iter().filter(|o| **o != key) {
+                *usage.related.entry(other.to_string()).or_insert(0) += 1;
+            }
+            if let Some(ref host) = host {
+                *usage.domains.entry(host.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut stats = usages
+        .into_iter()
+        .map(|(key, usage)| {
+            let first = (usage.first_used.year(), usage.first_used.month());
+            let last = (usage.last_used.year(), usage.last_used.month());
+            TagStats {
+                tag: names.get(key).unwrap_or(&key).to_string(),
+                count: usage.count,
+                first_used: usage.first_used,
+                last_used: usage.last_used,
+                monthly: timeline(first, last, &usage.months),
+                unread_ratio: usage.unread as f64 / usage.count as f64,
+                related_tags: top_counts(usage.related, &names, top),
+                domains: top_counts(usage.domains, &HashMap::new(), top),
+            }
+        })
+        .collect::<Vec<TagStats>>();
+    stats.sort_by_key(|s| (Reverse(s.count), s.tag.clone()));
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use env_logger;
+
+    fn pins() -> Vec<CachedPin<'static>> {
+        [
+            ("https://www.rust-lang.org/", "Rust async", "no", (2020, 3, 5)),
+            ("https://github.com/tokio-rs/tokio", "rust tokio", "yes", (2020, 1, 20)),
+            ("https://github.com/rust-lang/rust", "rust", "no", (2019, 12, 1)),
+            ("https://golang.org/", "go", "yes", (2019, 11, 2)),
+        ]
+        .iter()
+        .map(|&(url, tags, toread, (y, m, d))| {
+            let mut pin = PinBuilder::new(url, "title").tags(tags).toread(toread).into_pin();
+            pin.time = Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap();
+            CachedPin::new(pin)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn computes_tag_stats() {
+        let _ = env_logger::try_init();
+        debug!("computes_tag_stats: starting");
+        let pins = pins();
+        let stats = compute(&pins, |_| true, 10);
+        assert_eq!(
+            vec!["Rust", "async", "go", "tokio"],
+            stats.iter().map(|s| s.tag.as_str()).collect::<Vec<&str>>()
+        );
+
+        let rust = &stats[0];
+        assert_eq!(3, rust.count);
+        assert_eq!(Utc.with_ymd_and_hms(2019, 12, 1, 0, 0, 0).unwrap(), rust.first_used);
+        assert_eq!(Utc.with_ymd_and_hms(2020, 3, 5, 0, 0, 0).unwrap(), rust.last_used);
+        assert_eq!(
+            vec![(2019, 12, 1), (2020, 1, 1), (2020, 2, 0), (2020, 3, 1)],
+            rust.monthly
+                .iter()
+                .map(|m| (m.year, m.month, m.count))
+                .collect::<Vec<(i32, u32, usize)>>()
+        );
+        assert!((rust.unread_ratio - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            vec![
+                NamedCount { name: "async".into(), count: 1 },
+                NamedCount { name: "tokio".into(), count: 1 },
+            ],
+            rust.related_tags
+        );
+        assert_eq!(
+            vec![
+                NamedCount { name: "github.com".into(), count: 2 },
+                NamedCount { name: "rust-lang.org".into(), count: 1 },
+            ],
+            rust.domains
+        );
+        assert_eq!(1.0, stats[2].unread_ratio);
+    }
+
+    #[test]
+    fn limits_and_selects() {
+        let _ = env_logger::try_init();
+        debug!("limits_and_selects: starting");
+        let pins = pins();
+        let stats = compute(&pins, |t| t == "rust", 1);
+        assert_eq!(1, stats.len());
+        assert_eq!(1, stats[0].related_tags.len());
+        assert_eq!(1, stats[0].domains.len());
+        assert!(compute(&pins, |t| t == "missing", 1).is_empty());
+        assert!(compute(&[], |_| true, 1).is_empty());
+    }
+
+    #[test]
+    fn counts_repeated_tags_once() {
+        let _ = env_logger::try_init();
+        debug!("counts_repeated_tags_once: starting");
+        // Tags differing by case, as saved before tags were deduplicated.
+        let mut pin = PinBuilder::new("https://www.rust-lang.org/", "title").into_pin();
+        pin.tags = "Rust rust async".into();
+        pin.toread = "yes".into();
+        let pins = vec![CachedPin::new(pin)];
+        let stats = compute(&pins, |_| true, 10);
+        assert_eq!(2, stats.len());
+        let rust = &stats[0];
+        assert_eq!("Rust", rust.tag);
+        assert_eq!(1, rust.count);
+        assert_eq!(1, rust.monthly[0].count);
+        assert_eq!(1.0, rust.unread_ratio);
+        assert_eq!(vec![NamedCount { name: "async".into(), count: 1 }], rust.related_tags);
+        assert_eq!(
+            vec![NamedCount { name: "rust-lang.org".into(), count: 1 }],
+            rust.domains
+        );
+        assert_eq!(
+            vec![NamedCount { name: "Rust".into(), count: 1 }],
+            stats[1].related_tags
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Offline tag suggestions learned from the user's own bookmarks.
+//!
+//! A bookmark about to be saved gets its tags suggested from
+//!
+//! - tags spelled out in its title, e.g. `rust` or `timemachine` for "Time Machine in Rust"
+//! - tags used on other bookmarks of the same site
+//! - tags often used together with the two above
+//!
+//! Tags suggested by the server can be added to the mix; those the user never used are marked
+//! with [`TagFreq::New`].
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use url::Url;
+
+use super::cached_data::CachedPin;
+use super::normalize::normalize;
+use super::tag::{Tag, TagFreq};
+
+/// Weight of a tag found in the title.
+const TITLE_WEIGHT: f64 = 1.0;
+/// Weight of a tag used on every bookmark of the same site.
+const HOST_WEIGHT: f64 = 1.0;
+/// Weight of a tag always used along with a suggested one.
+const COOCCURRENCE_WEIGHT: f64 = 0.5;
+/// Weight of a tag suggested by the server.
+const SERVER_WEIGHT: f64 = 0.5;
+
+/// Tag usage statistics of a set of bookmarks, keyed by normalized tag.
+#[derive(Debug, Default)]
+pub struct TagSuggester {
+    /// Tag as the user spells it and the number of bookmarks using it.
+    tags: HashMap<String, (String, usize)>,
+    /// Number of bookmarks of each host, and of them using each tag.
+    hosts: HashMap<String, (usize, HashMap<String, usize>)>,
+    /// Number of bookmarks using both tags.
+    cooccurrences: HashMap<String, HashMap<String, usize>>,
+    /// Whether diacritics are stripped from the keys, as from the pins' `tag_list`.
+    strip_diacritics: bool,
+}
+
+/// Host of `url` in lowercase, without `www.`.
+pub fn host_of(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    Some(host.trim_start_matches("www.").to_string())
+}
+
+impl TagSuggester {
+    /// Learns from `pins`, whose normalized tags had diacritics stripped if
+    /// `strip_diacritics` is true.
+    pub fn new(pins: &[CachedPin], strip_diacritics: bool) -> Self {
+        let mut suggester = TagSuggester {
+            strip_diacritics,
+            ..TagSuggester::default()
+        };
+        for cached_pin in pins {
+            let mut keys: Vec<&String> = Vec::new();
+            let tags = cached_pin.pin.tags.split_whitespace();
+            for (key, tag) in cached_pin.tag_list.iter().zip(tags) {
+                if keys.contains(&key) {
+                    continue;
+                }
+                suggester
+                    .tags
+                    .entry(key.clone())
+                    .or_insert_with(|| (tag.to_string(), 0))
+                    .1 += 1;
+                keys.push(key);
+            }
+            if let Some(host) = host_of(&cached_pin.pin.url) {
+                let (count, tags) = suggester.hosts.entry(host).or_default();
+                *count += 1;
+                for key in &keys {
+                    *tags.entry(key.to_string()).or_insert(0) += 1;
+                }
+            }
+            for a in &keys {
+                let row = suggester.cooccurrences.entry(a.to_string()).or_default();
+                for b in keys.iter().filter(|b| *b != a) {
+                    *row.entry(b.to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+        suggester
+    }
+
+    /// Up to `limit` tags for a bookmark of `url` titled `title`, best first.
+    pub fn suggest(&self, url: &str, title: &str, limit: usize) -> Vec<Tag> {
+        self.suggest_with(url, title, &[], limit)
+    }
+
+    /// Same as [`suggest`](#method.suggest) but `server_tags` (e.g. from `posts/suggest`)
+    /// count too, and are suggested even if the user never used them.
+    pub fn suggest_with<S: AsRef<str>>(
+        &self,
+        url: &str,
+        title: &str,
+        server_tags: &[S],
+        limit: usize,
+    ) -> Vec<Tag> {
+        let mut scores: HashMap<String, f64> = HashMap::new();
+
+        // Tags spelled out in the title, as one word or two.
+        let title = normalize(title, self.strip_diacritics);
+        let words = title
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .collect::<Vec<&str>>();
+        let pairs = words.windows(2).flat_map(|w| vec![w.concat(), w.join("-")]);
+        for key in words.iter().map(|w| w.to_string()).chain(pairs) {
+            if self.tags.contains_key(&key) {
+                scores.insert(key, TITLE_WEIGHT);
+            }
+        }
+
+        if let Some(&(count, ref tags)) = host_of(url).and_then(|h| self.hosts.get(&h)) {
+            for (key, n) in tags {
+                *scores.entry(key.clone()).or_insert(0.0) += HOST_WEIGHT * *n as f64 / count as f64;
+            }
+        }
+
+        let mut new_tags: HashMap<String, String> = HashMap::new();
+        for tag in server_tags.iter().map(AsRef::as_ref) {
+            let key = normalize(tag, self.strip_diacritics);
+            if key.is_empty() {
+                continue;
+            }
+            if !self.tags.contains_key(&key) {
+                new_tags.insert(key.clone(), tag.to_string());
+            }
+            *scores.entry(key).or_insert(0.0) += SERVER_WEIGHT;
+        }
+
+        let mut extra: HashMap<String, f64> = HashMap::new();
+        for (key, score) in &scores {
+            // Tags new to the user don't co-occur with anything.
+            let count = match self.tags.get(key) {
+                Some(&(_, count)) => count as f64,
+                None => continue,
+            };
+            for (other, n) in self.cooccurrences.get(key).into_iter().flatten() {
+                *extra.entry(other.clone()).or_insert(0.0) +=
+                    score * COOCCURRENCE_WEIGHT * *n as f64 / count;
+            }
+        }
+        for (key, score) in extra {
+            *scores.entry(key).or_insert(0.0) += score;
+        }
+
+        let mut ranked = scores
+            .into_iter()
+            .map(|(key, score)| {
+                let tag = match self.tags.get(&key) {
+                    Some(&(ref name, count)) => Tag::new(name.clone(), count),
+                    None => Tag(new_tags[&key].clone(), TagFreq::New),
+                };
+                (score, tag)
+            })
+            .collect::<Vec<(f64, Tag)>>();
+        // On ties the user's own tags come first.
+        let own = |tag: &Tag| match tag.1 {
+            TagFreq::Used(_) => true,
+            _ => false,
+        };
+        ranked.sort_by(|a, b| {
+            b.0.partial_cmp(&a.0)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| own(&b.1).cmp(&own(&a.1)))
+                .then_with(|| b.1.cmp(&a.1))
+        });
+        ranked.into_iter().take(limit).map(|(_, tag)| tag).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use env_logger;
+
+    fn suggester() -> TagSuggester {
+        let pins = [
+            ("https://www.rust-lang.org/", "Rust", "rust programming"),
+            ("https://blog.rust-lang.org/a", "Async", "rust async"),
+            (
+                "https://github.com/tokio-rs/tokio",
+                "Tokio",
+                "rust async tokio",
+            ),
+            ("https://github.com/ruby/ruby", "Ruby", "ruby programming"),
+            ("https://github.com/torvalds/linux", "Linux", "linux C"),
+            (
+                "http://blog.khubla.com/x",
+                "Backups",
+                "timemachine freebsd zfs",
+            ),
+        ]
+        .iter()
+        .map(|&(url, title, tags)| {
+            CachedPin::new(
+                PinBuilder::new(url.to_string(), title.to_string())
+                    .tags(tags.to_string())
+                    .into_pin(),
+            )
+        })
+        .collect::<Vec<CachedPin>>();
+        TagSuggester::new(&pins, false)
+    }
+
+    fn names(tags: &[Tag]) -> Vec<&str> {
+        tags.iter().map(|t| t.0.as_str()).collect()
+    }
+
+    #[test]
+    fn suggests_from_title_and_cooccurrence() {
+        let _ = env_logger::try_init();
+        debug!("suggests_from_title_and_cooccurrence: starting");
+        let s = suggester();
+        let tags = s.suggest("https://example.com/", "Learning RUST the hard way", 3);
+        assert_eq!(vec!["rust", "async", "programming"], names(&tags));
+        assert_eq!(TagFreq::Used(3), tags[0].1);
+
+        let tags = s.suggest("https://example.com/", "Time Machine backups", 10);
+        assert_eq!("timemachine", tags[0].0);
+        assert!(names(&tags).contains(&"zfs"));
+        assert!(s
+            .suggest("https://example.com/", "Nothing known", 10)
+            .is_empty());
+    }
+
+    #[test]
+    fn suggests_from_host() {
+        let _ = env_logger::try_init();
+        debug!("suggests_from_host: starting");
+        let s = suggester();
+        let tags = s.suggest("https://rust-lang.org/learn", "Learn", 1);
+        assert_eq!(vec!["rust"], names(&tags));
+        let tags = s.suggest("https://github.com/x/y", "", 10);
+        // Used on most github bookmarks
+        assert!(names(&tags[..3]).contains(&"rust"));
+        assert!(names(&tags).contains(&"C"));
+    }
+
+    #[test]
+    fn merges_server_tags() {
+        let _ = env_logger::try_init();
+        debug!("merges_server_tags: starting");
+        let s = suggester();
+        let tags = s.suggest_with("https://docs.rs/x", "", &["Datetime", "RUBY"], 10);
+        assert_eq!(vec!["ruby", "Datetime", "programming"], names(&tags));
+        assert_eq!(TagFreq::Used(1), tags[0].1);
+        assert_eq!(TagFreq::New, tags[1].1);
+    }
+
+    #[test]
+    fn strips_diacritics() {
+        let _ = env_logger::try_init();
+        debug!("strips_diacritics: starting");
+        let pin = PinBuilder::new("https://example.com/a", "Coffee")
+            .tags("Café")
+            .into_pin();
+        let s = TagSuggester::new(&[CachedPin::normalized(pin, true)], true);
+        let tags = s.suggest("https://other.com/", "Best cafe in town", 1);
+        assert_eq!(vec!["Café"], names(&tags));
+    }
+}
@@ -1,6 +1,8 @@
 use std::cmp::Ordering;
 use std::fmt;
 
+use super::normalize;
+
 #[derive(Serialize, Deserialize, Debug, Eq, Clone)]
 pub struct Tag(pub String, pub TagFreq);
 
@@ -11,11 +13,31 @@ pub enum TagFreq {
     Popular,
 }
 
+/// Tags the server suggests for a url.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TagSuggestions {
+    /// Tags other users saved the url with.
+    pub popular: Vec<Tag>,
+    /// Tags picked from the user's own vocabulary.
+    pub recommended: Vec<Tag>,
+}
+
+/// Returns true if `tag` is private, i.e. starts with a dot. Private tags are only shown to
+/// their owner, even on public bookmarks.
+pub fn is_private(tag: &str) -> bool {
+    tag.starts_with('.')
+}
+
 impl Tag {
     pub fn new(tag: String, freq: usize) -> Self {
         Tag(tag, TagFreq::Used(freq))
     }
 
+    /// Returns true if the tag is private, see [`is_private`].
+    pub fn is_private(&self) -> bool {
+        is_private(&self.0)
+    }
+
     pub fn set_popular(mut self) -> Self {
         self.1 = TagFreq::Popular;
         self
@@ -43,9 +65,10 @@ impl Ord for Tag {
     }
 }
 
+// Tags are the same if they only differ in case or Unicode normalization form.
 impl PartialEq for Tag {
     fn eq(&self, other: &Tag) -> bool {
-        self.0.eq_ignore_ascii_case(&other.0)
+        normalize::eq(&self.0, &other.0)
     }
 }
 
@@ -85,6 +108,26 @@ mod tests {
         assert!(t1 != t2);
         let t3 = Tag::new("TaG👻2".to_string(), 1);
         assert!(t2 == t3);
+        let t4 = Tag::new("ＴａＧ👻2".to_string(), 1);
+        assert!(t2 == t4);
+
+        assert_eq!(
+            Tag::new("Ärger".to_string(), 1),
+            Tag::new("ärger".to_string(), 1)
+        );
+        assert!(Tag::new("Ärger".to_string(), 1) != Tag::new("arger".to_string(), 1));
+        assert_eq!(
+            Tag::new("Здравствуйт".to_string(), 1),
+            Tag::new("здравствуйт".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn private_tags() {
+        assert!(Tag::new(".todo".to_string(), 1).is_private());
+        assert!(!Tag::new("todo.".to_string(), 1).is_private());
+        assert!(!Tag::new("todo".to_string(), 1).is_private());
+        assert!(is_private("."));
     }
 
     #[test]
@@ -1,5 +1,6 @@
 // TODO: Add tests for case insensitivity searches of tags/pins
 use super::*;
+use std::collections::HashSet;
 use std::fs;
 
 #[cfg(feature = "bench")]
@@ -93,6 +94,33 @@ fn find_url_test() {
     assert!(r.is_ok());
     let op = r.unwrap();
     assert!(op.is_none());
+
+    // Fragments, trailing slashes and tracking params are ignored by default
+    let url = "http://blog.khubla.com/freebsd/time-machine-backups-using-freebsd-zfs";
+    let variants = [
+        format!("{}#fragment", url),
+        format!("{}/", url),
+        format!("{}?utm_source=rss&utm_medium=feed", url),
+        url.replace("http://blog.khubla.com", "HTTP://Blog.Khubla.com:80"),
+    ];
+    let https = url.replace("http:", "https:");
+    let found = |pinboard: &Pinboard, url: &str| pinboard.find_url(url).unwrap().is_some();
+    assert_eq!(UrlStrictness::Normal, pinboard.cfg.url_strictness);
+    assert!(variants.iter().all(|v| found(&pinboard, v)));
+    assert!(!found(&pinboard, &https));
+
+    pinboard.set_url_strictness(UrlStrictness::Loose);
+    assert!(variants.iter().all(|v| found(&pinboard, v)));
+    assert!(found(&pinboard, &https));
+
+    pinboard.set_url_strictness(UrlStrictness::Parsed);
+    assert!(found(&pinboard, url));
+    assert!(found(&pinboard, &variants[3]));
+    assert!(!variants[..3].iter().any(|v| found(&pinboard, v)));
+
+    pinboard.set_url_strictness(UrlStrictness::Exact);
+    assert!(found(&pinboard, &url.to_uppercase()));
+    assert!(!variants.iter().any(|v| found(&pinboard, v)));
 }
 
 #[test]
@@ -312,6 +340,115 @@ fn add_pin_test() {
             r.find_root_cause().downcast_ref::<ParseError>().unwrap()
         );
     }
+    {
+        // add a pin over Pinboard's limits, nothing is sent
+        let mut p = PinBuilder::new(TEST_URL, "test bookmark/pin")
+            .tags("tagestan what")
+            .into_pin();
+        p.tags = "tagestan TAGESTAN".into();
+        let r = pinboard
+            .add_pin(p)
+            .expect_err("Should refuse repeated tags");
+        assert_eq!(
+            vec![ValidationError::DuplicateTag("TAGESTAN".to_string())],
+            r.downcast_ref::<InvalidPin>().unwrap().errors
+        );
+    }
+}
+
+#[test]
+fn duplicates_test() {
+    let _ = env_logger::try_init();
+    debug!("duplicates_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    assert!(pinboard.find_duplicates(true).unwrap().is_none());
+
+    let url = "http://blog.khubla.com/freebsd/time-machine-backups-using-FreeBSD-zfs";
+    let duplicates = vec![
+        PinBuilder::new(
+            format!("{}/?utm_source=rss", url.replace("http:", "https:")),
+            "Time Machine Backups".to_string(),
+        )
+        .tags("backup Zfs".to_string())
+        .description("saved again".to_string())
+        .into_pin(),
+        PinBuilder::new(
+            "https://www.davd.eu/freebsd-世-nas/?id=2".to_string(),
+            "Building a FreeBSD NAS!".to_string(),
+        )
+        .into_pin(),
+    ];
+    pinboard
+        .cached_data
+        .pins
+        .as_mut()
+        .unwrap()
+        .extend(duplicates.into_iter().map(CachedPin::new));
+
+    let groups = pinboard.find_duplicates(false).unwrap().unwrap();
+    assert_eq!(1, groups.len());
+    assert_eq!(url, groups[0].survivor().url);
+    assert_eq!(2, pinboard.find_duplicates(true).unwrap().unwrap().len());
+
+    let report = pinboard
+        .merge_duplicates(&groups[0], true)
+        .expect("Dry run shouldn't fail");
+    assert!(report.dry_run);
+    assert_eq!(url, report.merged.url);
+    assert_eq!(groups[0].survivor().time, report.merged.time);
+    assert_eq!("timemachine freebsd network backup Zfs", report.merged.tags);
+    assert_eq!("no", report.merged.shared);
+    assert_eq!(Some("saved again"), report.merged.extended.as_deref());
+    assert_eq!(
+        vec![format!(
+            "{}/?utm_source=rss",
+            url.replace("http:", "https:")
+        )],
+        report.deleted
+    );
+    assert!(report.to_string().starts_with("would keep "));
+
+    {
+        let _m1 = start_mockito_server(r"^/posts/add.*$", 200, r#"{"result_code":"done"}"#);
+        let _m2 = start_mockito_server(r"^/posts/delete.*$", 200, r#"{"result_code":"done"}"#);
+        let report = pinboard
+            .merge_duplicates(&groups[0], false)
+            .expect("Couldn't merge duplicates");
+        assert!(!report.dry_run);
+        assert_eq!(1, report.deleted.len());
+    }
+    {
+        let _m1 = start_mockito_server(r"^/posts/add.*$", 200, r#"{"result_code":"done"}"#);
+        let _m2 = start_mockito_server(
+            r"^/posts/delete.*$",
+            200,
+            r#"{"result_code":"item not found"}"#,
+        );
+        let e = pinboard
+            .merge_duplicates(&groups[0], false)
+            .expect_err("Failed delete should be reported");
+        assert_eq!("item not found", e.to_string());
+    }
+
+    // Too many tags once merged
+    let tagged = |prefix: &str| {
+        let tags = (0..60)
+            .map(|i| format!("{}{}", prefix, i))
+            .collect::<Vec<String>>();
+        PinBuilder::new(url, "title").tags(tags.join(" ")).into_pin()
+    };
+    let (a, b) = (tagged("a"), tagged("b"));
+    let group = DuplicateGroup { pins: vec![&a, &b] };
+    let e = pinboard
+        .merge_duplicates(&group, false)
+        .expect_err("Merged pin has too many tags");
+    assert!(e.downcast_ref::<InvalidPin>().is_some());
 }
 
 #[test]
@@ -366,6 +503,311 @@ fn delete_test() {
     // assert!(e3.is_some());
     // assert_eq!(&ParseError::RelativeUrlWithoutBase, e3.unwrap());
 }
+#[test]
+fn suggest_tags_test() {
+    let _ = env_logger::try_init();
+    debug!("suggest_tags_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let _m3 = mock("GET", Matcher::Regex(r"^/posts/suggest.*$".to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"[{"popular":["datetime","library","rust"]},{"recommended":["datetime","library","programming","rust"]}]"#)
+            .create();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    let tags = pinboard
+        .suggest_tags("https://example.com/", "Writing RUST macros", 5, false)
+        .expect("Offline suggestions shouldn't fail");
+    assert!(tags.len() <= 5);
+    assert!(tags[..2].iter().any(|t| t.0 == "Rust"));
+    assert!(tags.iter().all(|t| t.1 != TagFreq::New));
+
+    let tags = pinboard
+        .suggest_tags("https://docs.rs/chrono/0.4.0/chrono", "", 20, true)
+        .expect("Merged suggestions shouldn't fail");
+    assert!(tags
+        .iter()
+        .any(|t| t.0 == "datetime" && t.1 == TagFreq::New));
+    assert!(tags
+        .iter()
+        .any(|t| t.0.eq_ignore_ascii_case("rust") && t.1 != TagFreq::New));
+
+    assert!(pinboard
+        .suggest_tags(":// bad url/#", "", 5, false)
+        .is_err());
+
+    let suggestions = pinboard
+        .tag_suggestions("https://docs.rs/chrono/0.4.0/chrono")
+        .expect("Server suggestions shouldn't fail");
+    assert_eq!(3, suggestions.popular.len());
+    assert!(suggestions.popular.iter().all(|t| t.1 == TagFreq::Popular));
+    let freqs = suggestions
+        .recommended
+        .iter()
+        .map(|t| (t.0.as_str(), t.1.clone()))
+        .collect::<Vec<(&str, TagFreq)>>();
+    assert_eq!(
+        vec![
+            ("datetime", TagFreq::Popular),
+            ("library", TagFreq::Used(1)),
+            ("programming", TagFreq::Popular),
+            ("rust", TagFreq::Used(10)),
+        ],
+        freqs
+    );
+}
+
+#[test]
+fn private_tags_test() {
+    let _ = env_logger::try_init();
+    debug!("private_tags_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let _m3 = start_mockito_server(r"^/posts/add.*$", 200, r#"{"result_code":"done"}"#);
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    pinboard.cached_data.tags.as_mut().unwrap().extend(
+        vec![(".todo", 3), (".rust-later", 1)]
+            .into_iter()
+            .map(|(t, n)| CachedTag::new(Tag::new(t.to_string(), n), false)),
+    );
+    let count = pinboard.list_tag_pairs().unwrap().len();
+    assert_eq!(
+        2,
+        pinboard
+            .list_tag_pairs()
+            .unwrap()
+            .iter()
+            .filter(|t| t.is_private())
+            .count()
+    );
+    assert!(pinboard
+        .search_list_of_tags("rust")
+        .unwrap()
+        .unwrap()
+        .iter()
+        .any(|t| t.0 == ".rust-later"));
+
+    pinboard.enable_hide_private_tags(true);
+    let tags = pinboard.list_tag_pairs().unwrap();
+    assert_eq!(count - 2, tags.len());
+    assert!(tags.iter().all(|t| !t.is_private()));
+    let tags = pinboard.search_list_of_tags("rust").unwrap().unwrap();
+    assert!(tags.iter().all(|t| !t.is_private()));
+    assert!(pinboard.search_list_of_tags("todo").unwrap().is_none());
+
+    // Only warns
+    let pin = PinBuilder::new("https://rust-lang.org/", "Rust")
+        .tags(".todo .rust-later")
+        .shared("yes")
+        .into_pin();
+    assert!(pin.has_only_private_tags());
+    pinboard.add_pin(pin).expect("Pin should be added");
+}
+
+#[test]
+fn bulk_tag_edit_test() {
+    let _ = env_logger::try_init();
+    debug!("bulk_tag_edit_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let _m3 = start_mockito_server(r"^/posts/add.*$", 200, r#"{"result_code":"done"}"#);
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    pinboard.set_bulk_edit_interval(Duration::from_millis(0));
+    let query = Query::parse("tag:freebsd").unwrap();
+    let edits = vec![
+        TagEdit::Add(vec!["archive".into()]),
+        TagEdit::Replace("freebsd".into(), "FreeBSD".into()),
+    ];
+    let expected = pinboard.search_query("tag:freebsd").unwrap().unwrap().len();
+
+    let edit = pinboard.edit_tags(&query, &edits, true).unwrap();
+    assert_eq!(expected, edit.changes.len());
+    assert_eq!(0, edit.done);
+    assert!(edit
+        .changes
+        .iter()
+        .all(|c| c.pin.tags.ends_with("archive") && c.pin.tags.contains("FreeBSD")));
+    assert!(pinboard.unfinished_tag_edit().unwrap().is_none());
+    assert!(pinboard.resume_tag_edit().unwrap().is_none());
+
+    {
+        let _m4 = start_mockito_server(
+            r"^/posts/add.*$",
+            200,
+            r#"{"result_code":"something went wrong"}"#,
+        );
+        assert!(pinboard.edit_tags(&query, &edits, false).is_err());
+        let unfinished = pinboard.unfinished_tag_edit().unwrap().unwrap();
+        assert_eq!(edit.changes, unfinished.changes);
+        assert_eq!(0, unfinished.done);
+        assert!(pinboard.edit_tags(&query, &edits, false).is_err());
+    }
+
+    let resumed = pinboard.resume_tag_edit().unwrap().unwrap();
+    assert!(resumed.is_done());
+    assert!(!pinboard.cached_data.store_path(BULK_CACHE_FN).exists());
+    assert!(!pinboard.discard_tag_edit().unwrap());
+
+    let edit = pinboard.edit_tags(&query, &edits, false).unwrap();
+    assert_eq!(expected, edit.done);
+    // Too many tags, refused before anything is sent
+    let many = vec![TagEdit::Add((0..100).map(|i| format!("t{}", i)).collect())];
+    let e = pinboard
+        .edit_tags(&query, &many, false)
+        .expect_err("Changed pins have too many tags");
+    assert!(e.downcast_ref::<InvalidPin>().is_some());
+    assert!(pinboard.unfinished_tag_edit().unwrap().is_none());
+    // Nothing to change
+    assert!(pinboard
+        .edit_tags(&Query::parse("tag:nonexistingtag").unwrap(), &edits, false)
+        .unwrap()
+        .changes
+        .is_empty());
+}
+
+#[test]
+fn tag_hygiene_test() {
+    let _ = env_logger::try_init();
+    debug!("tag_hygiene_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let _m3 = start_mockito_server(r#"^/tags/rename.*$"#, 200, r#"{"result":"done"}"#);
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    let report = pinboard.tag_hygiene().expect("Cache is valid");
+    let tags = pinboard.list_tag_pairs().unwrap();
+    assert_eq!(
+        tags.iter().filter(|t| t.1 == TagFreq::Used(1)).count(),
+        report.singletons.len()
+    );
+
+    pinboard.cached_data.tags.as_mut().unwrap().extend(
+        vec![("Microcontrollers", 1), ("hardwear", 1)]
+            .into_iter()
+            .map(|(t, n)| CachedTag::new(Tag::new(t.to_string(), n), false)),
+    );
+    let report = pinboard.tag_hygiene().unwrap();
+    let renames = report
+        .renames
+        .iter()
+        .filter(|r| r.from.0 == "Microcontrollers" || r.from.0 == "hardwear")
+        .cloned()
+        .collect::<Vec<TagRename>>();
+    assert_eq!(1, renames.len());
+    assert_eq!("microcontroller", renames[0].to.0);
+    assert_eq!(Similarity::Plural, renames[0].reason);
+    pinboard
+        .apply_tag_renames(&renames)
+        .expect("Renames should be accepted");
+}
+
+#[test]
+fn tag_stats_test() {
+    use serde_json;
+    let _ = env_logger::try_init();
+    debug!("tag_stats_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    let all = pinboard.all_tag_stats(5).expect("Cache is valid");
+    assert!(!all.is_empty());
+    assert!(all.windows(2).all(|w| w[0].count >= w[1].count));
+    for stats in &all {
+        assert_eq!(
+            stats.count,
+            stats.monthly.iter().map(|m| m.count).sum::<usize>()
+        );
+        assert!(stats.first_used <= stats.last_used);
+        assert!(stats.unread_ratio >= 0.0 && stats.unread_ratio <= 1.0);
+        assert!(stats.related_tags.len() <= 5 && stats.domains.len() <= 5);
+    }
+
+    let first = &all[0];
+    let stats = pinboard
+        .tag_stats(&first.tag.to_uppercase(), 5)
+        .unwrap()
+        .expect("Tag is used");
+    assert_eq!(first, &stats);
+    let json = serde_json::to_string(&stats).unwrap();
+    assert_eq!(stats, serde_json::from_str::<TagStats>(&json).unwrap());
+
+    assert!(pinboard.tag_stats("no-such-tag-here", 5).unwrap().is_none());
+}
+
+#[test]
+fn tag_tree_test() {
+    let _ = env_logger::try_init();
+    debug!("tag_tree_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let _m3 = start_mockito_server(r#"^/tags/rename.*$"#, 200, r#"{"result":"done"}"#);
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    pinboard.cached_data.tags.as_mut().unwrap().extend(
+        vec![("lang/rust", 3), ("Lang/Go", 2), ("lang/rust/async", 1), ("proj::x", 1)]
+            .into_iter()
+            .map(|(t, n)| CachedTag::new(Tag::new(t.to_string(), n), false)),
+    );
+
+    let roots = pinboard.tag_tree().expect("There are tags");
+    assert!(roots.iter().any(|n| n.path == "proj::x"));
+    let lang = roots.iter().find(|n| n.path == "lang").unwrap();
+    assert_eq!(None, lang.tag);
+    assert_eq!(6, lang.total);
+
+    let children = pinboard.tag_children("LANG").unwrap();
+    assert_eq!(
+        vec!["lang/Go", "lang/rust"],
+        children.iter().map(|n| n.path.as_str()).collect::<Vec<&str>>()
+    );
+    assert!(pinboard.tag_children("lang/go").is_none());
+    assert_eq!(4, pinboard.tag_subtree("lang/rust").unwrap().total);
+
+    let tags = pinboard.search_list_of_tags_under("lang", "rust").unwrap().unwrap();
+    assert_eq!(2, tags.len());
+    assert!(tags.iter().all(|t| t.0.starts_with("lang/rust")));
+    assert!(pinboard.search_list_of_tags_under("proj", "rust").unwrap().is_none());
+
+    for new in &["", " ", "a b", "/code", "code/", "lang"] {
+        assert!(pinboard.rename_tag_subtree("lang", new).is_err());
+    }
+    let renames = pinboard.rename_tag_subtree("lang", "code").unwrap();
+    assert_eq!(
+        vec![
+            ("lang/rust".to_string(), "code/rust".to_string()),
+            ("Lang/Go".to_string(), "code/Go".to_string()),
+            ("lang/rust/async".to_string(), "code/rust/async".to_string()),
+        ],
+        renames
+    );
+
+    assert!(pinboard.set_tag_separator("").is_err());
+    pinboard.set_tag_separator("::").unwrap();
+    assert_eq!(1, pinboard.tag_children("proj").unwrap().len());
+}
+
 #[test]
 fn popular_tags() {
     let _ = env_logger::try_init();
@@ -678,6 +1120,35 @@ fn search_multi_query_multi_field() {
         assert_eq!(3, pins.as_ref().unwrap().len());
     }
 
+    // Fuzzy search with regex meta characters
+    {
+        // A single character matches fuzzily exactly where it is contained
+        for query in &["(", ")", "|", "?", "*", "+", "[", "]"] {
+            let queries = [query];
+            let urls = |pins: Option<Vec<&Pin>>| {
+                pins.unwrap_or_default()
+                    .iter()
+                    .map(|p| p.url.to_string())
+                    .collect::<Vec<String>>()
+            };
+            pinboard.enable_fuzzy_search(false);
+            let expected = urls(pinboard.search(&queries, &[]).unwrap_or_else(|e| panic!(e)));
+            pinboard.enable_fuzzy_search(true);
+            let pins = urls(pinboard.search(&queries, &[]).unwrap_or_else(|e| panic!(e)));
+            assert_eq!(expected, pins, "{}", query);
+            pinboard.search_items(query).unwrap_or_else(|e| panic!(e));
+            pinboard
+                .search_list_of_tags(query)
+                .unwrap_or_else(|e| panic!(e));
+        }
+        let queries = ["[rust]*"];
+        let pins = pinboard.search(&queries, &[]).unwrap_or_else(|e| panic!(e));
+        assert!(pins.is_none());
+        let queries = ["(6+2)"];
+        let pins = pinboard.search(&queries, &[]).unwrap_or_else(|e| panic!(e));
+        assert!(pins.is_some());
+    }
+
     // Fuzzy search unicode
     {
         pinboard.enable_fuzzy_search(true);
@@ -777,6 +1248,999 @@ fn search_multi_query_multi_field() {
     }
 }
 
+#[test]
+fn search_query_test() {
+    let _ = env_logger::try_init();
+    debug!("search_query_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    _home.push("mockito-rusty-pin");
+    let cache_path = Some(_home);
+
+    let pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+
+    let count = |q: &str| {
+        pinboard
+            .search_query(q)
+            .unwrap_or_else(|e| panic!("{:?}", e))
+            .map_or(0, |pins| pins.len())
+    };
+    assert_eq!(10, count("tag:rust"));
+    assert_eq!(10, count("tag:RUST"));
+    assert_eq!(8, count("tag:rust -tag:embedded"));
+    assert_eq!(1, count("tag:rust site:github.com"));
+    assert_eq!(2, count("tag:rust site:github.io"));
+    assert_eq!(2, count("tag:zfs -tag:timemachine"));
+    assert_eq!(1, count(r#"tag:zfs "freebsd nas""#));
+    assert_eq!(2, count("toread:yes private:yes"));
+    assert_eq!(0, count("toread:yes private:no"));
+    assert_eq!(1, count("before:2017-01-01"));
+    assert_eq!(0, count("tag:rust -rust"));
+    assert_eq!(
+        count("tag:rust") + count("tag:zfs") - count("tag:rust tag:zfs"),
+        count("tag:rust OR tag:zfs")
+    );
+    assert_eq!(
+        count("tag:zfs tag:freebsd") + count("tag:zfs -tag:freebsd -tag:timemachine"),
+        count("tag:zfs tag:freebsd OR -tag:timemachine")
+    );
+
+    // Same query built programmatically
+    let query = Query::Tag("rust".into())
+        .or(Query::Tag("zfs".into()))
+        .and(Query::Site("github.com".into()).negate());
+    let found = pinboard
+        .search_by_query(&query, &SearchOptions::default())
+        .unwrap()
+        .unwrap();
+    assert_eq!(count("tag:rust OR tag:zfs -site:github.com"), found.len());
+    let page = SearchOptions::new()
+        .sort(SortKey::Title, SortOrder::Ascending)
+        .limit(3);
+    let first = pinboard
+        .search_by_query_iter(&query, &page)
+        .unwrap()
+        .collect::<Vec<&Pin>>();
+    assert_eq!(3, first.len());
+    assert!(first
+        .windows(2)
+        .all(|w| w[0].title.to_lowercase() <= w[1].title.to_lowercase()));
+
+    let e = pinboard
+        .search_query("tag:rust colour:red")
+        .expect_err("Should not parse unknown field");
+    assert_eq!(
+        &QueryError::UnknownField {
+            field: "colour".to_string(),
+            position: 9
+        },
+        e.downcast_ref::<QueryError>().unwrap()
+    );
+}
+
+#[test]
+fn search_ranked_test() {
+    let _ = env_logger::try_init();
+    debug!("search_ranked_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    _home.push("mockito-rusty-pin");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    pinboard.enable_fuzzy_search(false);
+
+    {
+        let queries = ["rust", "macros"];
+        let results = pinboard
+            .search_ranked(&queries, &[])
+            .unwrap_or_else(|e| panic!("{:?}", e))
+            .expect("No results");
+        assert_eq!("The Little Book of Rust Macros", results[0].pin.title);
+
+        // Both terms are highlighted in the title
+        let title = &results[0].matches[0];
+        assert_eq!(Field::Title, title.field);
+        let matched = title
+            .byte_ranges
+            .iter()
+            .map(|r| &results[0].pin.title[r.clone()])
+            .collect::<Vec<&str>>();
+        assert_eq!(vec!["Rust", "Macros"], matched);
+    }
+
+    {
+        // Same results as the unranked search, sorted by descending score
+        let queries = ["rust"];
+        let fields = vec![SearchType::TitleOnly, SearchType::TagOnly];
+        let results = pinboard
+            .search_ranked(&queries, &fields)
+            .unwrap_or_else(|e| panic!("{:?}", e))
+            .expect("No results");
+        let pins = pinboard
+            .search(&queries, &fields)
+            .unwrap_or_else(|e| panic!("{:?}", e))
+            .expect("No results");
+        assert_eq!(pins.len(), results.len());
+        assert!(results.iter().all(|r| pins.contains(&r.pin)));
+        assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+    }
+
+    {
+        let results = pinboard
+            .search_items_ranked("openpgp")
+            .unwrap_or_else(|e| panic!("{:?}", e))
+            .expect("No results");
+        assert_eq!("OpenPGP Best Practices - riseup.net", results[0].pin.title);
+    }
+
+    {
+        // fuzzy
+        pinboard.enable_fuzzy_search(true);
+        let results = pinboard
+            .search_items_ranked("failurecargopackage")
+            .unwrap_or_else(|e| panic!("{:?}", e))
+            .expect("No results");
+        assert_eq!(1, results.len());
+        assert_eq!("https://crates.io/crates/failure", results[0].pin.url);
+        assert!(pinboard
+            .search_items_ranked("non-existence-pin")
+            .unwrap_or_else(|e| panic!("{:?}", e))
+            .is_none());
+    }
+}
+
+#[test]
+fn search_index_test() {
+    let _ = env_logger::try_init();
+    debug!("search_index_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard = Pinboard::new(include_str!("api_token.txt"), cache_path.clone())
+        .expect("Can't setup Pinboard");
+    assert!(pinboard.cached_data.index_cache_file.exists());
+    assert!(pinboard.cached_data.index.is_some());
+
+    // Index is loaded back from disk
+    {
+        let reloaded =
+            Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+        assert_eq!(pinboard.cached_data.index, reloaded.cached_data.index);
+    }
+
+    fn urls(pins: Option<Vec<&Pin>>) -> Vec<String> {
+        pins.unwrap_or_default()
+            .iter()
+            .map(|p| p.url.to_string())
+            .collect()
+    }
+    fn tags(tags: Option<Vec<&Tag>>) -> Vec<String> {
+        tags.unwrap_or_default()
+            .iter()
+            .map(|t| t.0.clone())
+            .collect()
+    }
+    let queries = [
+        "rust",
+        "RUST",
+        "openpgp",
+        "zfs",
+        "fr",
+        "ma",
+        "h",
+        "",
+        "c++",
+        "(6+2)",
+        "amoozesh",
+        "\u{0622}\u{0645}\u{0648}",
+        "rst",
+        "Здрав",
+    ];
+    let run = |pinboard: &Pinboard| {
+        let mut found = Vec::new();
+        for query in &queries {
+            found.push(urls(pinboard.search(&[query], &[]).unwrap()));
+            found.push(urls(
+                pinboard
+                    .search(&[query, &"e"], &[SearchType::TagTitleOnly])
+                    .unwrap(),
+            ));
+            found.push(urls(
+                pinboard.search(&[query], &[SearchType::UrlOnly]).unwrap(),
+            ));
+            found.push(urls(pinboard.search_items(query).unwrap()));
+            found.push(tags(pinboard.search_list_of_tags(query).unwrap()));
+        }
+        found
+    };
+
+    // Same results with and without the index
+    for &fuzzy in &[false, true] {
+        for &tag_only in &[false, true] {
+            pinboard.enable_fuzzy_search(fuzzy);
+            pinboard.enable_tag_only_search(tag_only);
+            pinboard.cached_data.rebuild_index();
+            let indexed = run(&pinboard);
+            assert!(indexed.iter().any(|r| !r.is_empty()));
+            pinboard.cached_data.index = None;
+            assert_eq!(
+                indexed,
+                run(&pinboard),
+                "fuzzy: {}, tag_only: {}",
+                fuzzy,
+                tag_only
+            );
+        }
+    }
+}
+
+#[test]
+fn stale_index_test() {
+    let _ = env_logger::try_init();
+    debug!("stale_index_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    assert!(pinboard.search_items("zyxwvut").unwrap().is_none());
+
+    // Same number of pins, but one of them was edited since the index was saved
+    {
+        let pins = pinboard.cached_data.pins.as_mut().unwrap();
+        pins[0].pin.title = "zyxwvut".into();
+        pins[0].normalize(false);
+    }
+    let pins_cache_file = pinboard.cached_data.pins_cache_file.clone();
+    pinboard
+        .cached_data
+        .write_cache_file(&pins_cache_file, pinboard.cached_data.pins.as_ref().unwrap())
+        .unwrap();
+    pinboard.cached_data.load_cache_data_from_file().unwrap();
+    assert_eq!(
+        1,
+        pinboard.search_items("zyxwvut").unwrap().unwrap().len()
+    );
+}
+
+#[test]
+fn search_options_test() {
+    let _ = env_logger::try_init();
+    debug!("search_options_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+
+    fn urls(pins: Option<Vec<&Pin>>) -> Vec<String> {
+        pins.unwrap_or_default()
+            .iter()
+            .map(|p| p.url.to_string())
+            .collect()
+    }
+
+    // Paging without sorting slices the usual results
+    let all = urls(pinboard.search_items("e").unwrap());
+    assert!(all.len() > 10);
+    let page = SearchOptions::new().offset(3).limit(5);
+    assert_eq!(
+        all[3..8].to_vec(),
+        urls(pinboard.search_items_with("e", &page).unwrap())
+    );
+    let first = pinboard
+        .search_items_iter("e", &SearchOptions::new())
+        .unwrap()
+        .take(5)
+        .map(|p| p.url.to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(all[..5].to_vec(), first);
+    let past_end = SearchOptions::new().offset(all.len());
+    assert!(pinboard
+        .search_items_with("e", &past_end)
+        .unwrap()
+        .is_none());
+
+    let all = urls(pinboard.search(&["e"], &[SearchType::TitleOnly]).unwrap());
+    let page = SearchOptions::new().offset(2).limit(3);
+    assert_eq!(
+        all[2..5].to_vec(),
+        urls(
+            pinboard
+                .search_with(&["e"], &[SearchType::TitleOnly], &page)
+                .unwrap()
+        )
+    );
+
+    // Sorting
+    let by_time = pinboard
+        .list_bookmarks_with(&SearchOptions::new().sort(SortKey::Time, SortOrder::Ascending))
+        .unwrap();
+    assert_eq!(pinboard.list_bookmarks().unwrap().len(), by_time.len());
+    assert!(by_time.windows(2).all(|w| w[0].time <= w[1].time));
+    let newest = pinboard
+        .list_bookmarks_iter(
+            &SearchOptions::new()
+                .sort(SortKey::Time, SortOrder::Descending)
+                .limit(3),
+        )
+        .collect::<Vec<&Pin>>();
+    assert_eq!(3, newest.len());
+    assert!(newest[0].time >= newest[1].time && newest[1].time >= newest[2].time);
+    assert_eq!(by_time.iter().map(|p| p.time).max(), Some(newest[0].time));
+
+    let by_title = pinboard
+        .search_items_with(
+            "e",
+            &SearchOptions::new().sort(SortKey::Title, SortOrder::Ascending),
+        )
+        .unwrap()
+        .unwrap();
+    let titles = by_title
+        .iter()
+        .map(|p| p.title.to_lowercase())
+        .collect::<Vec<String>>();
+    let mut sorted = titles.clone();
+    sorted.sort();
+    assert_eq!(sorted, titles);
+
+    let by_url = pinboard
+        .search_items_with(
+            "e",
+            &SearchOptions::new().sort(SortKey::Url, SortOrder::Descending),
+        )
+        .unwrap()
+        .unwrap();
+    assert!(by_url
+        .windows(2)
+        .all(|w| w[0].url.to_lowercase() >= w[1].url.to_lowercase()));
+
+    let ranked = pinboard
+        .search_ranked(&["rust"], &[])
+        .unwrap()
+        .unwrap()
+        .iter()
+        .map(|r| r.pin.url.to_string())
+        .collect::<Vec<String>>();
+    let by_relevance = SearchOptions::new().sort(SortKey::Relevance, SortOrder::Descending);
+    assert_eq!(
+        ranked,
+        urls(pinboard.search_with(&["rust"], &[], &by_relevance).unwrap())
+    );
+
+    // Tags
+    let freq = |t: &Tag| match t.1 {
+        TagFreq::Used(n) => n,
+        _ => 0,
+    };
+    let tags = pinboard
+        .search_list_of_tags_with(
+            "",
+            &SearchOptions::new().sort(SortKey::TagFrequency, SortOrder::Ascending),
+        )
+        .unwrap()
+        .unwrap();
+    assert_eq!(pinboard.list_tag_pairs().unwrap().len(), tags.len());
+    assert!(tags.windows(2).all(|w| freq(w[0]) <= freq(w[1])));
+    let names = pinboard
+        .search_list_of_tags_iter(
+            "",
+            &SearchOptions::new()
+                .sort(SortKey::Title, SortOrder::Ascending)
+                .limit(4),
+        )
+        .unwrap()
+        .map(|t| t.0.to_lowercase())
+        .collect::<Vec<String>>();
+    assert_eq!(4, names.len());
+    let mut all_names = tags
+        .iter()
+        .map(|t| t.0.to_lowercase())
+        .collect::<Vec<String>>();
+    all_names.sort();
+    assert_eq!(all_names[..4].to_vec(), names);
+}
+
+#[test]
+fn search_filter_test() {
+    let _ = env_logger::try_init();
+    debug!("search_filter_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+
+    fn urls(pins: Option<Vec<&Pin>>) -> Vec<String> {
+        pins.unwrap_or_default()
+            .iter()
+            .map(|p| p.url.to_string())
+            .collect()
+    }
+
+    let middle = {
+        let mut times = pinboard
+            .list_bookmarks()
+            .unwrap()
+            .iter()
+            .map(|p| p.time)
+            .collect::<Vec<DateTime<Utc>>>();
+        times.sort();
+        times[times.len() / 2]
+    };
+    let filters = vec![
+        SearchFilter::new().unread_only(),
+        SearchFilter::new().private_only(),
+        SearchFilter::new().public_only(),
+        SearchFilter::new().has_description(true),
+        SearchFilter::new().tag_count(Some(3), None),
+        SearchFilter::new().created_after(middle),
+        SearchFilter::new()
+            .created_before(middle)
+            .has_description(false),
+    ];
+    let fields = [SearchType::TitleOnly, SearchType::TagOnly];
+    for &fuzzy in &[false, true] {
+        pinboard.enable_fuzzy_search(fuzzy);
+        let all = pinboard.search(&["e"], &fields).unwrap().unwrap();
+        for filter in &filters {
+            let expected = all
+                .iter()
+                .filter(|p| filter.matches(p))
+                .map(|p| p.url.to_string())
+                .collect::<Vec<String>>();
+            assert!(expected.len() < all.len() || filter.private == Some(true));
+            let options = SearchOptions::new().filter(filter.clone());
+            assert_eq!(
+                expected,
+                urls(pinboard.search_with(&["e"], &fields, &options).unwrap())
+            );
+        }
+    }
+
+    // Filters are applied before paging
+    let unread = SearchOptions::new().filter(SearchFilter::new().unread_only());
+    let all = urls(pinboard.list_bookmarks_with(&unread));
+    assert_eq!(2, all.len());
+    assert_eq!(
+        all[1..].to_vec(),
+        urls(pinboard.list_bookmarks_with(&unread.clone().offset(1).limit(5)))
+    );
+    let khubla = SearchOptions::new().filter(
+        SearchFilter::new()
+            .private_only()
+            .tag_count(Some(4), Some(4)),
+    );
+    assert!(urls(pinboard.search_items_with("khubla", &khubla).unwrap())
+        .iter()
+        .any(|u| u.contains("blog.khubla.com")));
+    let public = SearchOptions::new().filter(SearchFilter::new().public_only());
+    assert!(urls(pinboard.search_items_with("khubla", &public).unwrap())
+        .iter()
+        .all(|u| !u.contains("blog.khubla.com")));
+}
+
+#[test]
+fn tag_aliases_test() {
+    let _ = env_logger::try_init();
+    debug!("tag_aliases_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let _m3 = start_mockito_server(r#"^/tags/rename.*$"#, 200, r#"{"result":"done"}"#);
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard = Pinboard::new(include_str!("api_token.txt"), cache_path.clone())
+        .expect("Can't setup Pinboard");
+    let urls = |pinboard: &Pinboard, q: &str| {
+        pinboard
+            .search(&[q], &[SearchType::TagOnly])
+            .unwrap()
+            .unwrap_or_default()
+            .iter()
+            .map(|p| p.url.to_string())
+            .collect::<HashSet<String>>()
+    };
+    let tagged = |pinboard: &Pinboard, tag: &str| {
+        pinboard
+            .list_bookmarks()
+            .unwrap_or_default()
+            .iter()
+            .filter(|p| p.tags.split_whitespace().any(|t| t.to_lowercase() == tag))
+            .map(|p| p.url.to_string())
+            .collect::<HashSet<String>>()
+    };
+    let hardware = urls(&pinboard, "hardware");
+    let microcontroller = urls(&pinboard, "microcontroller");
+    assert!(!hardware.is_empty() && !microcontroller.is_empty());
+    // Aliases match whole tags, not tags containing them like `microcontrollers`
+    let with_hardware = hardware
+        .union(&tagged(&pinboard, "microcontroller"))
+        .cloned()
+        .collect::<HashSet<String>>();
+    let with_microcontroller = microcontroller
+        .union(&tagged(&pinboard, "hardware"))
+        .cloned()
+        .collect::<HashSet<String>>();
+    assert!(pinboard.tag_aliases().unwrap().is_empty());
+    assert!(pinboard.apply_tag_aliases(true).unwrap().is_empty());
+
+    assert!(pinboard.add_tag_alias("MicroController", "hardware").unwrap());
+    assert!(!pinboard.add_tag_alias("microcontroller", "HARDWARE").unwrap());
+    assert!(pinboard.add_tag_alias(" ", "hardware").is_err());
+    assert!(pinboard.cached_data.store_path(ALIASES_CACHE_FN).exists());
+
+    assert_eq!(with_hardware, urls(&pinboard, "hardware"));
+    assert_eq!(with_microcontroller, urls(&pinboard, "microcontroller"));
+
+    let freq = |tags: &[&Tag], name: &str| {
+        tags.iter()
+            .find(|t| t.0 == name)
+            .map(|t| t.1.clone())
+            .unwrap()
+    };
+    let pairs = pinboard.list_tag_pairs().unwrap();
+    let merged = pinboard.list_merged_tag_pairs().unwrap().unwrap();
+    assert_eq!(pairs.len() - 1, merged.len());
+    match (freq(&pairs, "hardware"), freq(&pairs, "microcontroller")) {
+        (TagFreq::Used(a), TagFreq::Used(b)) => assert_eq!(
+            TagFreq::Used(a + b),
+            freq(&merged.iter().collect::<Vec<&Tag>>(), "hardware")
+        ),
+        _ => panic!("Cached tags are used"),
+    }
+
+    let renames = vec![("microcontroller".to_string(), "hardware".to_string())];
+    assert_eq!(renames, pinboard.apply_tag_aliases(true).unwrap());
+    assert_eq!(renames, pinboard.apply_tag_aliases(false).unwrap());
+
+    {
+        let reloaded =
+            Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+        assert_eq!(
+            vec![TagAlias::new("hardware", vec!["MicroController".into()])],
+            reloaded.tag_aliases().unwrap()
+        );
+    }
+    assert!(pinboard.remove_tag_alias("microcontroller").unwrap());
+    assert!(!pinboard.remove_tag_alias("microcontroller").unwrap());
+    assert_eq!(hardware, urls(&pinboard, "hardware"));
+}
+
+#[test]
+fn tag_bundles_test() {
+    let _ = env_logger::try_init();
+    debug!("tag_bundles_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let pinboard = Pinboard::new(include_str!("api_token.txt"), cache_path.clone())
+        .expect("Can't setup Pinboard");
+    let urls = |pins: Option<Vec<&Pin>>| {
+        pins.unwrap_or_default()
+            .iter()
+            .map(|p| p.url.to_string())
+            .collect::<HashSet<String>>()
+    };
+    let tagged = |tag: &str| {
+        urls(pinboard.list_bookmarks().map(|pins| {
+            pins.into_iter()
+                .filter(|p| p.tags.split_whitespace().any(|t| t.to_lowercase() == tag))
+                .collect()
+        }))
+    };
+    let hardware = tagged("hardware");
+    let microcontroller = tagged("microcontroller");
+    assert!(!hardware.is_empty() && !microcontroller.is_empty());
+    assert!(pinboard.tag_bundles().unwrap().is_empty());
+    assert!(pinboard.search_tag_bundle("electronics").is_err());
+
+    let bundle = TagBundle::new("electronics", vec!["Hardware".into()]);
+    pinboard.save_tag_bundle(bundle.clone()).unwrap();
+    assert!(pinboard.save_tag_bundle(TagBundle::new(" ", vec![])).is_err());
+    assert!(pinboard.cached_data.store_path(BUNDLES_CACHE_FN).exists());
+    assert_eq!(Some(bundle), pinboard.tag_bundle("electronics").unwrap());
+    assert_eq!(
+        hardware,
+        urls(pinboard.search_tag_bundle("electronics").unwrap())
+    );
+
+    assert!(pinboard
+        .add_tags_to_bundle("electronics", &["MicroController".into()])
+        .unwrap());
+    assert!(!pinboard
+        .add_tags_to_bundle("electronics", &["hardware".into()])
+        .unwrap());
+    assert!(pinboard.add_tags_to_bundle("missing", &["a".into()]).is_err());
+    let all = hardware.union(&microcontroller).cloned().collect::<HashSet<String>>();
+    assert_eq!(all, urls(pinboard.search_tag_bundle("electronics").unwrap()));
+
+    let json = pinboard.export_tag_bundles().unwrap();
+    assert!(pinboard
+        .remove_tags_from_bundle("electronics", &["hardware".into()])
+        .unwrap());
+    assert_eq!(
+        vec!["MicroController"],
+        pinboard.tag_bundle("electronics").unwrap().unwrap().tags
+    );
+    assert!(pinboard.delete_tag_bundle("electronics").unwrap());
+    assert!(!pinboard.delete_tag_bundle("electronics").unwrap());
+    assert!(pinboard.tag_bundles().unwrap().is_empty());
+
+    assert!(pinboard.import_tag_bundles("not json").is_err());
+    assert_eq!(1, pinboard.import_tag_bundles(&json).unwrap());
+    {
+        let reloaded =
+            Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+        assert_eq!(
+            vec![TagBundle::new(
+                "electronics",
+                vec!["Hardware".into(), "MicroController".into()]
+            )],
+            reloaded.tag_bundles().unwrap()
+        );
+    }
+    assert_eq!(all, urls(pinboard.search_tag_bundle("electronics").unwrap()));
+}
+
+#[test]
+fn saved_searches_test() {
+    let _ = env_logger::try_init();
+    debug!("saved_searches_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard = Pinboard::new(include_str!("api_token.txt"), cache_path.clone())
+        .expect("Can't setup Pinboard");
+    assert!(pinboard.saved_searches().unwrap().is_empty());
+
+    let freebsd = SavedSearch::new("freebsd", vec!["freebsd".into()])
+        .fields(vec![SearchType::TagOnly, SearchType::TitleOnly]);
+    let unread =
+        SavedSearch::new("unread", vec!["".into()]).filter(SearchFilter::new().unread_only());
+    pinboard.save_search(freebsd.clone()).unwrap();
+    pinboard.save_search(unread.clone()).unwrap();
+    pinboard.save_search(freebsd.clone().fuzzy(true)).unwrap();
+    assert!(pinboard.save_search(SavedSearch::new(" ", vec![])).is_err());
+    assert!(pinboard.cached_data.store_path(SEARCHES_CACHE_FN).exists());
+    assert_eq!(
+        vec![freebsd.clone().fuzzy(true), unread.clone()],
+        pinboard.saved_searches().unwrap()
+    );
+
+    // Saved searches use their own settings
+    let expected = pinboard
+        .search_with(
+            &["freebsd"],
+            &[SearchType::TagOnly, SearchType::TitleOnly],
+            &SearchOptions::new(),
+        )
+        .unwrap()
+        .unwrap()
+        .len();
+    assert!(!pinboard.cfg.fuzzy_search);
+    pinboard.save_search(freebsd.clone()).unwrap();
+    let found = pinboard.run_saved_search("freebsd").unwrap().unwrap();
+    assert_eq!(expected, found.len());
+    assert_eq!(
+        2,
+        pinboard.run_saved_search("unread").unwrap().unwrap().len()
+    );
+    assert!(pinboard.run_saved_search("missing").is_err());
+
+    // Everything has been seen
+    assert!(pinboard.saved_search_news().unwrap().is_empty());
+    {
+        let reloaded =
+            Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+        let searches = reloaded.saved_searches().unwrap();
+        assert_eq!(2, searches.len());
+        assert!(searches.iter().all(|s| s.seen_urls.is_some()));
+    }
+
+    let mut pin = PinBuilder::new(
+        "https://www.freebsd.org/releases/".to_string(),
+        "FreeBSD Releases".to_string(),
+    )
+    .toread("no")
+    .into_pin();
+    // Older than the other bookmarks, as if imported
+    pin.time = Utc.with_ymd_and_hms(2001, 1, 1, 0, 0, 0).unwrap();
+    pinboard
+        .cached_data
+        .pins
+        .as_mut()
+        .unwrap()
+        .insert(0, CachedPin::new(pin));
+    let news = pinboard.saved_search_news().unwrap();
+    assert_eq!(1, news.len());
+    assert_eq!("freebsd", news[0].name);
+    assert_eq!(
+        vec!["https://www.freebsd.org/releases/"],
+        news[0]
+            .new_pins
+            .iter()
+            .map(|p| p.url.as_ref())
+            .collect::<Vec<&str>>()
+    );
+    assert!(pinboard.saved_search_news().unwrap().is_empty());
+
+    // Saved searches are encrypted along with the rest of the cache
+    pinboard
+        .set_cache_key(Some(CacheKey::from_bytes([7; 32])))
+        .unwrap();
+    assert_eq!(2, pinboard.saved_searches().unwrap().len());
+
+    assert!(pinboard.delete_saved_search("unread").unwrap());
+    assert!(!pinboard.delete_saved_search("unread").unwrap());
+    assert_eq!(
+        vec!["freebsd"],
+        pinboard
+            .saved_searches()
+            .unwrap()
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect::<Vec<&str>>()
+    );
+}
+
+#[test]
+fn search_regex_test() {
+    let _ = env_logger::try_init();
+    debug!("search_regex_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    let rust_tagged = pinboard.search_query("tag:rust").unwrap().unwrap().len();
+
+    pinboard.enable_regex_search(true);
+    // Regex search takes precedence over fuzzy search
+    pinboard.enable_fuzzy_search(true);
+    pinboard.enable_tag_only_search(true);
+    let found = pinboard.search_items("^rust$").unwrap().unwrap();
+    assert_eq!(rust_tagged, found.len());
+    assert!(pinboard.search_items("^rus$").unwrap().is_none());
+    pinboard.enable_tag_only_search(false);
+    assert!(pinboard.search_items("^rust$").unwrap().unwrap().len() >= rust_tagged);
+
+    let found = pinboard
+        .search(&["freebsd", r"zfs|\bnas\b"], &[SearchType::TitleOnly])
+        .unwrap()
+        .unwrap();
+    assert!(!found.is_empty());
+    let title = Regex::new(r"(?i)freebsd").unwrap();
+    let other = Regex::new(r"(?i)zfs|\bnas\b").unwrap();
+    assert!(found
+        .iter()
+        .all(|p| title.is_match(&p.title) && other.is_match(&p.title)));
+
+    let tags = pinboard.search_list_of_tags("^free").unwrap().unwrap();
+    assert!(tags.iter().all(|t| t.0.to_lowercase().starts_with("free")));
+    assert!(tags.iter().any(|t| t.0 == "freebsd"));
+    assert!(pinboard.search_list_of_tags("bsd$").unwrap().is_some());
+
+    let e = pinboard
+        .search_items("rust(")
+        .expect_err("Accepted bad regex");
+    match e.downcast_ref::<RegexError>() {
+        Some(RegexError::Invalid { pattern, .. }) => assert_eq!("rust(", pattern),
+        r => panic!("unexpected {:?}", r),
+    }
+    assert!(pinboard.search(&["ok", "[z-a]"], &[]).is_err());
+    let e = pinboard
+        .search_list_of_tags("a{1000}{1000}")
+        .expect_err("Accepted huge regex");
+    assert!(e.downcast_ref::<RegexError>().is_some());
+}
+
+#[test]
+fn search_diacritic_insensitive_test() {
+    let _ = env_logger::try_init();
+    debug!("search_diacritic_insensitive_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+
+    // Full-width chars and case are always normalized
+    let rust = pinboard.search(&["rust"], &[]).unwrap().unwrap().len();
+    assert_eq!(
+        rust,
+        pinboard.search(&["ＲＵＳＴ"], &[]).unwrap().unwrap().len()
+    );
+    assert!(pinboard
+        .search(&["\u{0622}\u{0645}\u{0648}"], &[SearchType::TagOnly])
+        .unwrap()
+        .is_some());
+
+    // آموزشی starts with an alef with a madda above, which is a diacritic.
+    let plain_alef = "\u{0627}\u{0645}\u{0648}\u{0632}\u{0634}\u{06cc}";
+    assert!(pinboard.search(&[plain_alef], &[]).unwrap().is_none());
+    assert!(pinboard.search_list_of_tags(plain_alef).unwrap().is_none());
+    pinboard.cached_data.pins.as_mut().unwrap().push(CachedPin::new(
+        PinBuilder::new("https://example.com/cr\u{e8}me-br\u{fb}l\u{e9}e", "Dessert").into_pin(),
+    ));
+    pinboard.cached_data.pins.as_mut().unwrap().push(CachedPin::new(
+        PinBuilder::new("https://example.com/caf\u{e9}", "Coffee").into_pin(),
+    ));
+    assert!(pinboard.search_items("creme").unwrap().is_none());
+    assert!(pinboard.search_query("url:cafe").unwrap().is_none());
+    assert!(pinboard.search_query("url:ＣＡＦ\u{c9}").unwrap().is_some());
+
+    pinboard.enable_diacritic_insensitive_search(true);
+    assert!(pinboard
+        .cached_data
+        .index
+        .as_ref()
+        .unwrap()
+        .strips_diacritics());
+    let pins = pinboard
+        .search(&[plain_alef], &[SearchType::TagOnly])
+        .unwrap()
+        .expect("Couldn't find pins without the madda");
+    assert_eq!(1, pins.len());
+    assert!(pins[0].tags.contains("\u{0622}\u{0645}\u{0648}"));
+    assert_eq!(
+        "\u{0622}\u{0645}\u{0648}\u{0632}\u{0634}\u{06cc}",
+        pinboard.search_list_of_tags(plain_alef).unwrap().unwrap()[0].0
+    );
+    assert!(pinboard.search_items(plain_alef).unwrap().is_some());
+    // Urls too, whichever way they are searched
+    assert!(pinboard.search_items("creme").unwrap().is_some());
+    assert!(pinboard
+        .search(&["creme"], &[SearchType::UrlOnly])
+        .unwrap()
+        .is_some());
+    assert!(pinboard.search_ranked(&["creme"], &[]).unwrap().is_some());
+    assert!(pinboard.search_query("url:cafe").unwrap().is_some());
+    assert!(pinboard.search_query("cafe").unwrap().is_some());
+    assert!(pinboard
+        .search_ranked(&[plain_alef], &[])
+        .unwrap()
+        .is_some());
+    assert!(pinboard
+        .search_query(format!("tag:{}", plain_alef))
+        .unwrap()
+        .is_some());
+    // Other searches are unaffected
+    assert_eq!(
+        rust,
+        pinboard.search(&["rust"], &[]).unwrap().unwrap().len()
+    );
+    assert!(pinboard.search(&["世"], &[]).unwrap().is_some());
+
+    pinboard.enable_diacritic_insensitive_search(false);
+    assert!(pinboard.search(&[plain_alef], &[]).unwrap().is_none());
+}
+
+#[test]
+fn search_words_test() {
+    let _ = env_logger::try_init();
+    debug!("search_words_test: starting.");
+    let (_m1, _m2) = create_mockito_servers();
+    let mut _home = rand_temp_path();
+    _home.push(".cache");
+    let cache_path = Some(_home);
+
+    let mut pinboard =
+        Pinboard::new(include_str!("api_token.txt"), cache_path).expect("Can't setup Pinboard");
+    fn titles(pins: Option<Vec<&Pin>>) -> Vec<String> {
+        pins.unwrap_or_default()
+            .iter()
+            .map(|p| p.title.to_string())
+            .collect()
+    }
+
+    let substring = titles(pinboard.search(&["use"], &[SearchType::TitleOnly]).unwrap());
+    assert!(substring.iter().any(|t| t.ends_with("Super User")));
+    assert!(!substring.iter().any(|t| t.starts_with("Using GnuPG")));
+    let stop_words = titles(
+        pinboard
+            .search(&["with"], &[SearchType::TitleOnly])
+            .unwrap(),
+    );
+    assert!(!stop_words.is_empty());
+    assert!(pinboard
+        .search(&["plotting widgets"], &[SearchType::TitleOnly])
+        .unwrap()
+        .is_none());
+
+    pinboard.enable_word_search(true);
+    assert!(pinboard
+        .cached_data
+        .pins
+        .as_ref()
+        .unwrap()
+        .iter()
+        .any(|p| !p.title_words.is_empty()));
+    let words = titles(pinboard.search(&["use"], &[SearchType::TitleOnly]).unwrap());
+    assert!(!words.iter().any(|t| t.ends_with("Super User")));
+    assert!(words.iter().any(|t| t.starts_with("Using GnuPG")));
+    assert!(words.iter().any(|t| t.starts_with("How to use a GPG key")));
+    assert_eq!(
+        vec!["Qt Plotting Widget QCustomPlot - Introduction"],
+        titles(
+            pinboard
+                .search(&["plotting widgets"], &[SearchType::TitleOnly])
+                .unwrap()
+        )
+    );
+    // Each query has to match on its own
+    assert!(pinboard
+        .search(&["plotting", "zfs"], &[SearchType::TitleOnly])
+        .unwrap()
+        .is_none());
+    // Descriptions are searched by words too
+    let linked = titles(pinboard.search_items("links").unwrap());
+    assert!(linked
+        .iter()
+        .any(|t| t.starts_with("Cross-compile and link")));
+    assert!(linked.iter().any(|t| t.starts_with("linux - SSH access")));
+    // Tags and urls are still searched as substrings
+    assert!(pinboard
+        .search(&["openpg"], &[SearchType::TagOnly, SearchType::UrlOnly])
+        .unwrap()
+        .is_some());
+    // Queries made of stop words only are searched as substrings
+    assert_eq!(
+        stop_words,
+        titles(
+            pinboard
+                .search(&["with"], &[SearchType::TitleOnly])
+                .unwrap()
+        )
+    );
+
+    // Fuzzy search takes precedence
+    pinboard.enable_fuzzy_search(true);
+    assert!(
+        titles(pinboard.search(&["use"], &[SearchType::TitleOnly]).unwrap())
+            .iter()
+            .any(|t| t.ends_with("Super User"))
+    );
+    pinboard.enable_fuzzy_search(false);
+
+    pinboard.set_language(Language::German);
+    assert_eq!(Some(Language::German), pinboard.cached_data.word_language());
+    pinboard.enable_word_search(false);
+    assert_eq!(None, pinboard.cached_data.word_language());
+    assert!(pinboard
+        .cached_data
+        .pins
+        .as_ref()
+        .unwrap()
+        .iter()
+        .all(|p| p.title_words.is_empty()));
+    assert_eq!(
+        substring,
+        titles(pinboard.search(&["use"], &[SearchType::TitleOnly]).unwrap())
+    );
+}
+
 #[test]
 fn serde_update_cache() {
     let _ = env_logger::try_init();
@@ -1024,3 +2488,169 @@ fn bench_search_fuzzy(b: &mut Bencher) {
             .unwrap_or_else(|e| panic!(e));
     });
 }
+
+/// Number of pins in the synthetic cache used by the `bench_*_50k_*` cases.
+#[cfg(feature = "bench")]
+const SYNTHETIC_PINS: usize = 50_000;
+
+/// Pinboard whose cache holds `SYNTHETIC_PINS` generated pins along with the words they are
+/// made of. Only the first 1000 words are used as tags.
+#[cfg(feature = "bench")]
+fn synthetic_pinboard(indexed: bool) -> (Pinboard<'static, 'static>, Vec<String>) {
+    let (_m1, _m2) = create_mockito_servers();
+    let mut cache_path = rand_temp_path();
+    cache_path.push(".cache");
+    let mut pinboard = Pinboard::new(include_str!("api_token.txt"), Some(cache_path))
+        .expect("Can't setup Pinboard");
+
+    // xorshift, so every run gets the same data
+    let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move |n: usize| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed % n as u64) as usize
+    };
+    let words = (0..5000)
+        .map(|_| {
+            let len = 3 + next(7);
+            (0..len)
+                .map(|_| (b'a' + next(26) as u8) as char)
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>();
+    let mut sentence = |count: usize, vocabulary: usize| {
+        (0..count)
+            .map(|_| words[next(vocabulary)].as_str())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    };
+
+    let pins = (0..SYNTHETIC_PINS)
+        .map(|i| {
+            let url = format!(
+                "https://{}.com/{}/{}",
+                sentence(1, 5000),
+                sentence(1, 5000),
+                i
+            );
+            let pin = PinBuilder::new(url, sentence(6, 5000))
+                .tags(sentence(3, 1000))
+                .description(sentence(12, 5000))
+                .into_pin();
+            CachedPin::new(pin)
+        })
+        .collect::<Vec<CachedPin>>();
+    let tags = words[..1000]
+        .iter()
+        .map(|w| CachedTag {
+            tag: Tag(w.clone(), TagFreq::Used(1)),
+            tag_lowered: w.clone(),
+        })
+        .collect::<Vec<CachedTag>>();
+    pinboard.cached_data.pins = Some(pins);
+    pinboard.cached_data.tags = Some(tags);
+    if indexed {
+        pinboard.cached_data.rebuild_index();
+    } else {
+        pinboard.cached_data.index = None;
+    }
+    (pinboard, words)
+}
+
+#[cfg(feature = "bench")]
+fn bench_search_50k(b: &mut Bencher, indexed: bool, fuzzy: bool) {
+    let (mut pinboard, words) = synthetic_pinboard(indexed);
+    pinboard.enable_fuzzy_search(fuzzy);
+    let queries = [words[42].as_str(), words[7].as_str()];
+    b.iter(|| {
+        let _pins = pinboard.search(&queries, &[]).unwrap_or_else(|e| panic!(e));
+    });
+}
+
+#[cfg(feature = "bench")]
+#[bench]
+fn bench_search_50k_indexed(b: &mut Bencher) {
+    let _ = env_logger::try_init();
+    debug!("bench_search_50k_indexed: starting.");
+    bench_search_50k(b, true, false);
+}
+
+#[cfg(feature = "bench")]
+#[bench]
+fn bench_search_50k_scan(b: &mut Bencher) {
+    let _ = env_logger::try_init();
+    debug!("bench_search_50k_scan: starting.");
+    bench_search_50k(b, false, false);
+}
+
+#[cfg(feature = "bench")]
+#[bench]
+fn bench_search_fuzzy_50k_indexed(b: &mut Bencher) {
+    let _ = env_logger::try_init();
+    debug!("bench_search_fuzzy_50k_indexed: starting.");
+    bench_search_50k(b, true, true);
+}
+
+#[cfg(feature = "bench")]
+#[bench]
+fn bench_search_fuzzy_50k_scan(b: &mut Bencher) {
+    let _ = env_logger::try_init();
+    debug!("bench_search_fuzzy_50k_scan: starting.");
+    bench_search_50k(b, false, true);
+}
+
+#[cfg(feature = "bench")]
+fn bench_search_items_50k(b: &mut Bencher, indexed: bool) {
+    let (mut pinboard, words) = synthetic_pinboard(indexed);
+    pinboard.enable_fuzzy_search(false);
+    pinboard.enable_tag_only_search(false);
+    let query = words[42].as_str();
+    b.iter(|| {
+        let _pins = pinboard.search_items(query).unwrap_or_else(|e| panic!(e));
+    });
+}
+
+#[cfg(feature = "bench")]
+#[bench]
+fn bench_search_items_50k_indexed(b: &mut Bencher) {
+    let _ = env_logger::try_init();
+    debug!("bench_search_items_50k_indexed: starting.");
+    bench_search_items_50k(b, true);
+}
+
+#[cfg(feature = "bench")]
+#[bench]
+fn bench_search_items_50k_scan(b: &mut Bencher) {
+    let _ = env_logger::try_init();
+    debug!("bench_search_items_50k_scan: starting.");
+    bench_search_items_50k(b, false);
+}
+
+#[cfg(feature = "bench")]
+fn bench_search_list_of_tags_50k(b: &mut Bencher, indexed: bool) {
+    let (mut pinboard, words) = synthetic_pinboard(indexed);
+    pinboard.enable_fuzzy_search(false);
+    let query = words[42].as_str();
+    b.iter(|| {
+        let _tags = pinboard
+            .search_list_of_tags(query)
+            .unwrap_or_else(|e| panic!(e));
+    });
+}
+
+#[cfg(feature = "bench")]
+#[bench]
+fn bench_search_list_of_tags_50k_indexed(b: &mut Bencher) {
+    let _ = env_logger::try_init();
+    debug!("bench_search_list_of_tags_50k_indexed: starting.");
+    bench_search_list_of_tags_50k(b, true);
+}
+
+#[cfg(feature = "bench")]
+#[bench]
+fn bench_search_list_of_tags_50k_scan(b: &mut Bencher) {
+    let _ = env_logger::try_init();
+    debug!("bench_search_list_of_tags_50k_scan: starting.");
+    bench_search_list_of_tags_50k(b, false);
+}
@@ -0,0 +1,229 @@
+//! Hierarchical view of tags.
+//!
+//! Tags like `lang/rust` and `lang/go` are split on a separator (`/` by default) into paths,
+//! making a tree where `lang` is the parent of `rust` and `go`. A node exists for every prefix
+//! of a tag even if the user has no tag spelled that way. Paths are compared like tags are, so
+//! `Lang/Rust` is in the `lang` subtree.
+use std::collections::BTreeMap;
+
+use super::normalize::normalize;
+use super::tag::{Tag, TagFreq};
+
+/// A node of the tag tree and the nodes under it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagNode<'a> {
+    /// Full path of the node, e.g. `lang/rust`.
+    pub path: String,
+    /// Last segment of the path, e.g. `rust`.
+    pub name: String,
+    /// The user's tag spelled as the path, if any.
+    pub tag: Option<&'a Tag>,
+    /// Number of bookmarks using the tag or any tag under it, counting a bookmark once per tag.
+    pub total: usize,
+    /// Nodes directly under this one, sorted by name.
+    pub children: Vec<TagNode<'a>>,
+}
+
+impl<'a> TagNode<'a> {
+    /// All tags of the subtree, parents before their children.
+    pub fn tags(&self) -> Vec<&'a Tag> {
+        let mut tags = self.tag.into_iter().collect::<Vec<&Tag>>();
+        for child in &self.children {
+            tags.extend(child.tags());
+        }
+        tags
+    }
+}
+
+#[derive(Debug, Default)]
+struct Branch<'a> {
+    path: String,
+    name: String,
+    tag: Option<&'a Tag>,
+    children: BTreeMap<String, Branch<'a>>,
+}
+
+impl<'a> Branch<'a> {
+    fn into_node(self) -> TagNode<'a> {
+        let children = self
+            .children
+            .into_iter()
+            .map(|(_, branch)| branch.into_node())
+            .collect::<Vec<TagNode>>();
+        let own = match self.tag {
+            Some(&Tag(_, TagFreq::Used(n))) => n,
+            _ => 0,
+        };
+        TagNode {
+            total: own + children.iter().map(|c| c.total).sum::<usize>(),
+            path: self.path,
+            name: self.name,
+            tag: self.tag,
+            children,
+        }
+    }
+}
+
+/// Normalized segments of `path`.
+fn keys(path: &str, separator: &str, strip_diacritics: bool) -> Vec<String> {
+    path.split(separator)
+        .map(|s| normalize(s, strip_diacritics))
+        .collect()
+}
+
+/// Returns true if `tag` is `path` or nested under it.
+pub fn is_under(tag: &str, path: &str, separator: &str, strip_diacritics: bool) -> bool {
+    let path = keys(path, separator, strip_diacritics);
+    let tag = keys(tag, separator, strip_diacritics);
+    tag.len() >= path.len() && tag[..path.len()] == path[..]
+}
+
+/// Name `tag` would get if the `old` subtree it is in was renamed to `new`.
+pub fn renamed(tag: &str, old: &str, new: &str, separator: &str) -> String {
+    let depth = old.split(separator).count();
+    let rest = tag.split(separator).skip(depth).collect::<Vec<&str>>();
+    if rest.is_empty() {
+        new.to_string()
+    } else {
+        [new, &rest.join(separator)].join(separator)
+    }
+}
+
+/// Builds the trees of `tags`, sorted by name.
+///
+/// Path segments are spelled as in the first tag going through them.
+pub fn build<'a, I>(tags: I, separator: &str, strip_diacritics: bool) -> Vec<TagNode<'a>>
+where
+    I: IntoIterator<Item = &'a Tag>,
+{
+    let mut root = Branch::default();
+    for tag in tags {
+        let mut branch = &mut root;
+        for (i, (name, key)) in tag
+            .0
+            .split(separator)
+            .zip(keys(&tag.0, separator, strip_diacritics))
+            .enumerate()
+        {
+            let path = if i == 0 {
+                name.to_string()
+            } else {
+                [branch.path.as_str(), name].join(separator)
+            };
+            branch = branch.children.entry(key).or_insert_with(|| Branch {
+                path,
+                name: name.to_string(),
+                ..Default::default()
+            });
+        }
+        if branch.tag.is_none() {
+            branch.tag = Some(tag);
+        }
+    }
+    root.into_node().children
+}
+
+/// The node at `path` in the trees of `tags`.
+pub fn subtree<'a, I>(
+    tags: I,
+    path: &str,
+    separator: &str,
+    strip_diacritics: bool,
+) -> Option<TagNode<'a>>
+where
+    I: IntoIterator<Item = &'a Tag>,
+{
+    let tags = tags
+        .into_iter()
+        .filter(|t| is_under(&t.0, path, separator, strip_diacritics));
+    let mut nodes = build(tags, separator, strip_diacritics);
+    let depth = path.split(separator).count();
+    for _ in 1..depth {
+        // Only the nodes along `path` are left.
+        nodes = nodes.pop()?.children;
+    }
+    nodes.pop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger;
+
+    fn tags() -> Vec<Tag> {
+        vec![
+            Tag::new("lang/rust".to_string(), 10),
+            Tag::new("lang/go".to_string(), 2),
+            Tag::new("Lang/Rust/async".to_string(), 3),
+            Tag::new("proj/x".to_string(), 1),
+            Tag::new("lang".to_string(), 4),
+            Tag::new("misc".to_string(), 5),
+        ]
+    }
+
+    fn names(nodes: &[TagNode]) -> Vec<String> {
+        nodes.iter().map(|n| n.path.clone()).collect()
+    }
+
+    #[test]
+    fn builds_tree() {
+        let _ = env_logger::try_init();
+        debug!("builds_tree: starting");
+        let tags = tags();
+        let roots = build(&tags, "/", false);
+        assert_eq!(vec!["lang", "misc", "proj"], names(&roots));
+
+        let lang = &roots[0];
+        assert_eq!(Some(&tags[4]), lang.tag);
+        assert_eq!(19, lang.total);
+        assert_eq!(vec!["lang/go", "lang/rust"], names(&lang.children));
+        let rust = &lang.children[1];
+        assert_eq!(13, rust.total);
+        assert_eq!("rust", rust.name);
+        assert_eq!(vec!["lang/rust/async"], names(&rust.children));
+        assert_eq!("async", rust.children[0].name);
+        assert_eq!(
+            vec!["lang", "lang/go", "lang/rust", "Lang/Rust/async"],
+            lang.tags().iter().map(|t| t.0.as_str()).collect::<Vec<&str>>()
+        );
+
+        // Prefixes without a tag of their own
+        let proj = &roots[2];
+        assert_eq!(None, proj.tag);
+        assert_eq!(1, proj.total);
+    }
+
+    #[test]
+    fn finds_subtrees() {
+        let _ = env_logger::try_init();
+        debug!("finds_subtrees: starting");
+        let tags = tags();
+        let rust = subtree(&tags, "LANG/rust", "/", false).expect("subtree exists");
+        assert_eq!("lang/rust", rust.path);
+        assert_eq!(13, rust.total);
+        assert_eq!(1, rust.children.len());
+        assert!(subtree(&tags, "lang/rust/sync", "/", false).is_none());
+        assert!(subtree(&tags, "lan", "/", false).is_none());
+
+        let tags = vec![Tag::new("lang::rust".to_string(), 1)];
+        assert_eq!(
+            "lang::rust",
+            subtree(&tags, "lang", "::", false).unwrap().children[0].path
+        );
+    }
+
+    #[test]
+    fn renames_and_checks_paths() {
+        let _ = env_logger::try_init();
+        debug!("renames_and_checks_paths: starting");
+        assert!(is_under("Lang/Rust/async", "lang/rust", "/", false));
+        assert!(is_under("lang", "lang", "/", false));
+        assert!(!is_under("language/rust", "lang", "/", false));
+        assert!(!is_under("lang", "lang/rust", "/", false));
+        assert!(is_under("café/x", "cafe", "/", true));
+
+        assert_eq!("code/rust/async", renamed("Lang/rust/async", "lang", "code", "/"));
+        assert_eq!("code", renamed("lang", "lang", "code", "/"));
+        assert_eq!("a/b/x", renamed("lang/x", "lang", "a/b", "/"));
+    }
+}
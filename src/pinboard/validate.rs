@@ -0,0 +1,192 @@
+//! Checks of bookmarks against Pinboard's limits, done before sending them.
+//!
+//! Pinboard rejects or silently mangles bookmarks that don't fit its limits: a title of at
+//! most 255 characters, a description of at most 65536 characters and at most 100 tags of up
+//! to 255 characters each, without commas or whitespace.
+use std::fmt;
+
+use super::normalize;
+use super::pin::Pin;
+
+pub const MAX_TITLE_LEN: usize = 255;
+pub const MAX_DESCRIPTION_LEN: usize = 65_536;
+pub const MAX_TAG_LEN: usize = 255;
+pub const MAX_TAGS: usize = 100;
+
+/// A reason for Pinboard to refuse a bookmark.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    EmptyTitle,
+    /// Length of the title, in characters.
+    TitleTooLong(usize),
+    /// Length of the description, in characters.
+    DescriptionTooLong(usize),
+    /// Number of tags.
+    TooManyTags(usize),
+    TagTooLong(String),
+    /// Tag containing a comma.
+    TagWithComma(String),
+    /// Tag appearing again, maybe in a different case.
+    DuplicateTag(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::EmptyTitle => write!(f, "title is empty"),
+            ValidationError::TitleTooLong(n) => {
+                write!(f, "title has {} characters, at most {} are allowed", n, MAX_TITLE_LEN)
+            }
+            ValidationError::DescriptionTooLong(n) => write!(
+                f,
+                "description has {} characters, at most {} are allowed",
+                n, MAX_DESCRIPTION_LEN
+            ),
+            ValidationError::TooManyTags(n) => {
+                write!(f, "{} tags, at most {} are allowed", n, MAX_TAGS)
+            }
+            ValidationError::TagTooLong(ref t) => write!(
+                f,
+                "tag {} has {} characters, at most {} are allowed",
+                t,
+                t.chars().count(),
+                MAX_TAG_LEN
+            ),
+            ValidationError::TagWithComma(ref t) => write!(f, "tag {} contains a comma", t),
+            ValidationError::DuplicateTag(ref t) => write!(f, "tag {} is repeated", t),
+        }
+    }
+}
+
+/// All the reasons a bookmark would be refused.
+#[derive(Debug, Clone, PartialEq, Eq, Fail)]
+pub struct InvalidPin {
+    pub errors: Vec<ValidationError>,
+}
+
+impl fmt::Display for InvalidPin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let errors = self
+            .errors
+            .iter()
+            .map(ValidationError::to_string)
+            .collect::<Vec<String>>();
+        write!(f, "invalid bookmark: {}", errors.join(", "))
+    }
+}
+
+/// `tags` separated by single spaces, split on commas too, without repeated tags: only the
+/// first spelling of tags differing by case is kept.
+pub fn normalize_tags(tags: &str) -> String {
+    let mut kept: Vec<&str> = Vec::new();
+    for tag in tags.split(|c: char| c == ',' || c.is_whitespace()) {
+        if !tag.is_empty() && !kept.iter().any(|t| normalize::eq(t, tag)) {
+            kept.push(tag);
+        }
+    }
+    kept.join(" ")
+}
+
+/// Checks `pin` against Pinboard's limits.
+pub fn check(pin: &Pin) -> Result<(), InvalidPin> {
+    let mut errors = Vec::new();
+
+    let title_len = pin.title.chars().count();
+    if pin.title.trim().is_empty() {
+        errors.push(ValidationError::EmptyTitle);
+    } else if title_len > MAX_TITLE_LEN {
+        errors.push(ValidationError::TitleTooLong(title_len));
+    }
+    if let Some(ref extended) = pin.extended {
+        let len = extended.chars().count();
+        if len > MAX_DESCRIPTION_LEN {
+            errors.push(ValidationError::DescriptionTooLong(len));
+        }
+    }
+
+    let tags = pin.tags.split_whitespace().collect::<Vec<&str>>();
+    if tags.len() > MAX_TAGS {
+        errors.push(ValidationError::TooManyTags(tags.len()));
+    }
+    for (i, tag) in tags.iter().enumerate() {
+        if tag.chars().count() > MAX_TAG_LEN {
+            errors.push(ValidationError::TagTooLong(tag.to_string()));
+        }
+        if tag.contains(',') {
+            errors.push(ValidationError::TagWithComma(tag.to_string()));
+        }
+        if tags[..i].iter().any(|t| normalize::eq(t, tag)) {
+            errors.push(ValidationError::DuplicateTag(tag.to_string()));
+        }
+    }
+
+    match errors.len() {
+        0 => Ok(()),
+        _ => Err(InvalidPin { errors }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use env_logger;
+
+    #[test]
+    fn normalizes_tags() {
+        let _ = env_logger::try_init();
+        debug!("normalizes_tags: starting");
+        assert_eq!("rust async", normalize_tags("rust,async"));
+        assert_eq!("rust async", normalize_tags("  rust, async ,,Rust\tRUST "));
+        assert_eq!("Ärger", normalize_tags("Ärger ärger"));
+        assert_eq!("", normalize_tags(" , "));
+    }
+
+    #[test]
+    fn checks_limits() {
+        let _ = env_logger::try_init();
+        debug!("checks_limits: starting");
+        let mut pin = PinBuilder::new("https://a.com/", "title")
+            .tags("rust async")
+            .description("desc")
+            .into_pin();
+        assert_eq!(Ok(()), check(&pin));
+
+        pin.title = "é".repeat(MAX_TITLE_LEN).into();
+        assert_eq!(Ok(()), check(&pin));
+        pin.title = "é".repeat(MAX_TITLE_LEN + 1).into();
+        pin.extended = Some("x".repeat(MAX_DESCRIPTION_LEN + 1).into());
+        pin.tags = format!("a,b {} rust Rust", "t".repeat(MAX_TAG_LEN + 1)).into();
+        let errors = check(&pin).unwrap_err().errors;
+        assert_eq!(
+            vec![
+                ValidationError::TitleTooLong(MAX_TITLE_LEN + 1),
+                ValidationError::DescriptionTooLong(MAX_DESCRIPTION_LEN + 1),
+                ValidationError::TagWithComma("a,b".into()),
+                ValidationError::TagTooLong("t".repeat(MAX_TAG_LEN + 1)),
+                ValidationError::DuplicateTag("Rust".into()),
+            ],
+            errors
+        );
+
+        pin.title = " ".into();
+        pin.extended = None;
+        pin.tags = (0..=MAX_TAGS)
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+            .into();
+        let error = check(&pin).unwrap_err();
+        assert_eq!(
+            vec![
+                ValidationError::EmptyTitle,
+                ValidationError::TooManyTags(MAX_TAGS + 1)
+            ],
+            error.errors
+        );
+        assert_eq!(
+            "invalid bookmark: title is empty, 101 tags, at most 100 are allowed",
+            error.to_string()
+        );
+    }
+}
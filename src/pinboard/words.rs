@@ -0,0 +1,202 @@
+//! Word level analysis of texts for word search.
+//!
+//! A text is split into words at every char that is neither a letter nor a digit. Common words
+//! of the search language (stop words, e.g. `the`, `of`) are dropped and the remaining ones are
+//! reduced to their stem with a Snowball stemmer, so `running`, `runs` and `run` are all `run`.
+//! Texts are expected to be normalized already.
+use std::collections::HashSet;
+
+use rust_stemmers::{Algorithm, Stemmer};
+
+use super::normalize::normalize;
+
+/// Language used to stem words and to pick the stop words to ignore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    Dutch,
+    #[default]
+    English,
+    French,
+    German,
+    Italian,
+    Portuguese,
+    Russian,
+    Spanish,
+    Swedish,
+}
+
+impl Language {
+    fn algorithm(self) -> Algorithm {
+        match self {
+            Language::Dutch => Algorithm::Dutch,
+            Language::English => Algorithm::English,
+            Language::French => Algorithm::French,
+            Language::German => Algorithm::German,
+            Language::Italian => Algorithm::Italian,
+            Language::Portuguese => Algorithm::Portuguese,
+            Language::Russian => Algorithm::Russian,
+            Language::Spanish => Algorithm::Spanish,
+            Language::Swedish => Algorithm::Swedish,
+        }
+    }
+
+    fn stop_words(self) -> &'static [&'static str] {
+        match self {
+            Language::Dutch => &[
+                "de", "en", "van", "ik", "te", "dat", "die", "in", "een", "hij", "het", "niet",
+                "zijn", "is", "was", "op", "aan", "met", "als", "voor", "had", "er", "maar", "om",
+                "hem", "dan", "zou", "of", "wat", "mijn", "men", "dit", "zo", "door", "over", "ze",
+                "bij", "ook", "tot", "je", "naar",
+            ],
+            Language::English => &[
+                "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "from", "has",
+                "have", "he", "her", "his", "how", "i", "if", "in", "into", "is", "it", "its",
+                "of", "on", "or", "our", "she", "so", "that", "the", "their", "them", "then",
+                "there", "these", "they", "this", "to", "was", "we", "were", "what", "when",
+                "which", "who", "will", "with", "you", "your",
+            ],
+            Language::French => &[
+                "au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "et",
+                "eux", "il", "je", "la", "le", "les", "leur", "lui", "ma", "mais", "me", "même",
+                "mes", "moi", "mon", "ne", "nos", "notre", "nous", "on", "ou", "par", "pas",
+                "pour", "qu", "que", "qui", "sa", "se", "ses", "son", "sur", "ta", "te", "tes",
+                "toi", "ton", "tu", "un", "une", "vos", "votre", "vous", "à", "est",
+            ],
+            Language::German => &[
+                "aber", "alle", "als", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis",
+                "das", "dass", "dem", "den", "der", "des", "die", "du", "ein", "eine", "einem",
+                "einen", "einer", "er", "es", "für", "hat", "ich", "im", "in", "ist", "mit",
+                "nach", "nicht", "noch", "oder", "sie", "sich", "sind", "so", "um", "und", "uns",
+                "von", "vor", "war", "wie", "wir", "zu", "zum", "zur",
+            ],
+            Language::Italian => &[
+                "a", "ad", "al", "alla", "che", "chi", "ci", "con", "da", "dal", "dei", "del",
+                "della", "di", "e", "gli", "ha", "ho", "i", "il", "in", "io", "la", "le", "lo",
+                "ma", "mi", "ne", "nel", "non", "o", "per", "più", "se", "si", "su", "sul", "tu",
+                "un", "una", "uno", "è",
+            ],
+            Language::Portuguese => &[
+                "a", "ao", "as", "com", "como", "da", "das", "de", "do", "dos", "e", "ela", "ele",
+                "em", "eu", "foi", "há", "isso", "já", "mais", "mas", "na", "nas", "no", "nos",
+                "não", "o", "os", "ou", "para", "pela", "pelo", "por", "que", "se", "seu", "sua",
+                "são", "um", "uma", "é",
+            ],
+            Language::Russian => &[
+                "а", "без", "бы", "в", "во", "вы", "да", "для", "до", "его", "ее", "если", "же",
+                "за", "и", "из", "или", "им", "их", "к", "как", "ли", "мы", "на", "не", "но", "о",
+                "об", "он", "она", "они", "от", "по", "с", "со", "так", "то", "ты", "у", "что",
+                "это", "я",
+            ],
+            Language::Spanish => &[
+                "a", "al", "como", "con", "de", "del", "el", "en", "es", "esta", "este", "la",
+                "las", "le", "les", "lo", "los", "mas", "me", "mi", "no", "o", "para", "pero",
+                "por", "que", "se", "si", "sin", "su", "sus", "te", "tu", "un", "una", "uno", "y",
+                "ya", "él", "más",
+            ],
+            Language::Swedish => &[
+                "och", "det", "att", "i", "en", "jag", "hon", "som", "han", "på", "den", "med",
+                "var", "sig", "för", "så", "till", "är", "men", "ett", "om", "hade", "de", "av",
+                "icke", "mig", "du", "henne", "då", "sin", "nu", "har", "inte", "hans", "honom",
+                "skulle", "hennes", "där", "min", "man", "ej", "vid", "kunde",
+            ],
+        }
+    }
+}
+
+/// Splits normalized texts into stemmed words, leaving stop words out.
+pub struct Analyzer {
+    stemmer: Stemmer,
+    stop_words: HashSet<String>,
+}
+
+impl Analyzer {
+    /// Creates an analyzer for texts in `language`, normalized with the given
+    /// `strip_diacritics` setting.
+    pub fn new(language: Language, strip_diacritics: bool) -> Self {
+        Analyzer {
+            stemmer: Stemmer::create(language.algorithm()),
+            stop_words: language
+                .stop_words()
+                .iter()
+                .map(|w| normalize(w, strip_diacritics))
+                .collect(),
+        }
+    }
+
+    fn stem(&self, word: &str) -> String {
+        self.stemmer.stem(word).into_owned()
+    }
+
+    /// Stems of the words of `text` that aren't stop words, sorted and without duplicates.
+    pub fn words(&self, text: &str) -> Vec<String> {
+        let mut words = split(text)
+            .filter(|w| !self.stop_words.contains(*w))
+            .map(|w| self.stem(w))
+            .collect::<Vec<String>>();
+        words.sort_unstable();
+        words.dedup();
+        words
+    }
+}
+
+/// Words of `text`, i.e. its runs of letters and digits.
+fn split(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+}
+
+/// Returns true if `words` (sorted) contains all of `wanted`. Empty `wanted` never matches.
+pub fn contains_all(words: &[String], wanted: &[String]) -> bool {
+    !wanted.is_empty() && wanted.iter().all(|w| words.binary_search(w).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use env_logger;
+
+    #[test]
+    fn stems_and_drops_stop_words() {
+        let _ = env_logger::try_init();
+        debug!("stems_and_drops_stop_words: starting");
+        let english = Analyzer::new(Language::English, false);
+        assert_eq!(vec!["run"], english.words("running"));
+        assert_eq!(english.words("runs"), english.words("running"));
+        assert_eq!(vec!["brunch"], english.words("brunch"));
+        assert_eq!(
+            vec!["book", "littl", "macro", "rust"],
+            english.words("the little book of rust macros")
+        );
+        assert_eq!(
+            vec!["c", "cross", "link"],
+            english.words("cross-link (c/c++)")
+        );
+        assert!(english.words("").is_empty());
+        assert!(english.words("the who").is_empty());
+
+        let german = Analyzer::new(Language::German, false);
+        assert_eq!(vec!["haus"], german.words("die häuser"));
+        let german = Analyzer::new(Language::German, true);
+        // Stop words lose their diacritics too
+        assert_eq!(vec!["haus"], german.words(&normalize("Für Häuser", true)));
+
+        let russian = Analyzer::new(Language::Russian, false);
+        assert_eq!(russian.words("книги"), russian.words("книга и"));
+    }
+
+    #[test]
+    fn matches_all_words() {
+        let _ = env_logger::try_init();
+        debug!("matches_all_words: starting");
+        let english = Analyzer::new(Language::English, false);
+        let words = english.words("running with scissors");
+        assert!(contains_all(&words, &english.words("runs")));
+        assert!(contains_all(&words, &english.words("scissor run")));
+        assert!(!contains_all(&words, &english.words("brunch")));
+        assert!(!contains_all(
+            &english.words("brunch"),
+            &english.words("run")
+        ));
+        assert!(!contains_all(&words, &[]));
+    }
+}